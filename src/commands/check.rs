@@ -0,0 +1,136 @@
+use anyhow::Result;
+use std::thread;
+use std::time::Duration;
+
+use crate::commands::select_sessions;
+use crate::config::manager::ConfigManager;
+use crate::models::session::Session;
+use crate::utils::check::{self, CheckResult, Status};
+use crate::utils::runner;
+
+/// Sessions checked at the same time, each with an ssh of its own.
+const MAX_PARALLEL_CHECKS: usize = 16;
+
+/// Checks the selected sessions, all of them by default, a few at a time. With `watch`, the
+/// checks are repeated every `watch` seconds until interrupted.
+pub fn handle_check(
+    names: Vec<String>,
+    tags: Option<String>,
+    json: bool,
+    timeout: u64,
+    watch: Option<u64>,
+) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+
+    let sessions = select_sessions(&manager.config, &names, tags.as_ref(), true)?
+        .into_iter()
+        .map(|s| manager.config.settings.apply_defaults(s))
+        .collect::<Result<Vec<Session>>>()?;
+
+    let timeout = Duration::from_secs(timeout.max(1));
+    let Some(interval) = watch else {
+        let results = check_all(&sessions, timeout);
+        if json {
+            println!("{}", serde_json::to_string_pretty(&results)?);
+        } else {
+            print_table(&results);
+        }
+
+        let down = results.iter().filter(|r| r.status != Status::Up).count();
+        if down > 0 {
+            anyhow::bail!("{} of {} sessions are not up", down, results.len());
+        }
+        return Ok(());
+    };
+
+    loop {
+        let results = check_all(&sessions, timeout);
+        if json {
+            // one line per round, for log shippers
+            println!("{}", serde_json::to_string(&results)?);
+        } else {
+            // clear the screen and refresh the table in place
+            print!("\x1b[2J\x1b[H");
+            print_table(&results);
+            println!("\nRefreshing every {}s, press Ctrl-C to stop.", interval);
+        }
+        thread::sleep(Duration::from_secs(interval.max(1)));
+    }
+}
+
+fn check_all(sessions: &[Session], timeout: Duration) -> Vec<CheckResult> {
+    runner::parallel_map(sessions, MAX_PARALLEL_CHECKS, |session| {
+        check::check(session, timeout)
+    })
+}
+
+fn print_table(results: &[CheckResult]) {
+    println!(
+        "{:<20} {:<25} {:<12} {:<10} {:<}",
+        "Name", "Host", "Status", "Latency", "Details"
+    );
+    println!("{:-<100}", "");
+    for result in results {
+        let latency = result
+            .latency_ms
+            .map_or_else(|| "N/A".to_string(), |ms| format!("{}ms", ms));
+        let details = result
+            .error
+            .as_ref()
+            .or(result.banner.as_ref())
+            .or(result.via.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        println!(
+            "{:<20} {:<25} {:<12} {:<10} {:<}",
+            result.name,
+            format!("{}:{}", result.host, result.port),
+            result.status,
+            latency,
+            details
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::AuthType;
+    use crate::utils::runner::RecordingRunner;
+
+    #[test]
+    fn test_check_all() {
+        let sessions: Vec<Session> = ["db1", "db2", "db3"]
+            .iter()
+            .map(|name| {
+                let mut session = Session::new(
+                    name.to_string(),
+                    format!("{}.internal", name),
+                    "deploy".to_string(),
+                    22,
+                    AuthType::Key,
+                    Some("/keys/id_ed25519".into()),
+                    None,
+                    None,
+                );
+                session.extra_args = vec!["-J".to_string(), "bastion".to_string()];
+                session
+            })
+            .collect();
+
+        // the checks run in threads of their own, with the runner of the caller
+        let recorder = RecordingRunner::new(255);
+        let results = runner::with_runner(recorder.clone(), || {
+            check_all(&sessions, Duration::from_secs(1))
+        });
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|result| result.status != Status::Up));
+        let mut commands = recorder.commands();
+        commands.sort();
+        assert_eq!(commands.len(), 3);
+        for (command, session) in commands.iter().zip(&sessions) {
+            assert!(command.ends_with(&format!(" {} true", session.host)));
+        }
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+
+use crate::commands::parse_tags;
+use crate::config::manager::ConfigManager;
+use crate::models::session::Session;
+
+/// Adds a copy of the session `src` named `new`, with the given fields changed.
+pub fn handle_clone(
+    src: String,
+    new: String,
+    host: Option<String>,
+    user: Option<String>,
+    port: Option<u16>,
+    tags: Option<String>,
+    dry_run: bool,
+) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
+    manager.load()?;
+
+    let session = manager
+        .config
+        .get_session(&src)
+        .with_context(|| format!("Session '{}' not found", src))?;
+    let clone = cloned(session, &new, host, user, port, tags);
+
+    clone.validate()?;
+    manager.config.add_session(clone)?;
+    manager.save()?;
+    if !dry_run {
+        println!("Session '{}' cloned to '{}'.", src, new);
+    }
+    Ok(())
+}
+
+/// A copy of `session` named `new`, with the given fields changed.
+fn cloned(
+    session: &Session,
+    new: &str,
+    host: Option<String>,
+    user: Option<String>,
+    port: Option<u16>,
+    tags: Option<String>,
+) -> Session {
+    let mut clone = Session {
+        name: new.to_string(),
+        host: host.unwrap_or_else(|| session.host.clone()),
+        user: user.unwrap_or_else(|| session.user.clone()),
+        port: port.unwrap_or(session.port),
+        tags: tags.map_or_else(|| session.tags.clone(), |t| parse_tags(Some(&t))),
+        ..session.clone()
+    };
+    // the pinned keys are the ones of the original host
+    if (clone.host.as_str(), clone.port) != (session.host.as_str(), session.port) {
+        clone.host_key_fingerprints.clear();
+    }
+    clone
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::AuthType;
+
+    #[test]
+    fn test_cloned() {
+        let mut session = Session::new(
+            "web".to_string(),
+            "web.example.com".to_string(),
+            "deploy".to_string(),
+            22,
+            AuthType::Key,
+            Some("/keys/id_ed25519".into()),
+            None,
+            None,
+        );
+        session.host_key_fingerprints = vec!["SHA256:ed".to_string()];
+
+        let clone = cloned(
+            &session,
+            "web2",
+            None,
+            Some("admin".to_string()),
+            None,
+            None,
+        );
+        assert_eq!(clone.name, "web2");
+        assert_eq!(clone.user, "admin");
+        assert_eq!(clone.host_key_fingerprints, session.host_key_fingerprints);
+
+        let clone = cloned(
+            &session,
+            "web2",
+            Some("web2.example.com".to_string()),
+            None,
+            None,
+            None,
+        );
+        assert!(clone.host_key_fingerprints.is_empty());
+        let clone = cloned(&session, "web2", None, None, Some(2222), None);
+        assert!(clone.host_key_fingerprints.is_empty());
+        let clone = cloned(&session, "web2", None, None, Some(22), None);
+        assert_eq!(clone.host_key_fingerprints.len(), 1);
+    }
+}
@@ -1,25 +1,34 @@
 use anyhow::Result;
 use std::path::PathBuf;
 
+use crate::config::history;
 use crate::config::manager::ConfigManager;
-use crate::utils::scp;
+use crate::models::session::Session;
+use crate::utils::{hooks, protected, scp};
 
 pub fn handle_cp(
     paths: Vec<PathBuf>,
     src: Option<String>,
     dst: Option<String>,
     recursive: bool,
+    dry_run: bool,
 ) -> Result<()> {
     let mut manager = ConfigManager::new(None);
     manager.load()?;
 
+    let settings = &manager.config.settings;
     let src_session = src
         .as_ref()
-        .and_then(|name| manager.config.get_session(name));
+        .and_then(|name| manager.config.get_session(name))
+        .map(|s| settings.apply_defaults(s))
+        .transpose()?;
 
     let dst_session = dst
         .as_ref()
-        .and_then(|name| manager.config.get_session(name));
+        .and_then(|name| manager.config.get_session(name))
+        .map(|s| settings.apply_defaults(s))
+        .transpose()?;
+    let (src_session, dst_session) = (src_session.as_ref(), dst_session.as_ref());
 
     if paths.len() < 2 {
         anyhow::bail!("At least two paths are required");
@@ -31,5 +40,37 @@ pub fn handle_cp(
         .collect();
     let dst_path = paths[paths.len() - 1].as_path();
 
-    scp::copy_file(src_session, dst_session, src_path, dst_path, recursive)
+    let sessions: Vec<&Session> = src_session.into_iter().chain(dst_session).collect();
+    let copy = || {
+        hooks::around(settings, &sessions, "cp", dry_run, || {
+            match sessions.first() {
+                Some(session) if !dry_run => history::track(session, "cp", || {
+                    scp::copy_file(
+                        src_session,
+                        dst_session,
+                        src_path,
+                        dst_path,
+                        recursive,
+                        false,
+                    )
+                }),
+                _ => scp::copy_file(
+                    src_session,
+                    dst_session,
+                    src_path,
+                    dst_path,
+                    recursive,
+                    dry_run,
+                ),
+            }
+        })
+    };
+    // copying from a protected session leaves it as it is
+    match dst_session {
+        Some(session) if !dry_run && settings.is_protected(session) => {
+            protected::confirm(session, "copy to")?;
+            protected::marked(session, copy)
+        }
+        _ => copy(),
+    }
 }
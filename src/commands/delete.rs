@@ -1,44 +1,138 @@
 use anyhow::Result;
-use std::collections::HashSet;
+use dialoguer::Confirm;
+use std::io::{self, IsTerminal};
 
-use crate::config::manager::ConfigManager;
+use crate::commands;
+use crate::config::facts::FactsStore;
+use crate::config::manager::{Config, ConfigManager};
 use crate::models::session::Session;
 
-pub fn handle_delete(names: Vec<String>) -> Result<()> {
+pub fn handle_delete(
+    names: Vec<String>,
+    yes: bool,
+    allow_protected: bool,
+    dry_run: bool,
+) -> Result<()> {
     let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
     manager.load()?;
 
-    for name in names {
-        manager.config.remove_session(&name)?;
-        println!("Session '{}' deleted successfully.", name);
+    for name in names.iter() {
+        if manager.config.get_session(name).is_none() {
+            anyhow::bail!("Session '{}' not found", name);
+        }
+    }
+    if names.len() > 1 {
+        check_protected(&manager.config, &names, allow_protected)?;
+    }
+    // a dry run changes nothing, there is nothing to confirm
+    if names.len() > 1 && !yes && !dry_run && !confirm(&names)? {
+        println!("Nothing was deleted.");
+        return Ok(());
+    }
+
+    for name in names.iter() {
+        manager.config.remove_session(name)?;
     }
     manager.save()?;
+    report(&manager.config, &names, dry_run);
 
     Ok(())
 }
 
-pub fn handle_delete_with_tags(tags: String) -> Result<()> {
+pub fn handle_delete_with_tags(
+    tags: String,
+    yes: bool,
+    allow_protected: bool,
+    dry_run: bool,
+) -> Result<()> {
     let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
     manager.load()?;
 
-    let sessions: Vec<Session> = manager
+    let names: Vec<String> = manager
         .config
         .sessions
         .iter()
-        .filter(|session| {
-            let session_tags: HashSet<String> = session.tags.iter().cloned().collect();
-            if session_tags.contains(&tags) {
-                println!("Session '{}' deleted successfully.", session.name);
-                false
-            } else {
-                true
-            }
-        })
-        .cloned()
+        .filter(|session| session.tags.contains(&tags))
+        .map(|session| session.name.clone())
         .collect();
+    if names.is_empty() {
+        anyhow::bail!("No sessions found with tag '{}'", tags);
+    }
+    check_protected(&manager.config, &names, allow_protected)?;
+    if !yes && !dry_run && !confirm(&names)? {
+        println!("Nothing was deleted.");
+        return Ok(());
+    }
 
-    manager.config.sessions = sessions;
+    manager
+        .config
+        .sessions
+        .retain(|session| !names.contains(&session.name));
     manager.save()?;
+    report(&manager.config, &names, dry_run);
 
     Ok(())
 }
+
+fn check_protected(config: &Config, names: &[String], allow_protected: bool) -> Result<()> {
+    let sessions: Vec<&Session> = names
+        .iter()
+        .filter_map(|name| config.get_session(name))
+        .collect();
+    commands::check_protected(&config.settings, &sessions, "delete", allow_protected)
+}
+
+/// Asks before deleting several sessions at once, `--yes` is required without a terminal.
+fn confirm(names: &[String]) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        anyhow::bail!(
+            "Refusing to delete {} sessions without confirmation, use --yes",
+            names.len()
+        );
+    }
+    println!("Sessions to delete: {}", names.join(", "));
+    Ok(Confirm::new()
+        .with_prompt(format!("Delete {} sessions?", names.len()))
+        .default(false)
+        .interact()?)
+}
+
+/// Reports the deleted sessions `names`, with the templates left without their session,
+/// and drops their facts.
+fn report(config: &Config, names: &[String], dry_run: bool) {
+    for name in names {
+        if !dry_run {
+            println!("Session '{}' deleted successfully.", name);
+        }
+        warn_templates(config, name);
+    }
+    if dry_run {
+        return;
+    }
+    // the config is already saved by then, so a failure is only a warning
+    let mut facts = FactsStore::new(None);
+    let result = facts.load().and_then(|_| {
+        if facts.prune(config) {
+            facts.save()?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        eprintln!(
+            "Warning: failed to remove the facts of the deleted sessions: {:#}",
+            e
+        );
+    }
+}
+
+/// Warns about the templates that are based on the deleted session `name`.
+fn warn_templates(config: &Config, name: &str) {
+    for template in config.templates.iter().filter(|t| t.session == name) {
+        eprintln!(
+            "Warning: template '{}' is based on session '{}' and can no longer be used, delete it with `devlg template delete {}`",
+            template.name, name, template.name
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use anyhow::Result;
+
+use crate::config::manager::ConfigManager;
+use crate::models::session::AuthType;
+use crate::utils::doctor::{self, Finding, Severity};
+
+/// Checks the installed tools, the config file and the key of every session.
+pub fn handle_doctor() -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+    let config = &manager.config;
+
+    let mut errors = 0;
+    let mut print = |subject: &str, findings: Vec<Finding>| {
+        for finding in findings {
+            if finding.severity == Severity::Error {
+                errors += 1;
+            }
+            println!(
+                "{:<8} {:<20} {}",
+                finding.severity, subject, finding.message
+            );
+        }
+    };
+
+    let password_sessions = config
+        .sessions
+        .iter()
+        .any(|s| s.auth_type == AuthType::Password);
+    print("tools", doctor::check_tools(password_sessions));
+
+    let config_path = manager.get_config_path()?;
+    if config_path.exists() {
+        // a readable config is only an error when it leaks passwords
+        let severity = if config
+            .sessions
+            .iter()
+            .any(|s| s.password.is_some() || s.totp_secret.is_some())
+        {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        print(
+            "config",
+            vec![doctor::check_private_file(&config_path, "config", severity)],
+        );
+    }
+
+    for session in config.sessions.iter() {
+        let mut findings = Vec::new();
+        if let Err(e) = session.validate() {
+            findings.push(Finding {
+                severity: Severity::Error,
+                message: format!("{:#}", e),
+            });
+        }
+        findings.extend(doctor::check_session(session));
+        print(&session.name, findings);
+    }
+
+    if errors > 0 {
+        anyhow::bail!("Found {} problems", errors);
+    }
+    println!("No problems found.");
+    Ok(())
+}
@@ -0,0 +1,238 @@
+use anyhow::{Context, Result};
+use dialoguer::Confirm;
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, IsTerminal, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::Path;
+use std::process::Command;
+
+use crate::commands::rename::move_session_data;
+use crate::commands::select_sessions;
+use crate::config::manager::{Config, ConfigManager};
+use crate::config::transfer;
+use crate::models::session::Session;
+use crate::utils::{doctor, runner};
+
+const HEADER: &str = "\
+# Edit the sessions below, save and quit to apply the changes.
+# Each table is named after the session it edits: change `name` to rename the session, remove
+# the table to leave it as it is. Passwords are not shown, add a `password` to change one.
+
+";
+
+/// Opens the selected sessions as TOML in `$VISUAL` or `$EDITOR` and applies the changes once
+/// every session is valid. With `dry_run`, the changes are only shown.
+pub fn handle_edit(names: Vec<String>, tags: Option<String>, dry_run: bool) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
+    manager.load()?;
+    let sessions = select_sessions(&manager.config, &names, tags.as_ref(), false)?;
+
+    let mut document = BTreeMap::new();
+    for session in sessions {
+        let mut session = session.clone();
+        session.strip_secrets();
+        document.insert(session.name.clone(), session);
+    }
+    let original = format!("{}{}", HEADER, toml::to_string_pretty(&document)?);
+
+    // the file is private, like the config, even though passwords are left out
+    let path = std::env::temp_dir().join(format!("devlg-edit-{}.toml", std::process::id()));
+    let mut content = original.clone();
+    let result = loop {
+        let _ = fs::remove_file(&path);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&path)
+            .with_context(|| format!("Failed to write {:?}", path))?;
+        file.write_all(content.as_bytes())?;
+        drop(file);
+
+        open_editor(&path)?;
+        content =
+            fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+        if content == original {
+            break Ok(None);
+        }
+
+        match apply(&manager, &content) {
+            Ok(edited) => break Ok(Some(edited)),
+            Err(e) => {
+                eprintln!("Error: {:#}", e);
+                // without a terminal to ask, the edit is over
+                if !io::stdin().is_terminal()
+                    || !Confirm::new()
+                        .with_prompt("Edit again?")
+                        .default(true)
+                        .interact()?
+                {
+                    break Err(anyhow::anyhow!("Edit aborted, nothing was changed"));
+                }
+            }
+        }
+    };
+    let _ = fs::remove_file(&path);
+
+    let Some((config, changes)) = result?.filter(|(_, changes)| !changes.is_empty()) else {
+        println!("No changes.");
+        return Ok(());
+    };
+    // a dry run shows the changes when saving
+    if !dry_run {
+        for line in changes {
+            println!("{}", line);
+        }
+    }
+    let renamed: Vec<(String, String)> = manager
+        .config
+        .sessions
+        .iter()
+        .zip(config.sessions.iter())
+        .filter(|(old, new)| old.name != new.name)
+        .map(|(old, new)| (old.name.clone(), new.name.clone()))
+        .collect();
+    manager.config = config;
+    manager.save()?;
+    if dry_run {
+        return Ok(());
+    }
+    // through temporary names, in case sessions swapped names
+    let settings = &manager.config.settings;
+    for (old, _) in renamed.iter() {
+        move_session_data(settings, old, &format!(".renaming-{}", old));
+    }
+    for (old, new) in renamed.iter() {
+        move_session_data(settings, &format!(".renaming-{}", old), new);
+    }
+    println!("Sessions updated.");
+    Ok(())
+}
+
+fn open_editor(path: &Path) -> Result<()> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    // through the shell, for editors given with arguments like `code --wait`
+    let status = runner::status(
+        Command::new("sh")
+            .arg("-c")
+            .arg(format!("{} \"$1\"", editor))
+            .arg("sh")
+            .arg(path),
+    )
+    .with_context(|| format!("Failed to run the editor '{}'", editor))?;
+    if !status.success() {
+        anyhow::bail!("The editor '{}' exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+/// Validates the edited sessions and applies them to a copy of the config. Returns it with
+/// the changes, one line per changed field.
+fn apply(manager: &ConfigManager, content: &str) -> Result<(Config, Vec<String>)> {
+    let edited: BTreeMap<String, Session> =
+        toml::from_str(content).context("Failed to parse the sessions")?;
+
+    let mut replacements = Vec::new();
+    let mut changes = Vec::new();
+    for (name, mut session) in edited {
+        let existing = manager
+            .config
+            .get_session(&name)
+            .with_context(|| format!("Unknown session '{}', sessions can't be added here", name))?;
+        session.restore_secrets(existing);
+        if manager.config.settings.deep_validation {
+            doctor::validate_deep(&session)
+        } else {
+            session.validate()
+        }
+        .with_context(|| format!("Invalid session '{}'", name))?;
+
+        let diff = transfer::diff(existing, &session)?;
+        if diff.is_empty() {
+            continue;
+        }
+        changes.push(format!("~ session {}", name));
+        changes.extend(diff.into_iter().map(|line| format!("    {}", line)));
+        replacements.push((name, session));
+    }
+
+    let mut config = manager.config.clone();
+    config.replace_sessions(replacements)?;
+    Ok((config, changes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::AuthType;
+    use tempfile::tempdir;
+
+    fn session(name: &str, host: &str, password: &str) -> Session {
+        Session::new(
+            name.to_string(),
+            host.to_string(),
+            "user".to_string(),
+            22,
+            AuthType::Password,
+            None,
+            Some(password.to_string()),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_apply() -> Result<()> {
+        let dir = tempdir()?;
+        let mut manager = ConfigManager::new(Some(dir.path().join("devlg.toml")));
+        manager.config.sessions = vec![session("a", "host-a", "pa"), session("b", "host-b", "pb")];
+
+        // a rename, with the password carried over
+        let (config, changes) = apply(
+            &manager,
+            "[a]\nname = \"c\"\nhost = \"host-a\"\nuser = \"user\"\nport = 22\n\
+             auth_type = \"password\"\n",
+        )?;
+        assert_eq!(changes, ["~ session a", "    name: a -> c"]);
+        let renamed = config.get_session("c").unwrap();
+        assert_eq!(renamed.password.as_deref(), Some("pa"));
+
+        // a swap keeps the hosts and passwords of both sessions
+        let (config, _) = apply(
+            &manager,
+            "[a]\nname = \"b\"\nhost = \"host-a\"\nuser = \"user\"\nport = 22\n\
+             auth_type = \"password\"\n\n\
+             [b]\nname = \"a\"\nhost = \"host-b\"\nuser = \"user\"\nport = 22\n\
+             auth_type = \"password\"\n",
+        )?;
+        let a = config.get_session("a").unwrap();
+        let b = config.get_session("b").unwrap();
+        assert_eq!(
+            (a.host.as_str(), a.password.as_deref()),
+            ("host-b", Some("pb"))
+        );
+        assert_eq!(
+            (b.host.as_str(), b.password.as_deref()),
+            ("host-a", Some("pa"))
+        );
+
+        // a session switched to key authentication drops its password
+        let (config, _) = apply(
+            &manager,
+            "[a]\nname = \"a\"\nhost = \"host-a\"\nuser = \"user\"\nport = 22\n\
+             auth_type = \"key\"\nprivate_key_path = \"/keys/id_ed25519\"\n",
+        )?;
+        assert_eq!(config.get_session("a").unwrap().password, None);
+
+        let unknown = apply(
+            &manager,
+            "[x]\nname = \"x\"\nhost = \"host-x\"\nuser = \"user\"\nport = 22\n\
+             auth_type = \"password\"\npassword = \"px\"\n",
+        );
+        assert!(unknown.is_err());
+        Ok(())
+    }
+}
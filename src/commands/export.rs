@@ -0,0 +1,67 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use crate::commands::select_sessions;
+use crate::config::ansible;
+use crate::config::manager::ConfigManager;
+use crate::config::transfer::{Bundle, Format};
+
+/// Writes the selected sessions, all of them by default, as an Ansible inventory.
+pub fn handle_export_ansible(
+    format: String,
+    tags: Option<String>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+    let sessions = select_sessions(&manager.config, &[], tags.as_ref(), true)?;
+
+    let content = match format.to_lowercase().as_str() {
+        "ini" => ansible::to_ini(&sessions),
+        "yaml" | "yml" => ansible::to_yaml(&sessions)?,
+        _ => anyhow::bail!("Invalid format. Use 'ini' or 'yaml'"),
+    };
+
+    match output {
+        Some(output) => {
+            fs::write(&output, content).with_context(|| format!("Failed to write {:?}", output))?;
+            println!(
+                "Exported {} sessions to {}.",
+                sessions.len(),
+                output.display()
+            );
+        }
+        None => io::stdout().write_all(content.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Writes the selected sessions, all of them by default, with their templates and the
+/// global settings. Passwords are left out unless `include_secrets` is set.
+pub fn handle_export(
+    format: String,
+    tags: Option<String>,
+    include_secrets: bool,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let format: Format = format.parse()?;
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+    let sessions = select_sessions(&manager.config, &[], tags.as_ref(), true)?;
+
+    let content = Bundle::new(&manager.config, &sessions, include_secrets).to_string(format)?;
+    match output {
+        Some(output) => {
+            fs::write(&output, content).with_context(|| format!("Failed to write {:?}", output))?;
+            println!(
+                "Exported {} sessions to {}.",
+                sessions.len(),
+                output.display()
+            );
+        }
+        None => println!("{}", content.trim_end()),
+    }
+    Ok(())
+}
@@ -0,0 +1,120 @@
+use anyhow::{Context, Result};
+
+use crate::commands::select_sessions;
+use crate::config::facts::FactsStore;
+use crate::config::manager::ConfigManager;
+use crate::models::facts::{GATHER_SCRIPT, HostFacts, format_size};
+use crate::models::session::Session;
+use crate::utils::runner;
+use crate::utils::ssh::{batch_options, exec_ssh_output};
+use crate::utils::time::{self, format_age, format_duration};
+
+/// Sessions whose facts are gathered at the same time.
+const MAX_PARALLEL_GATHERS: usize = 16;
+
+/// Collects the facts of the selected sessions, all of them by default, in parallel.
+pub fn handle_facts_refresh(names: Vec<String>, tags: Option<String>) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+    let sessions = select_sessions(&manager.config, &names, tags.as_ref(), true)?
+        .into_iter()
+        .map(|s| manager.config.settings.apply_defaults(s))
+        .collect::<Result<Vec<Session>>>()?;
+
+    let results = runner::parallel_map(&sessions, MAX_PARALLEL_GATHERS, gather);
+
+    let mut store = FactsStore::new(None);
+    store.load()?;
+    let mut failed = Vec::new();
+    for (session, result) in sessions.iter().zip(results) {
+        match result {
+            Ok(facts) => {
+                println!(
+                    "{:<20} {}",
+                    session.name,
+                    facts
+                        .os_name
+                        .as_deref()
+                        .or(facts.os.as_deref())
+                        .unwrap_or("N/A")
+                );
+                store.facts.insert(session.name.clone(), facts);
+            }
+            Err(e) => {
+                eprintln!("Failed to collect the facts of '{}': {:#}", session.name, e);
+                failed.push(session.name.clone());
+            }
+        }
+    }
+    store.prune(&manager.config);
+    store.save()?;
+
+    if !failed.is_empty() {
+        anyhow::bail!("Fact gathering failed for: {}", failed.join(", "));
+    }
+    Ok(())
+}
+
+pub fn handle_facts_show(name: String) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+    if manager.config.get_session(&name).is_none() {
+        anyhow::bail!("Session '{}' not found", name);
+    }
+
+    let mut store = FactsStore::new(None);
+    store.load()?;
+    let facts = store.get(&name).with_context(|| {
+        format!(
+            "No facts collected for session '{}', run `devlg facts refresh {}`",
+            name, name
+        )
+    })?;
+
+    let or_na = |value: Option<String>| value.unwrap_or_else(|| "N/A".to_string());
+    println!("{:<12} {}", "Collected", format_age(facts.collected_at));
+    println!(
+        "{:<12} {}",
+        "OS",
+        or_na(facts.os_name.clone().or(facts.os.clone()))
+    );
+    println!("{:<12} {}", "Kernel", or_na(facts.kernel.clone()));
+    println!("{:<12} {}", "Arch", or_na(facts.arch.clone()));
+    println!(
+        "{:<12} {}",
+        "CPUs",
+        or_na(facts.cpus.map(|c| c.to_string()))
+    );
+    println!(
+        "{:<12} {}",
+        "Memory",
+        or_na(facts.mem_bytes.map(format_size))
+    );
+    println!(
+        "{:<12} {}",
+        "Uptime",
+        or_na(facts.uptime_secs.map(format_duration))
+    );
+    println!("{:<12} {}", "IPs", facts.ips.join(", "));
+    Ok(())
+}
+
+fn gather(session: &Session) -> Result<HostFacts> {
+    let output = exec_ssh_output(session, &batch_options(session), GATHER_SCRIPT)?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+                .lines()
+                .last()
+                .map_or_else(
+                    || format!("ssh exited with {}", output.status),
+                    str::to_string
+                )
+        );
+    }
+    Ok(HostFacts::parse(
+        &String::from_utf8_lossy(&output.stdout),
+        time::now(),
+    ))
+}
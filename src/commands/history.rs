@@ -0,0 +1,101 @@
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::config::history::HistoryStore;
+use crate::config::manager::ConfigManager;
+use crate::utils::time::{self, format_age, format_duration};
+
+pub fn handle_history(session: Option<String>, limit: usize) -> Result<()> {
+    let mut store = HistoryStore::new(None);
+    store.load()?;
+
+    let entries: Vec<_> = store
+        .entries
+        .iter()
+        .rev()
+        .filter(|e| session.as_ref().is_none_or(|name| &e.session == name))
+        .take(limit)
+        .collect();
+
+    if entries.is_empty() {
+        println!("No connection history found.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<10} {:<6} {:<20} {:<20} {:<10} {:<6}",
+        "When", "Cmd", "Session", "Host", "Duration", "Exit"
+    );
+    println!("{:-<77}", "");
+    for entry in entries {
+        let exit = entry
+            .exit_code
+            .map(|code| code.to_string())
+            .unwrap_or_else(|| "N/A".to_string());
+        println!(
+            "{:<10} {:<6} {:<20} {:<20} {:<10} {:<6}",
+            format_age(entry.timestamp),
+            entry.command,
+            entry.session,
+            entry.host,
+            format_duration(entry.duration_secs),
+            exit
+        );
+    }
+
+    Ok(())
+}
+
+pub fn handle_stats(top: usize, unused_days: u64) -> Result<()> {
+    let mut store = HistoryStore::new(None);
+    store.load()?;
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+
+    println!("Most used sessions:");
+    print_ranking(store.entries.iter().map(|e| e.session.as_str()), top);
+
+    println!();
+    println!("Most used hosts:");
+    print_ranking(store.entries.iter().map(|e| e.host.as_str()), top);
+
+    println!();
+    println!("Sessions unused for {} days:", unused_days);
+    let cutoff = time::now().saturating_sub(unused_days * 86400);
+    let mut found = false;
+    for session in manager.config.sessions.iter() {
+        let last_used = store.last_used(&session.name);
+        if last_used.is_none_or(|t| t < cutoff) {
+            found = true;
+            println!(
+                "  {} ({}@{}:{}) last used {}",
+                session.name,
+                session.user,
+                session.host,
+                session.port,
+                last_used.map_or_else(|| "never".to_string(), format_age)
+            );
+        }
+    }
+    if !found {
+        println!("  none");
+    }
+
+    Ok(())
+}
+
+fn print_ranking<'a>(keys: impl Iterator<Item = &'a str>, top: usize) {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for key in keys {
+        *counts.entry(key).or_default() += 1;
+    }
+
+    let mut ranking: Vec<(&str, usize)> = counts.into_iter().collect();
+    ranking.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    if ranking.is_empty() {
+        println!("  none");
+    }
+    for (key, count) in ranking.into_iter().take(top) {
+        println!("  {:<30} {}", key, count);
+    }
+}
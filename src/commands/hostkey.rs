@@ -0,0 +1,129 @@
+use anyhow::{Context, Result};
+use dialoguer::Confirm;
+
+use crate::config::manager::ConfigManager;
+use crate::models::session::Session;
+use crate::utils::hostkey::{self, HostKey};
+
+pub fn handle_hostkey_scan(name: String) -> Result<()> {
+    let manager = load(false)?;
+    let session = get_session(&manager, &name)?;
+
+    for key in hostkey::scan(session)? {
+        let pinned = if session.host_key_fingerprints.contains(&key.fingerprint) {
+            "pinned"
+        } else {
+            ""
+        };
+        println!("{:<10} {:<55} {}", key.key_type, key.fingerprint, pinned);
+    }
+    Ok(())
+}
+
+pub fn handle_hostkey_pin(
+    name: String,
+    fingerprints: Vec<String>,
+    yes: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let mut manager = load(dry_run)?;
+    let mut session = get_session(&manager, &name)?.clone();
+
+    let offered = hostkey::scan(&session)?;
+    let keys: Vec<HostKey> = if fingerprints.is_empty() {
+        for key in offered.iter() {
+            println!("{:<10} {}", key.key_type, key.fingerprint);
+        }
+        if !yes
+            && !Confirm::new()
+                .with_prompt(format!(
+                    "Trust these host keys for {}:{}?",
+                    session.host, session.port
+                ))
+                .default(false)
+                .interact()?
+        {
+            anyhow::bail!("Host keys not pinned");
+        }
+        offered
+    } else {
+        // only pin keys that were verified out of band
+        for fp in fingerprints.iter() {
+            if !offered.iter().any(|k| &k.fingerprint == fp) {
+                anyhow::bail!(
+                    "Host {}:{} does not offer a key with fingerprint {}",
+                    session.host,
+                    session.port,
+                    fp
+                );
+            }
+        }
+        offered
+            .into_iter()
+            .filter(|k| fingerprints.contains(&k.fingerprint))
+            .collect()
+    };
+
+    session.host_key_fingerprints = keys.iter().map(|k| k.fingerprint.clone()).collect();
+    if dry_run {
+        println!(
+            "Would replace the known host keys of session '{}' with:",
+            name
+        );
+        for key in keys.iter() {
+            println!("{}", key.line);
+        }
+    } else {
+        hostkey::pin(&session, &keys)?;
+    }
+    manager.config.update_session(session)?;
+    manager.save()?;
+    if !dry_run {
+        println!("Pinned {} host keys for session '{}'.", keys.len(), name);
+    }
+    Ok(())
+}
+
+pub fn handle_hostkey_verify(name: String) -> Result<()> {
+    let manager = load(false)?;
+    let session = get_session(&manager, &name)?;
+    if session.host_key_fingerprints.is_empty() {
+        anyhow::bail!("Session '{}' has no pinned host keys", name);
+    }
+
+    hostkey::verify(session)?;
+    println!("Host keys of session '{}' match the pinned ones.", name);
+    Ok(())
+}
+
+pub fn handle_hostkey_forget(name: String, dry_run: bool) -> Result<()> {
+    let mut manager = load(dry_run)?;
+    let mut session = get_session(&manager, &name)?.clone();
+
+    if dry_run {
+        println!("Would remove the known host keys of session '{}'.", name);
+    } else {
+        hostkey::forget(&session)?;
+    }
+    session.host_key_fingerprints.clear();
+    manager.config.update_session(session)?;
+    manager.save()?;
+    if !dry_run {
+        println!("Forgot the host keys of session '{}'.", name);
+    }
+    Ok(())
+}
+
+fn load(dry_run: bool) -> Result<ConfigManager> {
+    let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
+    manager.load()?;
+    Ok(manager)
+}
+
+fn get_session<'a>(manager: &'a ConfigManager, name: &str) -> Result<&'a Session> {
+    manager
+        .config
+        .get_session(name)
+        .with_context(|| format!("Session '{}' not found", name))
+}
@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::PathBuf;
+
+use crate::config::manager::ConfigManager;
+use crate::config::transfer::{self, Bundle, Format};
+use crate::config::{ansible, clients};
+use crate::models::session::Session;
+
+/// Merges the sessions, templates and settings of a file exported with `devlg export`.
+pub fn handle_import(
+    file: PathBuf,
+    format: Option<String>,
+    strategy: String,
+    dry_run: bool,
+) -> Result<()> {
+    let format = match format {
+        Some(format) => Some(format.parse()?),
+        None => Format::from_path(&file),
+    };
+    let content =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read {:?}", file))?;
+    import_bundle(Bundle::parse(&content, format)?, &strategy, dry_run, false)
+}
+
+/// Adds the hosts of an Ansible inventory as sessions.
+pub fn handle_import_ansible(inventory: PathBuf, strategy: String, dry_run: bool) -> Result<()> {
+    let bundle = Bundle {
+        sessions: ansible::import(&inventory)?,
+        ..Default::default()
+    };
+    import_bundle(bundle, &strategy, dry_run, false)
+}
+
+pub fn handle_import_putty(file: PathBuf, strategy: String, dry_run: bool) -> Result<()> {
+    import_sessions(clients::import_putty(&file)?, &strategy, dry_run)
+}
+
+pub fn handle_import_remmina(paths: Vec<PathBuf>, strategy: String, dry_run: bool) -> Result<()> {
+    import_sessions(clients::import_remmina(&paths)?, &strategy, dry_run)
+}
+
+pub fn handle_import_filezilla(
+    file: Option<PathBuf>,
+    strategy: String,
+    dry_run: bool,
+) -> Result<()> {
+    import_sessions(clients::import_filezilla(file)?, &strategy, dry_run)
+}
+
+pub fn handle_import_csv(
+    file: PathBuf,
+    map: Vec<String>,
+    strategy: String,
+    dry_run: bool,
+) -> Result<()> {
+    import_sessions(clients::import_csv(&file, &map)?, &strategy, dry_run)
+}
+
+/// Sessions of other clients have names of their own, so the ones pointing at a host that
+/// already has a session are dropped instead of being merged by name.
+fn import_sessions(sessions: Vec<Session>, strategy: &str, dry_run: bool) -> Result<()> {
+    let bundle = Bundle {
+        sessions,
+        ..Default::default()
+    };
+    import_bundle(bundle, strategy, dry_run, true)
+}
+
+fn import_bundle(mut bundle: Bundle, strategy: &str, dry_run: bool, dedup: bool) -> Result<()> {
+    let strategy = strategy.parse()?;
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+
+    let mut report = Vec::new();
+    if dedup {
+        let (sessions, duplicates) = transfer::dedup(&manager.config, bundle.sessions);
+        bundle.sessions = sessions;
+        report = duplicates;
+    }
+    let (merged, merge_report) = transfer::merge(&manager.config, bundle, strategy)?;
+    report.extend(merge_report);
+    for line in report.iter() {
+        println!("{}", line);
+    }
+    if dry_run {
+        println!("Dry run, nothing was imported.");
+        return Ok(());
+    }
+
+    manager.config = merged;
+    manager.save()?;
+    println!("Import complete.");
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+
+use crate::config::manager::ConfigManager;
+use crate::utils::time::format_age;
+
+pub fn handle_undo(dry_run: bool) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
+    manager.load()?;
+    let mut journal = manager.journal();
+    journal.load()?;
+
+    let entry = journal.undo(&manager.config)?.clone();
+    let summary = entry.summary();
+    manager.restore(entry.before)?;
+    if !dry_run {
+        journal.save()?;
+        println!("Undid `{}`: {}", entry.command, summary);
+    }
+    Ok(())
+}
+
+pub fn handle_redo(dry_run: bool) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
+    manager.load()?;
+    let mut journal = manager.journal();
+    journal.load()?;
+
+    let entry = journal.redo(&manager.config)?.clone();
+    let summary = entry.summary();
+    manager.restore(entry.after)?;
+    if !dry_run {
+        journal.save()?;
+        println!("Redid `{}`: {}", entry.command, summary);
+    }
+    Ok(())
+}
+
+/// Lists the recorded changes, most recent first.
+pub fn handle_log(limit: usize) -> Result<()> {
+    let manager = ConfigManager::new(None);
+    let mut journal = manager.journal();
+    journal.load()?;
+
+    if journal.entries.is_empty() {
+        println!("No changes recorded.");
+        return Ok(());
+    }
+
+    println!(
+        "{:<10} {:<40} {:<}",
+        "When", "Command", "Changes (+added -removed ~modified)"
+    );
+    println!("{:-<100}", "");
+    let undone_from = journal.entries.len() - journal.undone;
+    for (i, entry) in journal.entries.iter().enumerate().rev().take(limit) {
+        let undone = if i >= undone_from { " (undone)" } else { "" };
+        println!(
+            "{:<10} {:<40} {}{}",
+            format_age(entry.timestamp),
+            entry.command,
+            entry.summary(),
+            undone
+        );
+    }
+    Ok(())
+}
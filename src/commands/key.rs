@@ -0,0 +1,217 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::commands::{check_protected, select_sessions};
+use crate::config::manager::{ConfigManager, Settings};
+use crate::models::session::{AuthType, Session};
+use crate::utils::{keys, runner};
+
+pub fn handle_key_gen(name: String, force: bool, dry_run: bool) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+    if manager.config.get_session(&name).is_none() {
+        anyhow::bail!("Session '{}' not found", name);
+    }
+
+    let path = keys::key_path(&name)?;
+    if path.exists() {
+        if !force {
+            anyhow::bail!(
+                "Key {} already exists, use --force to replace it",
+                path.display()
+            );
+        }
+        remove_keypair(&path, dry_run)?;
+    }
+
+    runner::dry_run(dry_run, || keys::generate(&path, &key_comment(&name)))?;
+    if dry_run {
+        return Ok(());
+    }
+    println!("Key generated at {}.", path.display());
+    println!("{}", keys::public_key(&path)?);
+    Ok(())
+}
+
+pub fn handle_key_deploy(name: String, dry_run: bool) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
+    manager.load()?;
+    let session = manager
+        .config
+        .get_session(&name)
+        .with_context(|| format!("Session '{}' not found", name))?
+        .clone();
+
+    let path = keys::key_path(&name)?;
+    let settings = &manager.config.settings;
+    let new_session = Session {
+        auth_type: AuthType::Key,
+        private_key_path: Some(path.clone()),
+        password: None,
+        ..session.clone()
+    };
+    let existing = if path.exists() {
+        Some(keys::public_key(&path)?)
+    } else {
+        None
+    };
+    runner::dry_run(dry_run, || -> Result<()> {
+        let public_key = match existing {
+            Some(public_key) => public_key,
+            None => {
+                keys::generate(&path, &key_comment(&name))?;
+                if !dry_run {
+                    println!("Key generated at {}.", path.display());
+                }
+                new_public_key(&path, &name, dry_run)?
+            }
+        };
+
+        // log in the current way to install the key, then make sure the key alone works
+        keys::authorize(&settings.apply_defaults(&session)?, &public_key)?;
+        keys::check_login(&settings.apply_defaults(&new_session)?)
+    })?;
+
+    manager.config.update_session(new_session)?;
+    manager.save()?;
+    if !dry_run {
+        println!(
+            "Key deployed, session '{}' now uses key authentication.",
+            name
+        );
+    }
+    Ok(())
+}
+
+pub fn handle_key_rotate(
+    names: Vec<String>,
+    tags: Option<String>,
+    allow_protected: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
+    manager.load()?;
+
+    let sessions = select_sessions(&manager.config, &names, tags.as_ref(), false)?;
+    if tags.is_some() || sessions.len() > 1 {
+        check_protected(
+            &manager.config.settings,
+            &sessions,
+            "rotate the keys of",
+            allow_protected,
+        )?;
+    }
+    let sessions: Vec<Session> = sessions.into_iter().cloned().collect();
+
+    // keep going on failures so one unreachable host does not stop the whole fleet
+    let mut failed = Vec::new();
+    for session in sessions {
+        let name = session.name.clone();
+        match rotate(&manager.config.settings, session, dry_run) {
+            Ok(session) => {
+                manager.config.update_session(session)?;
+                manager.save()?;
+                if !dry_run {
+                    println!("Rotated the key of session '{}'.", name);
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to rotate the key of session '{}': {:#}", name, e);
+                failed.push(name);
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!("Key rotation failed for: {}", failed.join(", "));
+    }
+    Ok(())
+}
+
+/// Replaces the key of a session with a new devlg-managed one, and removes the old key
+/// from the host if devlg generated it. With `dry_run`, the commands are only printed.
+fn rotate(settings: &Settings, session: Session, dry_run: bool) -> Result<Session> {
+    if session.auth_type != AuthType::Key {
+        anyhow::bail!(
+            "it uses password authentication, run `devlg key deploy {}` first",
+            session.name
+        );
+    }
+    let old_path = session
+        .private_key_path
+        .clone()
+        .context("it has no private key")?;
+    let old_public = keys::public_key(&old_path)?;
+
+    let path = keys::key_path(&session.name)?;
+    let new_path = PathBuf::from(format!("{}.new", path.display()));
+    remove_keypair(&new_path, dry_run)?;
+    let new_session = settings.apply_defaults(&Session {
+        private_key_path: Some(new_path.clone()),
+        ..session.clone()
+    })?;
+    runner::dry_run(dry_run, || -> Result<()> {
+        keys::generate(&new_path, &key_comment(&session.name))?;
+        keys::authorize(
+            &settings.apply_defaults(&session)?,
+            &new_public_key(&new_path, &session.name, dry_run)?,
+        )?;
+        keys::check_login(&new_session)?;
+
+        if keys::is_managed(&old_path) {
+            keys::revoke(&new_session, &old_public)
+        } else {
+            // the user's own key is probably used elsewhere too
+            eprintln!(
+                "Keeping {} in the authorized keys of '{}', it was not generated by devlg.",
+                old_path.display(),
+                session.name
+            );
+            Ok(())
+        }
+    })?;
+
+    if dry_run {
+        println!("Would move {} to {}.", new_path.display(), path.display());
+    } else {
+        fs::rename(&new_path, &path)?;
+        fs::rename(pub_path(&new_path), pub_path(&path))?;
+    }
+    Ok(Session {
+        private_key_path: Some(path),
+        ..session
+    })
+}
+
+fn key_comment(name: &str) -> String {
+    format!("devlg-{}", name)
+}
+
+fn pub_path(path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.pub", path.display()))
+}
+
+/// The public key of the key just generated at `path`, which a dry run did not generate.
+fn new_public_key(path: &Path, name: &str, dry_run: bool) -> Result<String> {
+    if dry_run {
+        return Ok(format!("ssh-ed25519 <new key> {}", key_comment(name)));
+    }
+    keys::public_key(path)
+}
+
+fn remove_keypair(path: &Path, dry_run: bool) -> Result<()> {
+    for file in [path.to_path_buf(), pub_path(path)] {
+        if !file.exists() {
+            continue;
+        }
+        if dry_run {
+            println!("Would remove {}.", file.display());
+        } else {
+            fs::remove_file(&file).with_context(|| format!("Failed to remove {:?}", file))?;
+        }
+    }
+    Ok(())
+}
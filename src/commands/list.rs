@@ -2,12 +2,26 @@ use anyhow::Result;
 use std::collections::HashSet;
 
 use crate::commands::parse_tags;
+use crate::config::facts::FactsStore;
 use crate::config::manager::ConfigManager;
+use crate::models::facts::{Filter, HostFacts, format_size};
 use crate::models::session::Session;
+use crate::utils::time::format_age;
+
+pub fn handle_list(
+    detailed: bool,
+    tags_filter: Option<String>,
+    filters: Vec<String>,
+) -> Result<()> {
+    let filters = filters
+        .iter()
+        .map(|f| f.parse())
+        .collect::<Result<Vec<Filter>>>()?;
 
-pub fn handle_list(detailed: bool, tags_filter: Option<String>) -> Result<()> {
     let mut manager = ConfigManager::new(None);
     manager.load()?;
+    let mut facts = FactsStore::new(None);
+    facts.load()?;
 
     let config = manager.config;
     if config.sessions.is_empty() {
@@ -30,18 +44,37 @@ pub fn handle_list(detailed: bool, tags_filter: Option<String>) -> Result<()> {
         config.sessions.iter().collect()
     };
 
+    let filtered_sessions: Vec<&Session> = filtered_sessions
+        .into_iter()
+        .filter(|session| {
+            filters
+                .iter()
+                .all(|f| f.matches(session, facts.get(&session.name)))
+        })
+        .collect();
+
     if filtered_sessions.is_empty() {
-        println!("No SSH sessions found matching the specified tags.");
+        println!("No SSH sessions found matching the specified tags or filters.");
         return Ok(());
     }
 
     println!("Available SSH sessions:");
     if detailed {
         println!(
-            "{:<20} {:<15} {:<10} {:<6} {:<10} {:<20} {:<20}",
-            "Name", "Host", "User", "Port", "Auth Type", "Key Path", "Tags"
+            "{:<20} {:<15} {:<10} {:<6} {:<10} {:<20} {:<20} {:<16} {:<5} {:<7} {:<10}",
+            "Name",
+            "Host",
+            "User",
+            "Port",
+            "Auth Type",
+            "Key Path",
+            "Tags",
+            "OS",
+            "CPUs",
+            "Memory",
+            "Facts"
         );
-        println!("{:-<105}", "");
+        println!("{:-<148}", "");
 
         for session in filtered_sessions.iter() {
             let auth_type = session.auth_type.to_string();
@@ -63,15 +96,24 @@ pub fn handle_list(detailed: bool, tags_filter: Option<String>) -> Result<()> {
                     .join(", ")
             };
 
+            let host_facts = facts.get(&session.name);
+            let fact = |f: &dyn Fn(&HostFacts) -> Option<String>| {
+                host_facts.and_then(f).unwrap_or_else(|| "N/A".to_string())
+            };
+
             println!(
-                "{:<20} {:<15} {:<10} {:<6} {:<10} {:<20} {:<20}",
+                "{:<20} {:<15} {:<10} {:<6} {:<10} {:<20} {:<20} {:<16} {:<5} {:<7} {:<10}",
                 session.name,
                 session.host,
                 session.user,
                 session.port,
                 auth_type,
                 key_path,
-                tags_str
+                tags_str,
+                fact(&|f| f.os.clone()),
+                fact(&|f| f.cpus.map(|c| c.to_string())),
+                fact(&|f| f.mem_bytes.map(format_size)),
+                fact(&|f| Some(format_age(f.collected_at)))
             );
         }
     } else {
@@ -90,14 +132,21 @@ pub fn handle_list(detailed: bool, tags_filter: Option<String>) -> Result<()> {
                 )
             };
 
+            let protected = if config.settings.is_protected(session) {
+                " (protected)"
+            } else {
+                ""
+            };
+
             println!(
-                "{}. {} ({}@{}:{}){}",
+                "{}. {} ({}@{}:{}){}{}",
                 i + 1,
                 session.name,
                 session.user,
                 session.host,
                 session.port,
-                tags_str
+                tags_str,
+                protected
             );
         }
     }
@@ -3,11 +3,20 @@ use dialoguer::Select;
 use std::collections::HashSet;
 
 use crate::commands::parse_tags;
-use crate::config::manager::ConfigManager;
-use crate::models::session::Session;
-use crate::utils::ssh;
+use crate::config::history::{self, HistoryStore};
+use crate::config::manager::{ConfigManager, Settings};
+use crate::models::session::{ReconnectPolicy, Session};
+use crate::utils::ssh::{self, CONNECTION_ERROR_CODE, ConnectOptions, ExitError};
+use crate::utils::{doctor, hooks, protected};
 
-pub fn handle_login(name: Option<String>, tags: Option<String>) -> Result<()> {
+pub fn handle_login(
+    name: Option<String>,
+    tags: Option<String>,
+    record: bool,
+    reconnect: bool,
+    command: Vec<String>,
+    dry_run: bool,
+) -> Result<()> {
     let mut manager = ConfigManager::new(None);
     manager.load()?;
     let config = manager.config;
@@ -19,34 +28,16 @@ pub fn handle_login(name: Option<String>, tags: Option<String>) -> Result<()> {
                 anyhow::bail!("No SSH sessions found matching the specified name")
             }
             if sessions.len() == 1 {
-                println!(
+                eprintln!(
                     "Found session {} matching the specified name",
                     sessions[0].name
                 );
                 sessions[0].clone()
+            } else if let Some(session) = sessions.iter().find(|s| s.name == name) {
+                // an exact match wins over the sessions merely containing the name
+                (*session).clone()
             } else {
-                let session_names: Vec<String> = sessions
-                    .iter()
-                    .map(|s| {
-                        let tags_str = if s.tags.is_empty() {
-                            "".to_string()
-                        } else {
-                            format!(
-                                " [{}]",
-                                s.tags.iter().cloned().collect::<Vec<String>>().join(", ")
-                            )
-                        };
-                        format!("{} ({}@{}:{}){}", s.name, s.user, s.host, s.port, tags_str)
-                    })
-                    .collect();
-
-                let selection = Select::new()
-                    .with_prompt("Select a session")
-                    .items(&session_names)
-                    .default(0)
-                    .interact()?;
-
-                sessions[selection].clone()
+                select_session(sessions)?
             }
         }
         None => {
@@ -74,31 +65,139 @@ pub fn handle_login(name: Option<String>, tags: Option<String>) -> Result<()> {
                 anyhow::bail!("No SSH sessions found matching the specified tags");
             }
 
-            let session_names: Vec<String> = filtered_sessions
-                .iter()
-                .map(|s| {
-                    let tags_str = if s.tags.is_empty() {
-                        "".to_string()
-                    } else {
-                        format!(
-                            " [{}]",
-                            s.tags.iter().cloned().collect::<Vec<String>>().join(", ")
-                        )
-                    };
-                    format!("{} ({}@{}:{}){}", s.name, s.user, s.host, s.port, tags_str)
-                })
-                .collect();
-
-            let selection = Select::new()
-                .with_prompt("Select a session")
-                .items(&session_names)
-                .default(0)
-                .interact()?;
-
-            filtered_sessions[selection].clone()
+            select_session(filtered_sessions)?
         }
     };
 
+    if config.settings.deep_validation {
+        doctor::validate_deep(&session)?;
+    }
+
     // Use the SSH utility module to connect
-    ssh::connect_ssh(&session)
+    let session = config.settings.apply_defaults(&session)?;
+    let mut options = connect_options(&config.settings, &session, record, reconnect)?;
+    options.dry_run = dry_run;
+    if command.is_empty() {
+        return connect(&config.settings, &session, "login", &options);
+    }
+
+    // a one-off command exits with the remote exit code, which is not an error of devlg
+    options.command = Some(command);
+    let result = connect(&config.settings, &session, "exec", &options);
+    if let Err(e) = &result
+        && let Some(exit) = e.downcast_ref::<ExitError>()
+        && let Some(code) = exit.status.code()
+        && code != CONNECTION_ERROR_CODE
+    {
+        std::process::exit(code);
+    }
+    result
+}
+
+/// Reconnects to the session of the most recent login.
+pub fn handle_last(dry_run: bool) -> Result<()> {
+    let mut store = HistoryStore::new(None);
+    store.load()?;
+    let last = store
+        .last("login")
+        .ok_or_else(|| anyhow::anyhow!("No previous login found"))?;
+
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+    let session = manager
+        .config
+        .get_session(&last.session)
+        .ok_or_else(|| anyhow::anyhow!("Session '{}' no longer exists", last.session))?
+        .clone();
+
+    login(&manager.config.settings, &session, dry_run)
+}
+
+/// Logs in to `session` with its own settings, like `devlg login <name>`.
+pub fn login(settings: &Settings, session: &Session, dry_run: bool) -> Result<()> {
+    if settings.deep_validation {
+        doctor::validate_deep(session)?;
+    }
+    let session = settings.apply_defaults(session)?;
+    let mut options = connect_options(settings, &session, false, false)?;
+    options.dry_run = dry_run;
+    connect(settings, &session, "login", &options)
+}
+
+/// Connects to `session` between its hooks, recording it in the history as `kind` unless it
+/// is a dry run. A protected session is confirmed first.
+fn connect(
+    settings: &Settings,
+    session: &Session,
+    kind: &str,
+    options: &ConnectOptions,
+) -> Result<()> {
+    let connect = || {
+        hooks::around(settings, &[session], kind, options.dry_run, || {
+            if options.dry_run {
+                return ssh::connect_ssh(session, options);
+            }
+            history::track(session, kind, || ssh::connect_ssh(session, options))
+        })
+    };
+    if options.dry_run || !settings.is_protected(session) {
+        return connect();
+    }
+    let action = if kind == "exec" {
+        "run a command on"
+    } else {
+        "log in to"
+    };
+    protected::confirm(session, action)?;
+    protected::marked(session, connect)
+}
+
+fn connect_options(
+    settings: &Settings,
+    session: &Session,
+    record: bool,
+    reconnect: bool,
+) -> Result<ConnectOptions> {
+    let record_to = if record || session.record {
+        Some(settings.new_recording_path(&session.name)?)
+    } else {
+        None
+    };
+
+    Ok(ConnectOptions {
+        record_to,
+        reconnect: reconnect.then(ReconnectPolicy::default),
+        command: None,
+        dry_run: false,
+    })
+}
+
+/// Lets the user pick one of `sessions`, most frequently and recently used first.
+fn select_session(mut sessions: Vec<&Session>) -> Result<Session> {
+    let mut store = HistoryStore::new(None);
+    store.load()?;
+    sessions.sort_by(|a, b| store.frecency(&b.name).total_cmp(&store.frecency(&a.name)));
+
+    let session_names: Vec<String> = sessions
+        .iter()
+        .map(|s| {
+            let tags_str = if s.tags.is_empty() {
+                "".to_string()
+            } else {
+                format!(
+                    " [{}]",
+                    s.tags.iter().cloned().collect::<Vec<String>>().join(", ")
+                )
+            };
+            format!("{} ({}@{}:{}){}", s.name, s.user, s.host, s.port, tags_str)
+        })
+        .collect();
+
+    let selection = Select::new()
+        .with_prompt("Select a session")
+        .items(&session_names)
+        .default(0)
+        .interact()?;
+
+    Ok(sessions[selection].clone())
 }
@@ -1,14 +1,34 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
 use std::{collections::HashSet, path::PathBuf};
 
+use crate::config::manager::{Config, Settings};
+use crate::models::session::Session;
+
+mod check;
+mod clone;
 mod cp;
 mod delete;
+mod doctor;
+mod edit;
+mod export;
+mod facts;
+mod history;
+mod hostkey;
+mod import;
+mod journal;
+mod key;
 mod list;
-mod login;
+pub(crate) mod login;
 mod modify;
+mod otp;
+mod panes;
+mod recordings;
+mod rename;
+mod sync;
 mod tag;
 mod template;
+mod tui;
 mod version;
 
 #[derive(Parser)]
@@ -16,6 +36,10 @@ mod version;
 pub struct Cli {
     #[command(subcommand)]
     pub command: Commands,
+
+    /// Print the ssh/scp commands or the config changes instead of running or saving them
+    #[arg(long, global = true)]
+    pub dry_run: bool,
 }
 
 #[derive(Subcommand)]
@@ -23,6 +47,12 @@ pub enum Commands {
     /// Show the current version
     Version,
 
+    /// Print the current one-time code of a session with TOTP authentication
+    Otp {
+        /// Session name
+        name: String,
+    },
+
     /// List all SSH sessions
     List {
         /// Show detailed information about each session
@@ -32,6 +62,10 @@ pub enum Commands {
         /// Filter sessions by tags (comma or semicolon separated)
         #[arg(short, long)]
         tags: Option<String>,
+
+        /// Filter sessions by their fields or facts, e.g. os=ubuntu-22.04 or mem>64G
+        #[arg(short, long = "filter", value_name = "EXPR")]
+        filter: Vec<String>,
     },
 
     /// Add a new SSH session
@@ -52,7 +86,7 @@ pub enum Commands {
         #[arg(short, long, default_value = "22")]
         port: Option<u16>,
 
-        /// Authentication type (key or password)
+        /// Authentication type (key, password or totp)
         #[arg(short, long, default_value = "key")]
         auth_type: Option<String>,
 
@@ -64,6 +98,10 @@ pub enum Commands {
         #[arg(short = 'P', long)]
         password: Option<String>,
 
+        /// Base32 secret of the one-time codes, for totp authentication
+        #[arg(long)]
+        totp_secret: Option<String>,
+
         /// Tags for the session (comma or semicolon separated)
         #[arg(short, long)]
         tags: Option<String>,
@@ -81,6 +119,27 @@ pub enum Commands {
         /// Delete sessions by tag, if provided, names will be ignored
         #[arg(short, long)]
         tag: Option<String>,
+
+        /// Don't ask before deleting several sessions
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Also delete protected sessions when deleting several sessions
+        #[arg(long)]
+        allow_protected: bool,
+    },
+
+    /// Undo the last change of the sessions, templates or settings
+    Undo,
+
+    /// Redo the last undone change
+    Redo,
+
+    /// Show the recorded changes, most recent first
+    Log {
+        /// Number of changes to show
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
     },
 
     /// Modify an existing SSH session
@@ -100,7 +159,7 @@ pub enum Commands {
         #[arg(short, long)]
         port: Option<u16>,
 
-        /// New authentication type (key or password)
+        /// New authentication type (key, password or totp)
         #[arg(short, long)]
         auth_type: Option<String>,
 
@@ -112,11 +171,59 @@ pub enum Commands {
         #[arg(short = 'P', long)]
         password: Option<String>,
 
+        /// New base32 secret of the one-time codes, for totp authentication
+        #[arg(long)]
+        totp_secret: Option<String>,
+
         /// New tags for the session (comma or semicolon separated)
         #[arg(short, long)]
         tags: Option<String>,
     },
 
+    /// Rename a session, along with the templates based on it
+    Rename {
+        /// Current session name
+        old: String,
+
+        /// New session name
+        new: String,
+    },
+
+    /// Add a copy of a session under a new name
+    Clone {
+        /// Session to copy
+        src: String,
+
+        /// Name of the copy
+        new: String,
+
+        /// Host address of the copy
+        #[arg(short = 'H', long)]
+        host: Option<String>,
+
+        /// Username of the copy
+        #[arg(short, long)]
+        user: Option<String>,
+
+        /// SSH port of the copy
+        #[arg(short, long)]
+        port: Option<u16>,
+
+        /// Tags of the copy (comma or semicolon separated)
+        #[arg(short, long)]
+        tags: Option<String>,
+    },
+
+    /// Edit sessions as TOML in $EDITOR, including their names
+    Edit {
+        /// Sessions to edit
+        names: Vec<String>,
+
+        /// Also edit the sessions with these tags (comma or semicolon separated)
+        #[arg(short, long)]
+        tags: Option<String>,
+    },
+
     /// Login to an SSH session
     Login {
         /// Session name to login to
@@ -125,6 +232,18 @@ pub enum Commands {
         /// Filter sessions by tags (comma or semicolon separated)
         #[arg(short, long)]
         tags: Option<String>,
+
+        /// Record the terminal session in asciicast format
+        #[arg(short, long)]
+        record: bool,
+
+        /// Reconnect when the connection drops, even if the session does not enable it
+        #[arg(long)]
+        reconnect: bool,
+
+        /// Run this command instead of a login shell and exit with its exit code
+        #[arg(last = true)]
+        command: Vec<String>,
     },
 
     /// Manage tags for SSH sessions
@@ -165,6 +284,147 @@ pub enum Commands {
         #[arg(short, long)]
         recursive: bool,
     },
+
+    /// Show the connection history
+    History {
+        /// Only show connections to this session
+        session: Option<String>,
+
+        /// Maximum number of entries to show
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+    },
+
+    /// Reconnect to the session of the last login
+    Last,
+
+    /// Show usage statistics of sessions and hosts
+    Stats {
+        /// Number of most used sessions and hosts to show
+        #[arg(short, long, default_value = "10")]
+        top: usize,
+
+        /// Report sessions not used for this many days
+        #[arg(short, long, default_value = "30")]
+        unused_days: u64,
+    },
+
+    /// Open a tmux window with one pane per session
+    Panes {
+        /// Session names to open
+        names: Vec<String>,
+
+        /// Also open the sessions having any of these tags (comma or semicolon separated)
+        #[arg(short, long)]
+        tags: Option<String>,
+
+        /// Send the input of one pane to all of them
+        #[arg(short, long)]
+        sync: bool,
+
+        /// Also open protected sessions, each pane still asks for the session name
+        #[arg(long)]
+        allow_protected: bool,
+    },
+
+    /// Review terminal recordings of login sessions
+    Recordings {
+        #[command(subcommand)]
+        action: RecordingsAction,
+    },
+
+    /// Sync the session inventory through a git repository
+    Sync {
+        #[command(subcommand)]
+        action: SyncAction,
+    },
+
+    /// Manage the trusted host keys of sessions
+    Hostkey {
+        #[command(subcommand)]
+        action: HostkeyAction,
+    },
+
+    /// Manage the SSH keys of sessions
+    Key {
+        #[command(subcommand)]
+        action: KeyAction,
+    },
+
+    /// Check the installed tools, the config file permissions and the session keys
+    Doctor,
+
+    /// Open a full-screen dashboard to browse, search and manage the sessions
+    Tui,
+
+    /// Collect and show facts about the hosts of sessions
+    Facts {
+        #[command(subcommand)]
+        action: FactsAction,
+    },
+
+    /// Export sessions, templates and settings, or sessions for other tools
+    #[command(args_conflicts_with_subcommands = true)]
+    Export {
+        #[command(subcommand)]
+        action: Option<ExportAction>,
+
+        /// Output format (json, yaml or toml)
+        #[arg(short, long, default_value = "toml")]
+        format: String,
+
+        /// Only export the sessions with these tags (comma or semicolon separated)
+        #[arg(short, long)]
+        tags: Option<String>,
+
+        /// Include passwords
+        #[arg(long)]
+        include_secrets: bool,
+
+        /// Output file, defaults to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Import a file made by export, or sessions from other tools
+    #[command(args_conflicts_with_subcommands = true)]
+    Import {
+        #[command(subcommand)]
+        action: Option<ImportAction>,
+
+        /// File to import
+        #[arg(required = true)]
+        file: Option<PathBuf>,
+
+        /// Format of the file (json, yaml or toml), guessed from the file by default
+        #[arg(short, long)]
+        format: Option<String>,
+
+        #[command(flatten)]
+        merge: MergeArgs,
+    },
+
+    /// Check that sessions are reachable and can log in, without opening a shell
+    Check {
+        /// Session names, all sessions by default
+        names: Vec<String>,
+
+        /// Check the sessions with these tags (comma or semicolon separated)
+        #[arg(short, long)]
+        tags: Option<String>,
+
+        /// Print the results as JSON
+        #[arg(short, long)]
+        json: bool,
+
+        /// Connection timeout in seconds
+        #[arg(long, default_value = "5")]
+        timeout: u64,
+
+        /// Repeat the checks every N seconds
+        #[arg(short, long, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "30")]
+        watch: Option<u64>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -189,6 +449,227 @@ pub enum TemplateAction {
     },
 }
 
+#[derive(Subcommand)]
+pub enum RecordingsAction {
+    /// List recordings
+    List {
+        /// Only list recordings of this session
+        session: Option<String>,
+    },
+
+    /// Replay a recording in the terminal
+    Play {
+        /// Recording id (<session>/<timestamp>) or path to a .cast file
+        id: String,
+
+        /// Playback speed multiplier
+        #[arg(short, long, default_value = "1.0")]
+        speed: f64,
+
+        /// Maximum pause between two outputs, in seconds
+        #[arg(short, long, default_value = "2.0")]
+        idle_limit: f64,
+    },
+
+    /// Export a recording
+    Export {
+        /// Recording id (<session>/<timestamp>) or path to a .cast file
+        id: String,
+
+        /// Output format (cast or txt)
+        #[arg(short, long, default_value = "cast")]
+        format: String,
+
+        /// Output file, defaults to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum SyncAction {
+    /// Use a local git repository to sync the config (secrets excluded)
+    Init {
+        /// Path of the local git repository, created if it does not exist
+        repo_path: PathBuf,
+
+        /// URL of the remote repository to pull from and push to
+        #[arg(short, long)]
+        remote: Option<String>,
+    },
+
+    /// Fetch the remote and merge its sessions into the local config
+    Pull,
+
+    /// Push the local config to the remote
+    Push,
+}
+
+#[derive(Subcommand)]
+pub enum HostkeyAction {
+    /// Show the host keys currently offered by the server of a session
+    Scan {
+        /// Session name
+        name: String,
+    },
+
+    /// Trust the host keys offered by the server of a session
+    Pin {
+        /// Session name
+        name: String,
+
+        /// Only pin the keys with these fingerprints (e.g. SHA256:...)
+        #[arg(short, long)]
+        fingerprint: Vec<String>,
+
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Check that the server still offers the pinned host keys
+    Verify {
+        /// Session name
+        name: String,
+    },
+
+    /// Remove the pinned host keys of a session
+    Forget {
+        /// Session name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum KeyAction {
+    /// Generate a dedicated keypair for a session
+    Gen {
+        /// Session name
+        name: String,
+
+        /// Replace an existing key
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Install the key of a session on its host and switch the session to key authentication
+    Deploy {
+        /// Session name
+        name: String,
+    },
+
+    /// Replace the keys of sessions with new ones and remove the old ones from the hosts
+    Rotate {
+        /// Session names
+        names: Vec<String>,
+
+        /// Rotate the sessions with these tags (comma or semicolon separated)
+        #[arg(short, long)]
+        tags: Option<String>,
+
+        /// Also rotate protected sessions when rotating several sessions
+        #[arg(long)]
+        allow_protected: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum FactsAction {
+    /// Collect OS, kernel, CPU, memory, uptime and IP addresses of the hosts
+    Refresh {
+        /// Session names, all sessions by default
+        names: Vec<String>,
+
+        /// Refresh the sessions with these tags (comma or semicolon separated)
+        #[arg(short, long)]
+        tags: Option<String>,
+    },
+
+    /// Show the collected facts of a session
+    Show {
+        /// Session name
+        name: String,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ExportAction {
+    /// Export sessions as an Ansible inventory, with tags as groups
+    Ansible {
+        /// Inventory format (ini or yaml)
+        #[arg(short, long, default_value = "ini")]
+        format: String,
+
+        /// Only export the sessions with these tags (comma or semicolon separated)
+        #[arg(short, long)]
+        tags: Option<String>,
+
+        /// Output file, defaults to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ImportAction {
+    /// Import the hosts of an Ansible inventory, with groups as tags
+    Ansible {
+        /// INI or YAML inventory file, group_vars and host_vars next to it are read too
+        inventory: PathBuf,
+
+        #[command(flatten)]
+        merge: MergeArgs,
+    },
+
+    /// Import the SSH sessions of a PuTTY registry export
+    Putty {
+        /// .reg file made with `reg export HKCU\Software\SimonTatham\PuTTY\Sessions putty.reg`
+        file: PathBuf,
+
+        #[command(flatten)]
+        merge: MergeArgs,
+    },
+
+    /// Import Remmina SSH and SFTP profiles, with groups as tags
+    Remmina {
+        /// .remmina files or directories of them, defaults to the Remmina profile directory
+        paths: Vec<PathBuf>,
+
+        #[command(flatten)]
+        merge: MergeArgs,
+    },
+
+    /// Import the SFTP sites of the FileZilla site manager, with folders as tags
+    Filezilla {
+        /// Site manager file, defaults to ~/.config/filezilla/sitemanager.xml
+        file: Option<PathBuf>,
+
+        #[command(flatten)]
+        merge: MergeArgs,
+    },
+
+    /// Import sessions from a CSV file with a header row
+    Csv {
+        /// CSV file
+        file: PathBuf,
+
+        /// Column of a session field, as field=column (fields: name, host, user, port, key,
+        /// password, tags). Columns named like the fields are used by default
+        #[arg(short, long)]
+        map: Vec<String>,
+
+        #[command(flatten)]
+        merge: MergeArgs,
+    },
+}
+
+#[derive(Args)]
+pub struct MergeArgs {
+    /// What to do with sessions and templates that already exist (skip, overwrite or rename)
+    #[arg(short, long, default_value = "skip")]
+    strategy: String,
+}
+
 #[derive(Default)]
 struct SessionParams {
     name: Option<String>,
@@ -198,6 +679,7 @@ struct SessionParams {
     auth_type: Option<String>,
     key_path: Option<PathBuf>,
     password: Option<String>,
+    totp_secret: Option<String>,
     tags: Option<String>,
 }
 
@@ -211,6 +693,7 @@ impl SessionParams {
         auth_type: Option<String>,
         key_path: Option<PathBuf>,
         password: Option<String>,
+        totp_secret: Option<String>,
         tags: Option<String>,
     ) -> Self {
         Self {
@@ -221,15 +704,47 @@ impl SessionParams {
             auth_type,
             key_path,
             password,
+            totp_secret,
             tags,
         }
     }
 }
 
-pub fn handle_command(command: Commands) -> Result<()> {
+pub fn handle_command(command: Commands, dry_run: bool) -> Result<()> {
+    let supports_dry_run = matches!(
+        command,
+        Commands::Add { .. }
+            | Commands::Modify { .. }
+            | Commands::Delete { .. }
+            | Commands::Tag { .. }
+            | Commands::Template { .. }
+            | Commands::Login { .. }
+            | Commands::Last
+            | Commands::Cp { .. }
+            | Commands::Import { .. }
+            | Commands::Rename { .. }
+            | Commands::Clone { .. }
+            | Commands::Edit { .. }
+            | Commands::Undo
+            | Commands::Redo
+            | Commands::Sync { .. }
+            | Commands::Hostkey {
+                action: HostkeyAction::Pin { .. } | HostkeyAction::Forget { .. }
+            }
+            | Commands::Key { .. }
+    );
+    if dry_run && !supports_dry_run {
+        anyhow::bail!("--dry-run is not supported by this command");
+    }
+
     match command {
         Commands::Version => version::handle_version(),
-        Commands::List { detailed, tags } => list::handle_list(detailed, tags),
+        Commands::Otp { name } => otp::handle_otp(name),
+        Commands::List {
+            detailed,
+            tags,
+            filter,
+        } => list::handle_list(detailed, tags, filter),
         Commands::Add {
             name,
             host,
@@ -238,15 +753,25 @@ pub fn handle_command(command: Commands) -> Result<()> {
             auth_type,
             key_path,
             password,
+            totp_secret,
             tags,
             template,
         } => {
             if let Some(template) = template {
-                modify::handle_add_with_template(template)
+                modify::handle_add_with_template(template, dry_run)
             } else {
-                let params =
-                    SessionParams::new(name, host, user, port, auth_type, key_path, password, tags);
-                modify::handle_add(params)
+                let params = SessionParams::new(
+                    name,
+                    host,
+                    user,
+                    port,
+                    auth_type,
+                    key_path,
+                    password,
+                    totp_secret,
+                    tags,
+                );
+                modify::handle_add(params, dry_run)
             }
         }
         Commands::Modify {
@@ -257,6 +782,7 @@ pub fn handle_command(command: Commands) -> Result<()> {
             auth_type,
             key_path,
             password,
+            totp_secret,
             tags,
         } => {
             let params = SessionParams::new(
@@ -267,37 +793,165 @@ pub fn handle_command(command: Commands) -> Result<()> {
                 auth_type,
                 key_path,
                 password,
+                totp_secret,
                 tags,
             );
-            modify::handle_modify(params)
+            modify::handle_modify(params, dry_run)
         }
-        Commands::Delete { names, tag } => {
+        Commands::Delete {
+            names,
+            tag,
+            yes,
+            allow_protected,
+        } => {
             match tag {
                 Some(tag) => {
                     // TODO: validate tag format
-                    delete::handle_delete_with_tags(tag)
+                    delete::handle_delete_with_tags(tag, yes, allow_protected, dry_run)
                 }
-                None => delete::handle_delete(names),
+                None => delete::handle_delete(names, yes, allow_protected, dry_run),
             }
         }
-        Commands::Login { name, tags } => login::handle_login(name, tags),
-        Commands::Tag { name, action, tags } => tag::handle_tag(name, action, tags),
+        Commands::Undo => journal::handle_undo(dry_run),
+        Commands::Redo => journal::handle_redo(dry_run),
+        Commands::Log { limit } => journal::handle_log(limit),
+        Commands::Login {
+            name,
+            tags,
+            record,
+            reconnect,
+            command,
+        } => login::handle_login(name, tags, record, reconnect, command, dry_run),
+        Commands::Tag { name, action, tags } => tag::handle_tag(name, action, tags, dry_run),
         Commands::Template { action } => match action {
             TemplateAction::List => template::handle_template_list(),
-            TemplateAction::Add { session, name } => template::handle_template_add(name, session),
-            TemplateAction::Delete { name } => template::handle_template_delete(name),
+            TemplateAction::Add { session, name } => {
+                template::handle_template_add(name, session, dry_run)
+            }
+            TemplateAction::Delete { name } => template::handle_template_delete(name, dry_run),
         },
         Commands::Cp {
             paths,
             src,
             dst,
             recursive,
-        } => cp::handle_cp(paths, src, dst, recursive),
+        } => cp::handle_cp(paths, src, dst, recursive, dry_run),
+        Commands::History { session, limit } => history::handle_history(session, limit),
+        Commands::Last => login::handle_last(dry_run),
+        Commands::Stats { top, unused_days } => history::handle_stats(top, unused_days),
+        Commands::Panes {
+            names,
+            tags,
+            sync,
+            allow_protected,
+        } => panes::handle_panes(names, tags, sync, allow_protected),
+        Commands::Recordings { action } => match action {
+            RecordingsAction::List { session } => recordings::handle_recordings_list(session),
+            RecordingsAction::Play {
+                id,
+                speed,
+                idle_limit,
+            } => recordings::handle_recordings_play(id, speed, idle_limit),
+            RecordingsAction::Export { id, format, output } => {
+                recordings::handle_recordings_export(id, format, output)
+            }
+        },
+        Commands::Sync { action } => match action {
+            SyncAction::Init { repo_path, remote } => {
+                sync::handle_sync_init(repo_path, remote, dry_run)
+            }
+            SyncAction::Pull => sync::handle_sync_pull(dry_run),
+            SyncAction::Push => sync::handle_sync_push(dry_run),
+        },
+        Commands::Hostkey { action } => match action {
+            HostkeyAction::Scan { name } => hostkey::handle_hostkey_scan(name),
+            HostkeyAction::Pin {
+                name,
+                fingerprint,
+                yes,
+            } => hostkey::handle_hostkey_pin(name, fingerprint, yes, dry_run),
+            HostkeyAction::Verify { name } => hostkey::handle_hostkey_verify(name),
+            HostkeyAction::Forget { name } => hostkey::handle_hostkey_forget(name, dry_run),
+        },
+        Commands::Key { action } => match action {
+            KeyAction::Gen { name, force } => key::handle_key_gen(name, force, dry_run),
+            KeyAction::Deploy { name } => key::handle_key_deploy(name, dry_run),
+            KeyAction::Rotate {
+                names,
+                tags,
+                allow_protected,
+            } => key::handle_key_rotate(names, tags, allow_protected, dry_run),
+        },
+        Commands::Doctor => doctor::handle_doctor(),
+        Commands::Tui => tui::handle_tui(),
+        Commands::Edit { names, tags } => edit::handle_edit(names, tags, dry_run),
+        Commands::Rename { old, new } => rename::handle_rename(old, new, dry_run),
+        Commands::Clone {
+            src,
+            new,
+            host,
+            user,
+            port,
+            tags,
+        } => clone::handle_clone(src, new, host, user, port, tags, dry_run),
+        Commands::Export {
+            action,
+            format,
+            tags,
+            include_secrets,
+            output,
+        } => match action {
+            Some(ExportAction::Ansible {
+                format,
+                tags,
+                output,
+            }) => export::handle_export_ansible(format, tags, output),
+            None => export::handle_export(format, tags, include_secrets, output),
+        },
+        Commands::Import {
+            action,
+            file,
+            format,
+            merge,
+        } => match action {
+            Some(ImportAction::Ansible { inventory, merge }) => {
+                import::handle_import_ansible(inventory, merge.strategy, dry_run)
+            }
+            Some(ImportAction::Putty { file, merge }) => {
+                import::handle_import_putty(file, merge.strategy, dry_run)
+            }
+            Some(ImportAction::Remmina { paths, merge }) => {
+                import::handle_import_remmina(paths, merge.strategy, dry_run)
+            }
+            Some(ImportAction::Filezilla { file, merge }) => {
+                import::handle_import_filezilla(file, merge.strategy, dry_run)
+            }
+            Some(ImportAction::Csv { file, map, merge }) => {
+                import::handle_import_csv(file, map, merge.strategy, dry_run)
+            }
+            None => import::handle_import(
+                file.context("No file to import")?,
+                format,
+                merge.strategy,
+                dry_run,
+            ),
+        },
+        Commands::Facts { action } => match action {
+            FactsAction::Refresh { names, tags } => facts::handle_facts_refresh(names, tags),
+            FactsAction::Show { name } => facts::handle_facts_show(name),
+        },
+        Commands::Check {
+            names,
+            tags,
+            json,
+            timeout,
+            watch,
+        } => check::handle_check(names, tags, json, timeout, watch),
     }
 }
 
 // Helper function to parse tags from a string
-fn parse_tags(tags_str: Option<&String>) -> HashSet<String> {
+pub(crate) fn parse_tags(tags_str: Option<&String>) -> HashSet<String> {
     tags_str
         .map(|s| {
             s.split([',', ';'])
@@ -307,3 +961,53 @@ fn parse_tags(tags_str: Option<&String>) -> HashSet<String> {
         })
         .unwrap_or_default()
 }
+
+/// Refuses to `action` several sessions at once when some of them are protected, unless
+/// `allow_protected` is given.
+fn check_protected(
+    settings: &Settings,
+    sessions: &[&Session],
+    action: &str,
+    allow_protected: bool,
+) -> Result<()> {
+    let protected: Vec<&str> = sessions
+        .iter()
+        .filter(|s| settings.is_protected(s))
+        .map(|s| s.name.as_str())
+        .collect();
+    if !allow_protected && !protected.is_empty() {
+        anyhow::bail!(
+            "Refusing to {} protected sessions in bulk: {}, use --allow-protected",
+            action,
+            protected.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Returns the sessions named in `names` or tagged with one of `tags`, or all of them
+/// when neither is given and `all_by_default` is set.
+fn select_sessions<'a>(
+    config: &'a Config,
+    names: &[String],
+    tags: Option<&String>,
+    all_by_default: bool,
+) -> Result<Vec<&'a Session>> {
+    for name in names.iter() {
+        if config.get_session(name).is_none() {
+            anyhow::bail!("Session '{}' not found", name);
+        }
+    }
+
+    let filter_tags = parse_tags(tags);
+    let select_all = all_by_default && names.is_empty() && filter_tags.is_empty();
+    let sessions: Vec<&Session> = config
+        .sessions
+        .iter()
+        .filter(|s| select_all || names.contains(&s.name) || !filter_tags.is_disjoint(&s.tags))
+        .collect();
+    if sessions.is_empty() {
+        anyhow::bail!("No SSH sessions found matching the specified names or tags");
+    }
+    Ok(sessions)
+}
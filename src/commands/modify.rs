@@ -1,45 +1,53 @@
 use anyhow::{Context, Ok, Result};
 use dialoguer::{Input, Select};
 use rpassword::read_password;
+use std::io::{self, Write};
 use std::path::PathBuf;
 
 use crate::commands::{SessionParams, parse_tags};
 use crate::config::manager::ConfigManager;
-use crate::models::session::{AuthType, Session};
+use crate::models::session::{AuthType, Session, SessionBuilder};
+use crate::utils::doctor;
 
-pub fn handle_add(params: SessionParams) -> Result<()> {
+pub fn handle_add(params: SessionParams, dry_run: bool) -> Result<()> {
     let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
     manager.load()?;
 
-    let session = if params.name.is_some() && params.host.is_some() && params.user.is_some() {
-        // Command line mode
-        // auth_type has a default value of "key", so it can safely be unwrapped
-        let auth_type = params.auth_type.unwrap().parse()?;
-
-        Session::new(
-            params.name.unwrap(),
-            params.host.unwrap(),
-            params.user.unwrap(),
-            params.port.unwrap(),
-            auth_type,
-            params.key_path,
-            params.password,
-            Some(parse_tags(params.tags.as_ref())),
-        )
-    } else {
-        // Interactive mode
-        new_session_with_default(&Session::empty_template(), true)?
-    };
-
-    session.validate()?;
+    let session =
+        if let (Some(name), Some(host), Some(user)) = (params.name, params.host, params.user) {
+            // Command line mode
+            // auth_type has a default value of "key", so it can safely be unwrapped
+            let auth_type = params.auth_type.unwrap().parse()?;
+
+            SessionBuilder::new()
+                .name(name)
+                .host(host)
+                .user(user)
+                .port(params.port.unwrap())
+                .auth_type(auth_type)
+                .private_key_path(params.key_path)
+                .password(params.password)
+                .totp_secret(params.totp_secret)
+                .tags(Some(parse_tags(params.tags.as_ref())))
+                .build()?
+        } else {
+            // Interactive mode
+            new_session_with_default(&Session::empty_template(), true)?
+        };
+
+    validate(&manager, &session)?;
     manager.config.add_session(session)?;
     manager.save()?;
-    println!("Session added successfully.");
+    if !dry_run {
+        println!("Session added successfully.");
+    }
     Ok(())
 }
 
-pub fn handle_add_with_template(name: String) -> Result<()> {
+pub fn handle_add_with_template(name: String, dry_run: bool) -> Result<()> {
     let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
     manager.load()?;
 
     let template = manager
@@ -47,18 +55,22 @@ pub fn handle_add_with_template(name: String) -> Result<()> {
         .get_template(&name)
         .context("Template not found")?;
 
-    let session = manager
+    let mut session = manager
         .config
         .get_session(&template.session)
-        .context("Session not found")?;
+        .context("Session not found")?
+        .clone();
+    template.apply_to(&mut session);
 
     // enter interactive mode
-    let new_session = new_session_with_default(session, true)?;
+    let new_session = new_session_with_default(&session, true)?;
 
-    new_session.validate()?;
+    validate(&manager, &new_session)?;
     manager.config.add_session(new_session)?;
     manager.save()?;
-    println!("Session added successfully.");
+    if !dry_run {
+        println!("Session added successfully.");
+    }
     Ok(())
 }
 
@@ -87,16 +99,18 @@ fn new_session_with_default(sess: &Session, create: bool) -> Result<Session> {
         .default(sess.port)
         .interact_text()?;
 
-    let auth_types = vec![AuthType::Key, AuthType::Password];
+    let auth_types = vec![AuthType::Key, AuthType::Password, AuthType::Totp];
     let auth_type_idx = Select::new()
         .with_prompt("Authentication type")
         .items(&auth_types)
         .default(match sess.auth_type {
             AuthType::Key => 0,
             AuthType::Password => 1,
+            AuthType::Totp => 2,
         })
         .interact()?;
-    let (auth_type, private_key_path, password) = match auth_types[auth_type_idx] {
+    let auth_type = auth_types[auth_type_idx];
+    let (private_key_path, password, totp_secret) = match auth_type {
         AuthType::Key => {
             let key_path: String = Input::new()
                 .with_prompt("Private key path")
@@ -108,9 +122,9 @@ fn new_session_with_default(sess: &Session, create: bool) -> Result<Session> {
                         .to_string(),
                 )
                 .interact_text()?;
-            (AuthType::Key, Some(PathBuf::from(key_path)), None)
+            (Some(PathBuf::from(key_path)), None, None)
         }
-        AuthType::Password => {
+        AuthType::Password | AuthType::Totp => {
             let new_pass = read_password().context("Failed to read password")?;
             let password = if new_pass.is_empty() {
                 sess.password.clone()
@@ -118,7 +132,20 @@ fn new_session_with_default(sess: &Session, create: bool) -> Result<Session> {
                 Some(new_pass)
             };
 
-            (AuthType::Password, None, password)
+            let totp_secret = if auth_type == AuthType::Totp {
+                print!("TOTP secret (base32, empty to keep the current one): ");
+                io::stdout().flush()?;
+                let new_secret = read_password().context("Failed to read TOTP secret")?;
+                if new_secret.is_empty() {
+                    sess.totp_secret.clone()
+                } else {
+                    Some(new_secret)
+                }
+            } else {
+                None
+            };
+
+            (None, password, totp_secret)
         }
     };
 
@@ -134,13 +161,10 @@ fn new_session_with_default(sess: &Session, create: bool) -> Result<Session> {
         .allow_empty(true)
         .interact_text()?;
 
-    let tags = if tags_input.is_empty() {
-        None
-    } else {
-        Some(parse_tags(Some(&tags_input)))
-    };
+    let tags = parse_tags(Some(&tags_input));
 
-    let new_session = Session::new(
+    // keep the settings that are not prompted for
+    let new_session = Session {
         name,
         host,
         user,
@@ -148,14 +172,17 @@ fn new_session_with_default(sess: &Session, create: bool) -> Result<Session> {
         auth_type,
         private_key_path,
         password,
+        totp_secret,
         tags,
-    );
+        ..sess.clone()
+    };
 
     Ok(new_session)
 }
 
-pub fn handle_modify(params: SessionParams) -> Result<()> {
+pub fn handle_modify(params: SessionParams, dry_run: bool) -> Result<()> {
     let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
     manager.load()?;
 
     let session = manager
@@ -170,34 +197,46 @@ pub fn handle_modify(params: SessionParams) -> Result<()> {
         || params.auth_type.is_some()
         || params.key_path.is_some()
         || params.password.is_some()
+        || params.totp_secret.is_some()
         || params.tags.is_some()
     {
         // Command line mode
-        // auth_type has a default value of "key", so it can safely be unwrapped
-        let auth_type = params.auth_type.unwrap().parse()?;
-
-        Session::new(
-            session.name,
-            params.host.unwrap_or(session.host),
-            params.user.unwrap_or(session.user),
-            params.port.unwrap_or(session.port),
+        let auth_type = match params.auth_type {
+            Some(auth_type) => auth_type.parse()?,
+            None => session.auth_type,
+        };
+
+        Session {
+            host: params.host.unwrap_or(session.host),
+            user: params.user.unwrap_or(session.user),
+            port: params.port.unwrap_or(session.port),
             auth_type,
-            params.key_path.or(session.private_key_path),
-            params.password.or(session.password),
-            Some(
-                params
-                    .tags
-                    .map_or_else(|| session.tags.clone(), |s| parse_tags(Some(&s))),
-            ),
-        )
+            private_key_path: params.key_path.or(session.private_key_path),
+            password: params.password.or(session.password),
+            totp_secret: params.totp_secret.or(session.totp_secret),
+            tags: params
+                .tags
+                .map_or_else(|| session.tags.clone(), |s| parse_tags(Some(&s))),
+            ..session
+        }
     } else {
         // Interactive mode
         new_session_with_default(&session, false)?
     };
 
-    new_session.validate()?;
+    validate(&manager, &new_session)?;
     manager.config.update_session(new_session)?;
     manager.save()?;
-    println!("Session modified successfully.");
+    if !dry_run {
+        println!("Session modified successfully.");
+    }
     Ok(())
 }
+
+fn validate(manager: &ConfigManager, session: &Session) -> Result<()> {
+    if manager.config.settings.deep_validation {
+        doctor::validate_deep(session)
+    } else {
+        session.validate()
+    }
+}
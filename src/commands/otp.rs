@@ -0,0 +1,25 @@
+use anyhow::{Context, Result};
+
+use crate::config::manager::ConfigManager;
+use crate::utils::{time, totp};
+
+/// Prints the current one-time code of the session, and how long it stays valid on stderr,
+/// so the code alone can be piped.
+pub fn handle_otp(name: String) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+
+    let session = manager
+        .config
+        .get_session(&name)
+        .with_context(|| format!("Session '{}' not found", name))?;
+    let secret = session
+        .totp_secret
+        .as_ref()
+        .with_context(|| format!("Session '{}' has no TOTP secret", name))?;
+
+    let now = time::now();
+    println!("{}", totp::code(secret, now)?);
+    eprintln!("Valid for {}s", totp::STEP_SECS - now % totp::STEP_SECS);
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use anyhow::{Context, Result};
+use std::env;
+use std::process::{self, Command};
+
+use crate::commands::{check_protected, parse_tags};
+use crate::config::manager::ConfigManager;
+use crate::models::session::Session;
+use crate::utils::ssh::shell_quote;
+
+/// Opens a local tmux window with one pane logged into each selected session.
+/// Runs in a new tmux session when not called from inside tmux.
+pub fn handle_panes(
+    names: Vec<String>,
+    tags: Option<String>,
+    sync: bool,
+    allow_protected: bool,
+) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+
+    for name in names.iter() {
+        if manager.config.get_session(name).is_none() {
+            anyhow::bail!("Session '{}' not found", name);
+        }
+    }
+
+    let filter_tags = parse_tags(tags.as_ref());
+    let sessions: Vec<&Session> = manager
+        .config
+        .sessions
+        .iter()
+        .filter(|s| names.contains(&s.name) || !filter_tags.is_disjoint(&s.tags))
+        .collect();
+    if sessions.is_empty() {
+        anyhow::bail!("No SSH sessions found matching the specified names or tags");
+    }
+    check_protected(&manager.config.settings, &sessions, "open", allow_protected)?;
+
+    let devlg = env::current_exe().context("Failed to get the path of devlg")?;
+    let login = |session: &Session| {
+        format!(
+            "{} login {}",
+            shell_quote(&devlg.to_string_lossy()),
+            shell_quote(&session.name)
+        )
+    };
+    let window_name = tags.unwrap_or_else(|| "devlg".to_string());
+
+    let inside_tmux = env::var_os("TMUX").is_some();
+    let tmux_session = format!("devlg-{}", process::id());
+    let window = if inside_tmux {
+        tmux(&[
+            "new-window",
+            "-P",
+            "-F",
+            "#{window_id}",
+            "-n",
+            &window_name,
+            &login(sessions[0]),
+        ])?
+    } else {
+        tmux(&[
+            "new-session",
+            "-d",
+            "-P",
+            "-F",
+            "#{window_id}",
+            "-s",
+            &tmux_session,
+            "-n",
+            &window_name,
+            &login(sessions[0]),
+        ])?
+    };
+
+    for session in sessions.iter().skip(1) {
+        tmux(&["split-window", "-t", &window, &login(session)])?;
+        // re-tile after each split, tmux refuses to split a pane that got too small
+        tmux(&["select-layout", "-t", &window, "tiled"])?;
+    }
+
+    if sync {
+        tmux(&[
+            "set-window-option",
+            "-t",
+            &window,
+            "synchronize-panes",
+            "on",
+        ])?;
+    }
+
+    println!("Opened {} panes in tmux window {}.", sessions.len(), window);
+
+    if !inside_tmux {
+        let status = Command::new("tmux")
+            .args(["attach-session", "-t", &tmux_session])
+            .status()
+            .context("Failed to execute tmux")?;
+        if !status.success() {
+            anyhow::bail!("tmux attach failed with exit code: {}", status);
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a tmux command and returns its trimmed stdout.
+fn tmux(args: &[&str]) -> Result<String> {
+    let output = Command::new("tmux")
+        .args(args)
+        .output()
+        .context("Failed to execute tmux, is it installed?")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "tmux {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
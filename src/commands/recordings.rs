@@ -0,0 +1,135 @@
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use crate::config::manager::ConfigManager;
+use crate::utils::asciicast::{Recording, strip_escapes};
+use crate::utils::time::{format_age, format_duration};
+
+pub fn handle_recordings_list(session: Option<String>) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+    let dir = manager.config.settings.recordings_dir()?;
+
+    let mut ids = Vec::new();
+    if dir.exists() {
+        for session_dir in fs::read_dir(&dir)? {
+            let session_dir = session_dir?.path();
+            let session_name = session_dir
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .to_string();
+            if !session_dir.is_dir() || session.as_ref().is_some_and(|s| s != &session_name) {
+                continue;
+            }
+
+            for file in fs::read_dir(&session_dir)? {
+                let file = file?.path();
+                if file.extension().is_some_and(|ext| ext == "cast") {
+                    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+                    ids.push(format!("{}/{}", session_name, stem));
+                }
+            }
+        }
+    }
+
+    if ids.is_empty() {
+        println!("No recordings found.");
+        return Ok(());
+    }
+    ids.sort();
+
+    println!(
+        "{:<40} {:<10} {:<10} {:<10}",
+        "Id", "When", "Duration", "Size"
+    );
+    println!("{:-<73}", "");
+    for id in ids {
+        let path = dir.join(format!("{}.cast", id));
+        let size = fs::metadata(&path)?.len();
+        let (when, duration) = match Recording::load(&path) {
+            Ok(recording) => (
+                recording
+                    .header
+                    .timestamp
+                    .map_or_else(|| "N/A".to_string(), format_age),
+                format_duration(recording.duration() as u64),
+            ),
+            Err(_) => ("N/A".to_string(), "invalid".to_string()),
+        };
+        println!(
+            "{:<40} {:<10} {:<10} {:<10}",
+            id,
+            when,
+            duration,
+            format!("{}K", size.div_ceil(1024))
+        );
+    }
+
+    Ok(())
+}
+
+pub fn handle_recordings_play(id: String, speed: f64, idle_limit: f64) -> Result<()> {
+    if speed <= 0.0 {
+        anyhow::bail!("Speed must be greater than 0");
+    }
+    let recording = Recording::load(&recording_path(&id)?)?;
+
+    let mut stdout = io::stdout();
+    let mut previous = 0.0;
+    for event in recording.events.iter().filter(|e| e.1 == "o") {
+        let delay = ((event.0 - previous) / speed).clamp(0.0, idle_limit);
+        thread::sleep(Duration::from_secs_f64(delay));
+        previous = event.0;
+
+        stdout.write_all(event.2.as_bytes())?;
+        stdout.flush()?;
+    }
+    println!();
+
+    Ok(())
+}
+
+pub fn handle_recordings_export(id: String, format: String, output: Option<PathBuf>) -> Result<()> {
+    let path = recording_path(&id)?;
+    let content = match format.to_lowercase().as_str() {
+        "cast" => fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read recording at {:?}", path))?,
+        "txt" => strip_escapes(&Recording::load(&path)?.output()),
+        _ => anyhow::bail!("Invalid format. Use 'cast' or 'txt'"),
+    };
+
+    match output {
+        Some(output) => {
+            fs::write(&output, content).with_context(|| format!("Failed to write {:?}", output))?;
+            println!("Recording exported to {}.", output.display());
+        }
+        None => io::stdout().write_all(content.as_bytes())?,
+    }
+
+    Ok(())
+}
+
+/// Resolves a recording id (`<session>/<timestamp>`) or a path to a `.cast` file.
+fn recording_path(id: &str) -> Result<PathBuf> {
+    let path = PathBuf::from(id);
+    if path.is_file() {
+        return Ok(path);
+    }
+
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+    let path = manager
+        .config
+        .settings
+        .recordings_dir()?
+        .join(format!("{}.cast", id));
+    if !path.is_file() {
+        anyhow::bail!("Recording '{}' not found", id);
+    }
+    Ok(path)
+}
@@ -0,0 +1,76 @@
+use anyhow::{Context, Result};
+use std::fs;
+
+use crate::config::facts::FactsStore;
+use crate::config::history::HistoryStore;
+use crate::config::manager::{ConfigManager, Settings};
+use crate::models::session::Session;
+
+/// Renames a session, along with the templates based on it and its history, facts and
+/// recordings.
+pub fn handle_rename(old: String, new: String, dry_run: bool) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
+    manager.load()?;
+
+    let session = manager
+        .config
+        .get_session(&old)
+        .with_context(|| format!("Session '{}' not found", old))?;
+    let mut renamed = Session {
+        name: new.clone(),
+        ..session.clone()
+    };
+    renamed.validate()?;
+    // keep attaching to the multiplexer session that was named after the session
+    if renamed.multiplexer.is_some() && renamed.multiplexer_session.is_none() {
+        renamed.multiplexer_session = Some(old.clone());
+    }
+
+    manager
+        .config
+        .replace_sessions(vec![(old.clone(), renamed)])?;
+    manager.save()?;
+    if !dry_run {
+        move_session_data(&manager.config.settings, &old, &new);
+        println!("Session '{}' renamed to '{}'.", old, new);
+    }
+    Ok(())
+}
+
+/// Moves the history, facts and recordings of the session `old` to the session `new`. The
+/// config is already saved by then, so failures are only warnings.
+pub(crate) fn move_session_data(settings: &Settings, old: &str, new: &str) {
+    let warn = |what: &str, result: Result<()>| {
+        if let Err(e) = result {
+            eprintln!("Warning: failed to move the {} of '{}': {:#}", what, old, e);
+        }
+    };
+
+    let mut history = HistoryStore::new(None);
+    warn(
+        "history",
+        history.load().and_then(|_| history.rename(old, new)),
+    );
+
+    let mut facts = FactsStore::new(None);
+    warn(
+        "facts",
+        facts.load().and_then(|_| {
+            facts.rename(old, new);
+            facts.save()
+        }),
+    );
+
+    warn(
+        "recordings",
+        settings.recordings_dir().and_then(|dir| {
+            let (from, to) = (dir.join(old), dir.join(new));
+            if from.exists() && !to.exists() {
+                fs::rename(&from, &to)
+                    .with_context(|| format!("Failed to move {:?} to {:?}", from, to))?;
+            }
+            Ok(())
+        }),
+    );
+}
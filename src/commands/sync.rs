@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use dialoguer::Select;
+use std::path::PathBuf;
+
+use crate::config::manager::{Config, ConfigManager};
+use crate::config::sync::{self, Conflict, Side, SyncConfig};
+use crate::utils::runner;
+
+pub fn handle_sync_init(repo_path: PathBuf, remote: Option<String>, dry_run: bool) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
+    manager.load()?;
+
+    let sync_config = if dry_run {
+        println!(
+            "Would set up {} as the sync repository.",
+            repo_path.display()
+        );
+        SyncConfig::new(std::path::absolute(&repo_path)?)
+    } else {
+        sync::init(&repo_path, remote.as_deref())?
+    };
+
+    // the repository may already hold the inventory of another machine
+    if let Some(existing) = sync::read_checkout(&sync_config)? {
+        manager.config = sync::merge_configs(
+            &Config::default(),
+            &manager.config,
+            &existing,
+            &mut prompt_conflict,
+        )?;
+    }
+
+    manager.config.sync = Some(sync_config.clone());
+    manager.save()?;
+    if !dry_run {
+        println!(
+            "Sync initialized with repository {}.",
+            sync_config.repo.display()
+        );
+    }
+    Ok(())
+}
+
+/// Merges the changes of the remote into the local config. A dry run still fetches, but
+/// neither commits nor merges.
+pub fn handle_sync_pull(dry_run: bool) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
+    manager.load()?;
+    let sync_config = sync_config(&manager)?;
+
+    if !dry_run {
+        sync::commit(&sync_config, &manager.config)?;
+    }
+    let Some(upstream) = sync::fetch(&sync_config)? else {
+        println!("Nothing to pull, the remote branch does not exist yet.");
+        return Ok(());
+    };
+    if upstream.up_to_date {
+        println!("Already up to date.");
+        return Ok(());
+    }
+
+    let merged = sync::merge_configs(
+        &upstream.base,
+        &manager.config,
+        &upstream.remote,
+        &mut prompt_conflict,
+    )?;
+    manager.config = merged;
+    if dry_run {
+        return manager.save();
+    }
+    sync::begin_merge(&sync_config, &upstream)?;
+    if let Err(e) = manager.save() {
+        let _ = sync::abort_merge(&sync_config);
+        return Err(e);
+    }
+    println!("Pulled changes from {}.", upstream.remote_ref);
+    Ok(())
+}
+
+pub fn handle_sync_push(dry_run: bool) -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+    let sync_config = sync_config(&manager)?;
+
+    if dry_run {
+        for line in sync::pending_changes(&sync_config, &manager.config)? {
+            println!("{}", line);
+        }
+        return runner::dry_run(true, || sync::push(&sync_config));
+    }
+    sync::commit(&sync_config, &manager.config)?;
+    sync::push(&sync_config)?;
+    println!("Pushed changes to {}.", sync_config.remote);
+    Ok(())
+}
+
+fn sync_config(manager: &ConfigManager) -> Result<SyncConfig> {
+    manager
+        .config
+        .sync
+        .clone()
+        .context("Sync is not initialized, run `devlg sync init <repo-path>` first")
+}
+
+fn prompt_conflict(conflict: &Conflict) -> Result<Side> {
+    println!(
+        "Conflict: {} '{}' was changed on both sides.",
+        conflict.kind, conflict.name
+    );
+    println!(
+        "--- local ---\n{}",
+        conflict.local.as_deref().unwrap_or("(deleted)\n")
+    );
+    println!(
+        "--- remote ---\n{}",
+        conflict.remote.as_deref().unwrap_or("(deleted)\n")
+    );
+
+    let selection = Select::new()
+        .with_prompt("Which version do you want to keep?")
+        .items(&["local", "remote"])
+        .default(0)
+        .interact()?;
+
+    Ok(if selection == 0 {
+        Side::Local
+    } else {
+        Side::Remote
+    })
+}
@@ -4,8 +4,9 @@ use std::collections::HashSet;
 use crate::commands::parse_tags;
 use crate::config::manager::ConfigManager;
 
-pub fn handle_tag(name: String, action: String, tags: Option<String>) -> Result<()> {
+pub fn handle_tag(name: String, action: String, tags: Option<String>, dry_run: bool) -> Result<()> {
     let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
     manager.load()?;
 
     let session = manager
@@ -21,7 +22,6 @@ pub fn handle_tag(name: String, action: String, tags: Option<String>) -> Result<
             if let Some(tags_str) = tags {
                 let new_tags = parse_tags(Some(&tags_str));
                 session_tags.extend(new_tags);
-                println!("Tags added to session '{}'.", name);
             } else {
                 anyhow::bail!("Tags must be specified for 'add' action");
             }
@@ -30,7 +30,6 @@ pub fn handle_tag(name: String, action: String, tags: Option<String>) -> Result<
             if let Some(tags_str) = tags {
                 let tags_to_remove = parse_tags(Some(&tags_str));
                 session_tags.retain(|tag| !tags_to_remove.contains(tag));
-                println!("Tags removed from session '{}'.", name);
             } else {
                 anyhow::bail!("Tags must be specified for 'remove' action");
             }
@@ -47,10 +46,18 @@ pub fn handle_tag(name: String, action: String, tags: Option<String>) -> Result<
         _ => anyhow::bail!("Invalid action. Use 'add', 'remove', or 'list'"),
     }
 
+    let message = if action.eq_ignore_ascii_case("add") {
+        format!("Tags added to session '{}'.", name)
+    } else {
+        format!("Tags removed from session '{}'.", name)
+    };
     let mut updated_session = session;
     updated_session.tags = session_tags.into_iter().collect();
     manager.config.update_session(updated_session)?;
     manager.save()?;
+    if !dry_run {
+        println!("{}", message);
+    }
 
     Ok(())
 }
@@ -2,22 +2,33 @@ use crate::config::manager::ConfigManager;
 use crate::models::session::Template;
 use anyhow::Result;
 
-pub fn handle_template_add(name: String, session: String) -> Result<()> {
+pub fn handle_template_add(name: String, session: String, dry_run: bool) -> Result<()> {
     let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
     manager.load()?;
 
-    manager.config.add_template(Template { name, session })?;
+    manager.config.add_template(Template {
+        name,
+        session,
+        ssh_options: Default::default(),
+        extra_args: Vec::new(),
+    })?;
     manager.save()?;
-    println!("Template added successfully.");
+    if !dry_run {
+        println!("Template added successfully.");
+    }
     Ok(())
 }
 
-pub fn handle_template_delete(name: String) -> Result<()> {
+pub fn handle_template_delete(name: String, dry_run: bool) -> Result<()> {
     let mut manager = ConfigManager::new(None);
+    manager.dry_run = dry_run;
     manager.load()?;
     manager.config.remove_template(&name)?;
     manager.save()?;
-    println!("Template deleted successfully.");
+    if !dry_run {
+        println!("Template deleted successfully.");
+    }
     Ok(())
 }
 
@@ -0,0 +1,10 @@
+use anyhow::Result;
+
+use crate::config::manager::ConfigManager;
+use crate::tui;
+
+pub fn handle_tui() -> Result<()> {
+    let mut manager = ConfigManager::new(None);
+    manager.load()?;
+    tui::run(manager)
+}
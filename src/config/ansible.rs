@@ -0,0 +1,581 @@
+use crate::models::session::{AuthType, Session, SessionBuilder};
+use crate::utils::keys;
+use anyhow::{Context, Result};
+use serde_yaml::{Mapping, Value};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+type Vars = BTreeMap<String, String>;
+
+/// Groups every host is implicitly part of, never turned into tags.
+const IMPLICIT_GROUPS: [&str; 2] = ["all", "ungrouped"];
+
+/// Renders sessions as an INI inventory: every host with its connection variables, then
+/// one group per tag.
+pub fn to_ini(sessions: &[&Session]) -> String {
+    let mut out = String::new();
+    for session in sessions {
+        out.push_str(&session.name);
+        for (key, value) in host_vars(session) {
+            out.push_str(&format!(" {}={}", key, ini_quote(&value)));
+        }
+        out.push('\n');
+    }
+
+    for (group, hosts) in groups(sessions) {
+        out.push_str(&format!("\n[{}]\n", group));
+        for host in hosts {
+            out.push_str(&host);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// Renders sessions as a YAML inventory, with the same layout as [`to_ini`].
+pub fn to_yaml(sessions: &[&Session]) -> Result<String> {
+    let mut hosts = Mapping::new();
+    for session in sessions {
+        let vars: Mapping = host_vars(session)
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match key.as_str() {
+                    "ansible_port" => Value::from(session.port),
+                    _ => Value::from(value),
+                };
+                (Value::from(key), value)
+            })
+            .collect();
+        hosts.insert(Value::from(session.name.clone()), Value::Mapping(vars));
+    }
+
+    let mut children = Mapping::new();
+    for (group, members) in groups(sessions) {
+        let members: Mapping = members
+            .into_iter()
+            .map(|host| (Value::from(host), Value::Null))
+            .collect();
+        let mut group_def = Mapping::new();
+        group_def.insert("hosts".into(), Value::Mapping(members));
+        children.insert(Value::from(group), Value::Mapping(group_def));
+    }
+
+    let mut all = Mapping::new();
+    all.insert("hosts".into(), Value::Mapping(hosts));
+    if !children.is_empty() {
+        all.insert("children".into(), Value::Mapping(children));
+    }
+    let mut root = Mapping::new();
+    root.insert("all".into(), Value::Mapping(all));
+    Ok(serde_yaml::to_string(&root)?)
+}
+
+/// Connection variables of a session. Passwords are never exported.
+fn host_vars(session: &Session) -> Vars {
+    let mut vars = Vars::new();
+    if session.host != session.name {
+        vars.insert("ansible_host".to_string(), session.host.clone());
+    }
+    vars.insert("ansible_user".to_string(), session.user.clone());
+    vars.insert("ansible_port".to_string(), session.port.to_string());
+    if session.auth_type == AuthType::Key
+        && let Some(key_path) = &session.private_key_path
+    {
+        vars.insert(
+            "ansible_ssh_private_key_file".to_string(),
+            key_path.to_string_lossy().to_string(),
+        );
+    }
+
+    let ssh_args: Vec<String> = session
+        .ssh_options
+        .iter()
+        .map(|(key, value)| format!("-o {}={}", key, value))
+        .chain(session.extra_args.iter().cloned())
+        .collect();
+    if !ssh_args.is_empty() {
+        vars.insert("ansible_ssh_extra_args".to_string(), ssh_args.join(" "));
+    }
+    vars
+}
+
+/// Tags as groups, with names made valid for Ansible.
+fn groups(sessions: &[&Session]) -> BTreeMap<String, Vec<String>> {
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for session in sessions {
+        let tags: BTreeSet<String> = session.tags.iter().map(|t| group_name(t)).collect();
+        for tag in tags {
+            groups.entry(tag).or_default().push(session.name.clone());
+        }
+    }
+    groups
+}
+
+fn group_name(tag: &str) -> String {
+    let name: String = tag
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if name.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{}", name)
+    } else {
+        name
+    }
+}
+
+fn ini_quote(value: &str) -> String {
+    if value.contains(char::is_whitespace) || value.contains(['"', '\'', '#']) {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Hosts, groups and variables of an inventory.
+#[derive(Debug, Default)]
+struct Inventory {
+    /// Hosts in order of appearance, with their inline variables
+    hosts: Vec<(String, Vars)>,
+    /// Direct members of every group
+    members: BTreeMap<String, BTreeSet<String>>,
+    /// Child groups of every group
+    children: BTreeMap<String, BTreeSet<String>>,
+    group_vars: BTreeMap<String, Vars>,
+}
+
+impl Inventory {
+    fn add_host(&mut self, host: &str, vars: Vars) {
+        match self.hosts.iter_mut().find(|(name, _)| name == host) {
+            Some((_, existing)) => existing.extend(vars),
+            None => self.hosts.push((host.to_string(), vars)),
+        }
+    }
+
+    /// Groups of `host`, including the parents of its groups, ordered from the most
+    /// general to the most specific so that their variables can be layered.
+    fn groups_of(&self, host: &str) -> Vec<String> {
+        let mut groups: BTreeMap<String, usize> = BTreeMap::new();
+        for (group, members) in self.members.iter() {
+            if members.contains(host) {
+                groups.insert(group.clone(), self.depth(group, &mut HashSet::new()));
+            }
+        }
+        // walk up to the parents of the groups
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (parent, children) in self.children.iter() {
+                if !groups.contains_key(parent) && children.iter().any(|c| groups.contains_key(c)) {
+                    groups.insert(parent.clone(), self.depth(parent, &mut HashSet::new()));
+                    changed = true;
+                }
+            }
+        }
+
+        let mut groups: Vec<(String, usize)> = groups.into_iter().collect();
+        groups.sort_by_key(|(name, depth)| (*depth, name.clone()));
+        groups.into_iter().map(|(name, _)| name).collect()
+    }
+
+    /// Distance of `group` from the top of the group hierarchy.
+    fn depth(&self, group: &str, seen: &mut HashSet<String>) -> usize {
+        if !seen.insert(group.to_string()) {
+            return 0;
+        }
+        self.children
+            .iter()
+            .filter(|(_, children)| children.contains(group))
+            .map(|(parent, _)| self.depth(parent, seen) + 1)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+/// Reads an INI or YAML inventory, along with the group_vars and host_vars directories
+/// next to it, and turns its hosts into sessions.
+pub fn import(path: &Path) -> Result<Vec<Session>> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read inventory {:?}", path))?;
+    let is_yaml = path
+        .extension()
+        .is_some_and(|ext| ext == "yml" || ext == "yaml")
+        || content.trim_start().starts_with("---")
+        || content.trim_start().starts_with("all:");
+    let inventory = if is_yaml {
+        parse_yaml(&content)?
+    } else {
+        parse_ini(&content)?
+    };
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut sessions = Vec::new();
+    for (host, inline_vars) in inventory.hosts.iter() {
+        let groups = inventory.groups_of(host);
+
+        let mut vars = Vars::new();
+        vars.extend(inventory.group_vars.get("all").cloned().unwrap_or_default());
+        vars.extend(read_vars_dir(&dir.join("group_vars"), "all")?);
+        for group in groups.iter().filter(|g| g.as_str() != "all") {
+            vars.extend(inventory.group_vars.get(group).cloned().unwrap_or_default());
+            vars.extend(read_vars_dir(&dir.join("group_vars"), group)?);
+        }
+        vars.extend(inline_vars.clone());
+        vars.extend(read_vars_dir(&dir.join("host_vars"), host)?);
+
+        let tags = groups
+            .into_iter()
+            .filter(|g| !IMPLICIT_GROUPS.contains(&g.as_str()))
+            .collect();
+        // one bad host doesn't spoil the rest of the inventory
+        match session_from_vars(host, &vars, tags) {
+            Ok(session) => sessions.push(session),
+            Err(e) => eprintln!("Warning: skipping host '{}': {:#}", host, e),
+        }
+    }
+    Ok(sessions)
+}
+
+fn session_from_vars(name: &str, vars: &Vars, tags: HashSet<String>) -> Result<Session> {
+    let get = |keys: &[&str]| keys.iter().find_map(|k| vars.get(*k).cloned());
+
+    let host = get(&["ansible_host", "ansible_ssh_host"]).unwrap_or_else(|| name.to_string());
+    let user = get(&["ansible_user", "ansible_ssh_user"])
+        .or_else(|| std::env::var("USER").ok())
+        .with_context(|| format!("Host '{}' has no ansible_user", name))?;
+    let port = match get(&["ansible_port", "ansible_ssh_port"]) {
+        Some(port) => port
+            .parse()
+            .with_context(|| format!("Invalid ansible_port '{}' of host '{}'", port, name))?,
+        None => 22,
+    };
+    let password = get(&["ansible_password", "ansible_ssh_pass"]);
+    let key_path = get(&["ansible_ssh_private_key_file", "ansible_private_key_file"]);
+    let (auth_type, key_path) = match (key_path, &password) {
+        (Some(key_path), _) => (AuthType::Key, Some(PathBuf::from(key_path))),
+        (None, Some(_)) => (AuthType::Password, None),
+        // ssh would use the default key
+        (None, None) => (AuthType::Key, Some(keys::default_key_path())),
+    };
+
+    let mut session = SessionBuilder::new()
+        .name(name.to_string())
+        .host(host)
+        .user(user)
+        .port(port)
+        .auth_type(auth_type)
+        .private_key_path(key_path)
+        .password(password.filter(|_| auth_type == AuthType::Password))
+        .tags(Some(tags))
+        .build()?;
+
+    let args: Vec<String> = ["ansible_ssh_common_args", "ansible_ssh_extra_args"]
+        .iter()
+        .filter_map(|k| vars.get(*k))
+        .flat_map(|v| split_args(v))
+        .collect();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let option = match arg.strip_prefix("-o") {
+            Some("") => args.next(),
+            Some(option) => Some(option.to_string()),
+            None => None,
+        };
+        match option.as_deref().and_then(|o| o.split_once('=')) {
+            Some((key, value)) => {
+                session
+                    .ssh_options
+                    .insert(key.trim().to_string(), value.trim().to_string());
+            }
+            None => session.extra_args.push(option.unwrap_or(arg)),
+        }
+    }
+
+    Ok(session)
+}
+
+fn parse_ini(content: &str) -> Result<Inventory> {
+    let mut inventory = Inventory::default();
+    // hosts before the first section are ungrouped
+    let mut section = ("ungrouped".to_string(), "hosts".to_string());
+
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            section = match header.split_once(':') {
+                Some((group, kind)) => (group.to_string(), kind.to_string()),
+                None => (header.to_string(), "hosts".to_string()),
+            };
+            continue;
+        }
+
+        let words = split_args(line);
+        let (group, kind) = (&section.0, section.1.as_str());
+        match kind {
+            "hosts" => {
+                let host = &words[0];
+                let vars = words[1..]
+                    .iter()
+                    .filter_map(|w| w.split_once('='))
+                    .map(|(k, v)| (k.to_string(), v.to_string()))
+                    .collect();
+                inventory.add_host(host, vars);
+                inventory
+                    .members
+                    .entry(group.clone())
+                    .or_default()
+                    .insert(host.clone());
+            }
+            "vars" => {
+                let (key, value) = line
+                    .split_once('=')
+                    .with_context(|| format!("Invalid variable at line {}", number + 1))?;
+                let value = split_args(value).join(" ");
+                inventory
+                    .group_vars
+                    .entry(group.clone())
+                    .or_default()
+                    .insert(key.trim().to_string(), value);
+            }
+            "children" => {
+                inventory
+                    .children
+                    .entry(group.clone())
+                    .or_default()
+                    .insert(words[0].clone());
+            }
+            _ => anyhow::bail!("Unknown section type '{}' at line {}", kind, number + 1),
+        }
+    }
+    Ok(inventory)
+}
+
+fn parse_yaml(content: &str) -> Result<Inventory> {
+    let root: Mapping = serde_yaml::from_str(content).context("Failed to parse YAML inventory")?;
+    let mut inventory = Inventory::default();
+    for (group, def) in root.iter() {
+        let group = value_string(group).context("Invalid group name")?;
+        parse_yaml_group(&mut inventory, &group, def)?;
+    }
+    Ok(inventory)
+}
+
+fn parse_yaml_group(inventory: &mut Inventory, group: &str, def: &Value) -> Result<()> {
+    let Some(def) = def.as_mapping() else {
+        return Ok(());
+    };
+
+    if let Some(hosts) = def.get("hosts").and_then(Value::as_mapping) {
+        for (host, vars) in hosts.iter() {
+            let host = value_string(host).context("Invalid host name")?;
+            inventory.add_host(&host, mapping_vars(vars));
+            inventory
+                .members
+                .entry(group.to_string())
+                .or_default()
+                .insert(host);
+        }
+    }
+    if let Some(vars) = def.get("vars") {
+        inventory
+            .group_vars
+            .entry(group.to_string())
+            .or_default()
+            .extend(mapping_vars(vars));
+    }
+    if let Some(children) = def.get("children").and_then(Value::as_mapping) {
+        for (child, child_def) in children.iter() {
+            let child = value_string(child).context("Invalid group name")?;
+            inventory
+                .children
+                .entry(group.to_string())
+                .or_default()
+                .insert(child.clone());
+            parse_yaml_group(inventory, &child, child_def)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads the variables of a group or host from `<dir>/<name>[.yml|.yaml]`, or from all
+/// the files in `<dir>/<name>/`.
+fn read_vars_dir(dir: &Path, name: &str) -> Result<Vars> {
+    let mut files = Vec::new();
+    for candidate in [
+        dir.join(name),
+        dir.join(format!("{}.yml", name)),
+        dir.join(format!("{}.yaml", name)),
+    ] {
+        if candidate.is_file() {
+            files.push(candidate);
+        } else if candidate.is_dir() {
+            let mut entries: Vec<PathBuf> = fs::read_dir(&candidate)?
+                .filter_map(|e| e.ok().map(|e| e.path()))
+                .filter(|p| p.is_file())
+                .collect();
+            entries.sort();
+            files.extend(entries);
+        }
+    }
+
+    let mut vars = Vars::new();
+    for file in files {
+        let content = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read variables file {:?}", file))?;
+        let value: Value = serde_yaml::from_str(&content)
+            .with_context(|| format!("Failed to parse variables file {:?}", file))?;
+        vars.extend(mapping_vars(&value));
+    }
+    Ok(vars)
+}
+
+/// Scalar variables of a YAML mapping; encrypted and structured values are ignored.
+fn mapping_vars(value: &Value) -> Vars {
+    value
+        .as_mapping()
+        .map(|mapping| {
+            mapping
+                .iter()
+                .filter_map(|(k, v)| Some((value_string(k)?, value_string(v)?)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn value_string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+/// Splits a line into words, keeping quoted strings together.
+fn split_args(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote = None;
+    let mut in_word = false;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), '\\') => word.extend(chars.next()),
+            (Some(_), c) => word.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, '#') if !in_word => break,
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ansible_round_trip() -> Result<()> {
+        let mut web = Session::new(
+            "web".to_string(),
+            "10.0.0.5".to_string(),
+            "deploy".to_string(),
+            2222,
+            AuthType::Key,
+            Some(PathBuf::from("~/.ssh/web key")),
+            None,
+            Some(HashSet::from(["prod".to_string(), "eu-west".to_string()])),
+        );
+        web.ssh_options
+            .insert("ProxyJump".to_string(), "bastion".to_string());
+        let db = Session::new(
+            "db".to_string(),
+            "db".to_string(),
+            "admin".to_string(),
+            22,
+            AuthType::Password,
+            None,
+            Some("secret".to_string()),
+            None,
+        );
+
+        let dir = tempfile::tempdir()?;
+        for (file, content) in [
+            ("hosts.ini", to_ini(&[&web, &db])),
+            ("hosts.yml", to_yaml(&[&web, &db])?),
+        ] {
+            assert!(!content.contains("secret"));
+            let path = dir.path().join(file);
+            fs::write(&path, content)?;
+
+            let sessions = import(&path)?;
+            assert_eq!(sessions.len(), 2);
+            assert_eq!(sessions[0].host, web.host);
+            assert_eq!(sessions[0].port, web.port);
+            assert_eq!(sessions[0].private_key_path, web.private_key_path);
+            assert_eq!(sessions[0].ssh_options, web.ssh_options);
+            assert_eq!(
+                sessions[0].tags,
+                HashSet::from(["prod".to_string(), "eu_west".to_string()])
+            );
+            assert_eq!(sessions[1].user, "admin");
+            assert!(sessions[1].tags.is_empty());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_ansible_import_vars() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::create_dir_all(dir.path().join("group_vars").join("prod"))?;
+        fs::create_dir_all(dir.path().join("host_vars"))?;
+        fs::write(
+            dir.path().join("inventory"),
+            "bad ansible_port=0\nnouser ansible_user=\"\"\n\n\
+             [web]\nweb1 ansible_host=10.0.0.1\nweb2\n\n[prod:children]\nweb\n\n\
+             [all:vars]\nansible_user=root\nansible_port=2200\n",
+        )?;
+        fs::write(
+            dir.path().join("group_vars").join("prod").join("ssh.yml"),
+            "ansible_user: deploy\nansible_ssh_private_key_file: ~/.ssh/prod\n",
+        )?;
+        fs::write(
+            dir.path().join("host_vars").join("web2.yml"),
+            "ansible_port: 22\nansible_password: !vault |\n  $ANSIBLE_VAULT;1.1;AES256\n",
+        )?;
+
+        // the hosts with an invalid port or an empty user are skipped
+        let sessions = import(&dir.path().join("inventory"))?;
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(sessions[0].host, "10.0.0.1");
+        assert_eq!(sessions[0].user, "deploy");
+        assert_eq!(sessions[0].port, 2200);
+        assert_eq!(
+            sessions[0].tags,
+            HashSet::from(["web".to_string(), "prod".to_string()])
+        );
+        assert_eq!(sessions[1].host, "web2");
+        assert_eq!(sessions[1].port, 22);
+        assert_eq!(sessions[1].auth_type, AuthType::Key);
+        Ok(())
+    }
+}
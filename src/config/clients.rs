@@ -0,0 +1,521 @@
+//! Importers for the session stores of other SSH clients.
+
+use crate::models::session::{AuthType, Session, SessionBuilder};
+use crate::utils::keys;
+use anyhow::{Context, Result};
+use base64::Engine;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PUTTY_SESSIONS: &str = "\\Software\\SimonTatham\\PuTTY\\Sessions\\";
+
+/// Reads the SSH sessions of a PuTTY registry export, made with
+/// `reg export HKCU\Software\SimonTatham\PuTTY\Sessions putty.reg`.
+pub fn import_putty(path: &Path) -> Result<Vec<Session>> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+    parse_putty(&decode_reg(&bytes))
+}
+
+/// `reg export` writes UTF-16, regedit of old Windows versions and hand edits write UTF-8.
+fn decode_reg(bytes: &[u8]) -> String {
+    match bytes {
+        [0xff, 0xfe, rest @ ..] => {
+            let units: Vec<u16> = rest
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+fn parse_putty(content: &str) -> Result<Vec<Session>> {
+    let mut reg_keys: BTreeMap<String, HashMap<String, String>> = BTreeMap::new();
+    let mut current = None;
+    for line in content.lines().map(str::trim) {
+        if let Some(key) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+            current = key
+                .split_once(PUTTY_SESSIONS)
+                .map(|(_, name)| percent_decode(name));
+            if let Some(name) = &current {
+                reg_keys.entry(name.clone()).or_default();
+            }
+            continue;
+        }
+        let (Some(name), Some((value_name, value))) = (&current, line.split_once('=')) else {
+            continue;
+        };
+        let Some(value_name) = value_name
+            .strip_prefix('"')
+            .and_then(|n| n.strip_suffix('"'))
+        else {
+            continue;
+        };
+        let value = if let Some(dword) = value.strip_prefix("dword:") {
+            u32::from_str_radix(dword, 16)
+                .with_context(|| format!("Invalid value of {} in session '{}'", value_name, name))?
+                .to_string()
+        } else if let Some(string) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+            string.replace("\\\"", "\"").replace("\\\\", "\\")
+        } else {
+            continue;
+        };
+        if let Some(values) = reg_keys.get_mut(name) {
+            values.insert(value_name.to_string(), value);
+        }
+    }
+
+    let mut sessions = Vec::new();
+    for (name, values) in reg_keys {
+        let get = |key: &str| values.get(key).filter(|v| !v.is_empty()).cloned();
+        if get("Protocol").is_some_and(|p| p != "ssh") {
+            continue;
+        }
+        // "Default Settings" and other sessions only kept for their settings
+        let Some(host) = get("HostName") else {
+            continue;
+        };
+        let (user, host) = match host.rsplit_once('@') {
+            Some((user, host)) => (Some(user.to_string()), host.to_string()),
+            None => (None, host),
+        };
+        let user = get("UserName").or(user);
+        let port = get("PortNumber").and_then(|p| p.parse().ok());
+        let key = get("PublicKeyFile");
+        if let Some(key) = &key {
+            eprintln!(
+                "Warning: session '{}' uses the PuTTY key {}, convert it with `puttygen <key>.ppk -O private-openssh -o <key>` and update the session with `devlg modify`",
+                name, key
+            );
+        }
+        sessions.push(new_session(
+            &name,
+            host,
+            user,
+            port,
+            key.map(PathBuf::from),
+            None,
+            HashSet::new(),
+        )?);
+    }
+    Ok(sessions)
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Reads Remmina SSH and SFTP profiles. `paths` are `.remmina` files or directories of them,
+/// by default the profile directory of Remmina. Groups become tags.
+pub fn import_remmina(paths: &[PathBuf]) -> Result<Vec<Session>> {
+    let default = dirs::data_dir()
+        .context("Failed to get data directory")?
+        .join("remmina");
+    let paths = if paths.is_empty() {
+        std::slice::from_ref(&default)
+    } else {
+        paths
+    };
+
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)
+                .with_context(|| format!("Failed to read {:?}", path))?
+                .map(|e| e.map(|e| e.path()))
+                .collect::<std::io::Result<Vec<PathBuf>>>()?;
+            entries.retain(|p| p.extension().is_some_and(|e| e == "remmina"));
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    let mut sessions = Vec::new();
+    for file in files {
+        let content =
+            fs::read_to_string(&file).with_context(|| format!("Failed to read {:?}", file))?;
+        if let Some(session) = parse_remmina(&content)
+            .with_context(|| format!("Failed to import the Remmina profile {:?}", file))?
+        {
+            sessions.push(session);
+        }
+    }
+    Ok(sessions)
+}
+
+fn parse_remmina(content: &str) -> Result<Option<Session>> {
+    let mut values = HashMap::new();
+    let mut in_profile = false;
+    for line in content.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_profile = line == "[remmina]";
+        } else if in_profile && let Some((key, value)) = line.split_once('=') {
+            values.insert(key.trim(), value.trim());
+        }
+    }
+    let get = |key: &str| values.get(key).copied().filter(|v| !v.is_empty());
+
+    if !get("protocol")
+        .is_some_and(|p| p.eq_ignore_ascii_case("ssh") || p.eq_ignore_ascii_case("sftp"))
+    {
+        return Ok(None);
+    }
+    let server = get("server").context("Profile has no server")?;
+    let (host, port) = split_host_port(server)?;
+    let name = get("name").unwrap_or(host);
+    let user = get("ssh_username").or(get("username")).map(str::to_string);
+    let key = get("ssh_privatekey").map(PathBuf::from);
+    let tags = get("group")
+        .map(|g| g.split('/').map(|t| t.trim().to_string()).collect())
+        .unwrap_or_default();
+
+    let mut session = new_session(name, host.to_string(), user, port, key, None, tags)?;
+    // ssh_auth 0 is password, 1 an identity file, the rest agent or automatic public key.
+    // Passwords are encrypted with a secret of Remmina and can't be imported.
+    if get("ssh_auth") == Some("0") {
+        session.auth_type = AuthType::Password;
+        session.private_key_path = None;
+    }
+    Ok(Some(session))
+}
+
+/// Splits `host:port` or `[v6 address]:port`.
+fn split_host_port(server: &str) -> Result<(&str, Option<u16>)> {
+    let (host, port) = match server.strip_prefix('[').and_then(|s| s.split_once(']')) {
+        Some((host, rest)) => (host, rest.strip_prefix(':')),
+        None => match server.split_once(':') {
+            Some((host, port)) if !port.contains(':') => (host, Some(port)),
+            _ => (server, None),
+        },
+    };
+    let port = port
+        .map(|p| p.parse().with_context(|| format!("Invalid port '{}'", p)))
+        .transpose()?;
+    Ok((host, port))
+}
+
+/// Reads the SFTP sites of a FileZilla site manager file, by default
+/// `~/.config/filezilla/sitemanager.xml`. Folders become tags.
+pub fn import_filezilla(path: Option<PathBuf>) -> Result<Vec<Session>> {
+    let path = match path {
+        Some(path) => path,
+        None => dirs::config_dir()
+            .context("Failed to get config directory")?
+            .join("filezilla")
+            .join("sitemanager.xml"),
+    };
+    let content =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    parse_filezilla(&content)
+}
+
+fn parse_filezilla(content: &str) -> Result<Vec<Session>> {
+    let document =
+        roxmltree::Document::parse(content).context("Failed to parse the FileZilla site file")?;
+    let servers = document
+        .descendants()
+        .find(|n| n.has_tag_name("Servers"))
+        .context("No Servers in the FileZilla site file")?;
+    let mut sessions = Vec::new();
+    filezilla_folder(servers, &[], &mut sessions)?;
+    Ok(sessions)
+}
+
+fn filezilla_folder(
+    folder: roxmltree::Node,
+    tags: &[String],
+    sessions: &mut Vec<Session>,
+) -> Result<()> {
+    for node in folder.children() {
+        if node.has_tag_name("Folder") {
+            // the folder name is the text before the first child element
+            let mut tags = tags.to_vec();
+            if let Some(name) = node.text().map(str::trim).filter(|n| !n.is_empty()) {
+                tags.push(name.to_string());
+            }
+            filezilla_folder(node, &tags, sessions)?;
+        } else if node.has_tag_name("Server") {
+            let get = |tag: &str| {
+                node.children()
+                    .find(|c| c.has_tag_name(tag))
+                    .and_then(|c| c.text())
+                    .map(str::trim)
+                    .filter(|t| !t.is_empty())
+            };
+            // protocol 1 is SFTP, the others FTP variants
+            if get("Protocol") != Some("1") {
+                continue;
+            }
+            let host = get("Host").context("Site has no host")?;
+            let name = get("Name").unwrap_or(host);
+            let port = get("Port").and_then(|p| p.parse().ok());
+            // logon type 5 is a key file
+            let key = match get("Logontype") {
+                Some("5") => get("Keyfile").map(PathBuf::from),
+                _ => None,
+            };
+            let password = node
+                .children()
+                .find(|c| c.has_tag_name("Pass"))
+                .filter(|_| key.is_none())
+                .and_then(|pass| {
+                    let text = pass.text()?.trim();
+                    match pass.attribute("encoding") {
+                        Some("base64") => base64::engine::general_purpose::STANDARD
+                            .decode(text)
+                            .ok()
+                            .and_then(|p| String::from_utf8(p).ok()),
+                        Some(_) => None,
+                        None => Some(text.to_string()),
+                    }
+                });
+            sessions.push(new_session(
+                name,
+                host.to_string(),
+                get("User").map(str::to_string),
+                port,
+                key,
+                password,
+                tags.iter().cloned().collect(),
+            )?);
+        }
+    }
+    Ok(())
+}
+
+/// The session fields a CSV column can be mapped to.
+const CSV_FIELDS: [&str; 7] = ["name", "host", "user", "port", "key", "password", "tags"];
+
+/// Reads sessions from a CSV file with a header row. Columns named like the session fields
+/// are used unless `mappings` of `field=column` say otherwise. Tags are separated by commas
+/// or semicolons.
+pub fn import_csv(path: &Path, mappings: &[String]) -> Result<Vec<Session>> {
+    let content = fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+    parse_csv(&content, mappings)
+}
+
+fn parse_csv(content: &str, mappings: &[String]) -> Result<Vec<Session>> {
+    let mut columns: HashMap<&str, String> =
+        CSV_FIELDS.iter().map(|f| (*f, f.to_string())).collect();
+    for mapping in mappings {
+        let (field, column) = mapping
+            .split_once('=')
+            .with_context(|| format!("Invalid mapping '{}', use field=column", mapping))?;
+        let field = CSV_FIELDS
+            .iter()
+            .find(|f| f.eq_ignore_ascii_case(field.trim()))
+            .with_context(|| {
+                format!(
+                    "Unknown field '{}', use one of: {}",
+                    field,
+                    CSV_FIELDS.join(", ")
+                )
+            })?;
+        columns.insert(field, column.trim().to_string());
+    }
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(content.as_bytes());
+    let headers = reader.headers()?.clone();
+    let index: HashMap<&str, usize> = columns
+        .iter()
+        .filter_map(|(field, column)| {
+            let i = headers
+                .iter()
+                .position(|h| h.eq_ignore_ascii_case(column))?;
+            Some((*field, i))
+        })
+        .collect();
+    if !index.contains_key("host") {
+        anyhow::bail!(
+            "No '{}' column for the host, map one with --map host=<column>",
+            columns["host"]
+        );
+    }
+
+    let mut sessions = Vec::new();
+    for (line, record) in reader.records().enumerate() {
+        let record = record?;
+        let get = |field: &str| {
+            index
+                .get(field)
+                .and_then(|i| record.get(*i))
+                .filter(|v| !v.is_empty())
+        };
+        let row = || format!("Invalid row {}", line + 2);
+        let host = get("host").with_context(|| format!("Row {} has no host", line + 2))?;
+        let port = get("port")
+            .map(|p| p.parse().with_context(|| format!("Invalid port '{}'", p)))
+            .transpose()
+            .with_context(row)?;
+        let tags = get("tags")
+            .map(|t| {
+                t.split([',', ';'])
+                    .map(|t| t.trim().to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        sessions.push(
+            new_session(
+                get("name").unwrap_or(host),
+                host.to_string(),
+                get("user").map(str::to_string),
+                port,
+                get("key").map(PathBuf::from),
+                get("password").map(str::to_string),
+                tags,
+            )
+            .with_context(row)?,
+        );
+    }
+    Ok(sessions)
+}
+
+/// Builds an imported session. Names get dashes instead of whitespace, so they are easy to
+/// type, and sessions with neither a key nor a password use the default key of ssh.
+fn new_session(
+    name: &str,
+    host: String,
+    user: Option<String>,
+    port: Option<u16>,
+    key: Option<PathBuf>,
+    password: Option<String>,
+    tags: HashSet<String>,
+) -> Result<Session> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join("-");
+    let user = user
+        .or_else(|| std::env::var("USER").ok())
+        .with_context(|| format!("Session '{}' has no user", name))?;
+    let (auth_type, key) = match (key, &password) {
+        (Some(key), _) => (AuthType::Key, Some(key)),
+        (None, Some(_)) => (AuthType::Password, None),
+        (None, None) => (AuthType::Key, Some(keys::default_key_path())),
+    };
+    let tags = tags.into_iter().filter(|t| !t.is_empty()).collect();
+    SessionBuilder::new()
+        .name(name.clone())
+        .host(host)
+        .user(user)
+        .port(port.unwrap_or(22))
+        .auth_type(auth_type)
+        .private_key_path(key)
+        .password(password.filter(|_| auth_type == AuthType::Password))
+        .tags(Some(tags))
+        .build()
+        .with_context(|| format!("Invalid session '{}'", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_importers() -> Result<()> {
+        let reg = "Windows Registry Editor Version 5.00\r\n\r\n\
+            [HKEY_CURRENT_USER\\Software\\SimonTatham\\PuTTY\\Sessions\\Default%20Settings]\r\n\
+            \"Protocol\"=\"ssh\"\r\n\r\n\
+            [HKEY_CURRENT_USER\\Software\\SimonTatham\\PuTTY\\Sessions\\prod%20web]\r\n\
+            \"HostName\"=\"admin@web.example.com\"\r\n\
+            \"PortNumber\"=dword:00000916\r\n\
+            \"Protocol\"=\"ssh\"\r\n\
+            \"PublicKeyFile\"=\"C:\\\\keys\\\\web.ppk\"\r\n\r\n\
+            [HKEY_CURRENT_USER\\Software\\SimonTatham\\PuTTY\\Sessions\\router]\r\n\
+            \"HostName\"=\"10.0.0.1\"\r\n\
+            \"Protocol\"=\"telnet\"\r\n";
+        let utf16: Vec<u8> = [0xff, 0xfe]
+            .into_iter()
+            .chain(reg.encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        let sessions = parse_putty(&decode_reg(&utf16))?;
+        assert_eq!(sessions.len(), 1);
+        let web = &sessions[0];
+        assert_eq!(
+            (
+                web.name.as_str(),
+                web.host.as_str(),
+                web.user.as_str(),
+                web.port
+            ),
+            ("prod-web", "web.example.com", "admin", 2326)
+        );
+        assert_eq!(
+            web.private_key_path,
+            Some(PathBuf::from("C:\\keys\\web.ppk"))
+        );
+
+        let profile = "[remmina]\nname=db 1\ngroup=prod/db\nprotocol=SSH\n\
+            server=[fe80::1]:2200\nusername=postgres\nssh_auth=0\n";
+        let db = parse_remmina(profile)?.unwrap();
+        assert_eq!((db.host.as_str(), db.port), ("fe80::1", 2200));
+        assert_eq!(db.auth_type, AuthType::Password);
+        assert!(db.tags.contains("prod") && db.tags.contains("db"));
+        assert!(parse_remmina("[remmina]\nprotocol=RDP\nserver=win\n")?.is_none());
+
+        let sites = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <FileZilla3><Servers><Folder expanded="1">staging
+                <Server><Host>files.example.com</Host><Port>22</Port><Protocol>1</Protocol>
+                    <User>deploy</User><Pass encoding="base64">aHVudGVyMg==</Pass>
+                    <Logontype>1</Logontype><Name>files</Name></Server>
+                <Server><Host>ftp.example.com</Host><Protocol>0</Protocol><Name>ftp</Name></Server>
+            </Folder></Servers></FileZilla3>"#;
+        let sessions = parse_filezilla(sites)?;
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].password.as_deref(), Some("hunter2"));
+        assert!(sessions[0].tags.contains("staging"));
+
+        let csv = "Hostname,Login,SSH Port,Groups\nweb1,root,2222,\"prod;web\"\ndb1,root,,\n";
+        let mappings = [
+            "host=Hostname",
+            "user=login",
+            "port=SSH Port",
+            "tags=Groups",
+        ]
+        .map(String::from);
+        let sessions = parse_csv(csv, &mappings)?;
+        assert_eq!(sessions.len(), 2);
+        assert_eq!(
+            (sessions[0].name.as_str(), sessions[0].port),
+            ("web1", 2222)
+        );
+        assert_eq!(sessions[0].tags.len(), 2);
+        assert_eq!(sessions[1].port, 22);
+        assert!(parse_csv(csv, &[]).is_err());
+        let error = parse_csv("host,user,port\nweb1,root,0\n", &[]).unwrap_err();
+        assert_eq!(
+            format!("{:#}", error),
+            "Invalid row 2: Invalid session 'web1': Port cannot be 0"
+        );
+
+        let zero_port = "[HKEY_CURRENT_USER\\Software\\SimonTatham\\PuTTY\\Sessions\\web]\r\n\
+            \"HostName\"=\"root@web.example.com\"\r\n\
+            \"PortNumber\"=dword:00000000\r\n\
+            \"Protocol\"=\"ssh\"\r\n";
+        assert!(parse_putty(zero_port).is_err());
+        Ok(())
+    }
+}
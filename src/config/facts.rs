@@ -0,0 +1,155 @@
+use crate::config::manager::Config;
+use crate::models::facts::HostFacts;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+/// Facts collected from the hosts, by session name.
+pub struct FactsStore {
+    facts_path: PathBuf,
+    pub facts: BTreeMap<String, HostFacts>,
+}
+
+impl FactsStore {
+    pub fn new(facts_path: Option<PathBuf>) -> Self {
+        let path = if let Some(p) = facts_path {
+            p
+        } else {
+            Self::get_default_path().unwrap()
+        };
+
+        FactsStore {
+            facts_path: path,
+            facts: BTreeMap::new(),
+        }
+    }
+
+    pub fn load(&mut self) -> Result<()> {
+        if !self.facts_path.exists() {
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(&self.facts_path)
+            .with_context(|| format!("Failed to read facts file at {:?}", self.facts_path))?;
+        self.facts =
+            serde_json::from_str(&content).with_context(|| "Failed to parse facts file")?;
+
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.facts_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create facts directory at {:?}", parent))?;
+        }
+
+        let content = serde_json::to_string_pretty(&self.facts)?;
+
+        // like the config, the facts are private and never left half written
+        let tmp_path = self.facts_path.with_extension("json.tmp");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)
+            .with_context(|| format!("Failed to write facts file at {:?}", tmp_path))?;
+        file.write_all(content.as_bytes())
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&tmp_path, &self.facts_path))
+            .with_context(|| format!("Failed to write facts file at {:?}", self.facts_path))
+    }
+
+    /// Drops the facts of the sessions that are not in `config` anymore, returning whether
+    /// there were any.
+    pub fn prune(&mut self, config: &Config) -> bool {
+        let count = self.facts.len();
+        self.facts
+            .retain(|name, _| config.get_session(name).is_some());
+        self.facts.len() < count
+    }
+
+    /// Moves the facts of the session `old` to the session `new`.
+    pub fn rename(&mut self, old: &str, new: &str) {
+        if let Some(facts) = self.facts.remove(old) {
+            self.facts.insert(new.to_string(), facts);
+        }
+    }
+
+    pub fn get(&self, session: &str) -> Option<&HostFacts> {
+        self.facts.get(session)
+    }
+
+    fn get_default_path() -> Result<PathBuf> {
+        let data_dir = dirs::data_dir().context("Failed to get data directory")?;
+        Ok(data_dir.join("devlg").join("facts.json"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_facts_rename() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let facts_path = temp_dir.path().join("facts.json");
+
+        let mut store = FactsStore::new(Some(facts_path.clone()));
+        store.load()?;
+        let facts = HostFacts {
+            collected_at: 1,
+            kernel: Some("6.8.0".to_string()),
+            ..Default::default()
+        };
+        store.facts.insert("old".to_string(), facts.clone());
+        store.rename("old", "new");
+        store.rename("unknown", "old");
+        store.save()?;
+
+        let mut loaded = FactsStore::new(Some(facts_path.clone()));
+        loaded.load()?;
+        assert_eq!(loaded.get("new"), Some(&facts));
+        assert_eq!(loaded.get("old"), None);
+        assert_eq!(loaded.facts.len(), 1);
+
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(
+            fs::metadata(&facts_path)?.permissions().mode() & 0o777,
+            0o600
+        );
+        assert!(!facts_path.with_extension("json.tmp").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_facts_prune() {
+        let mut store = FactsStore::new(Some(PathBuf::from("facts.json")));
+        store.facts.insert("web".to_string(), HostFacts::default());
+        store
+            .facts
+            .insert("deleted".to_string(), HostFacts::default());
+        let config: Config = toml::from_str(
+            r#"
+            templates = []
+
+            [[sessions]]
+            name = "web"
+            host = "web.example.com"
+            user = "deploy"
+            port = 22
+            auth_type = "key"
+            "#,
+        )
+        .unwrap();
+
+        assert!(store.prune(&config));
+        assert!(!store.prune(&config));
+        assert!(store.get("web").is_some());
+        assert!(store.get("deleted").is_none());
+    }
+}
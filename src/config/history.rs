@@ -0,0 +1,211 @@
+use crate::models::history::HistoryEntry;
+use crate::models::session::Session;
+use crate::utils::{ssh::ExitError, time};
+use anyhow::{Context, Result};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Instant;
+
+const DAY_SECS: u64 = 86400;
+
+/// Connection history, stored as one JSON entry per line.
+pub struct HistoryStore {
+    history_path: PathBuf,
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl HistoryStore {
+    pub fn new(history_path: Option<PathBuf>) -> Self {
+        let path = if let Some(p) = history_path {
+            p
+        } else {
+            Self::get_default_path().unwrap()
+        };
+
+        HistoryStore {
+            history_path: path,
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn load(&mut self) -> Result<()> {
+        if !self.history_path.exists() {
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(&self.history_path)
+            .with_context(|| format!("Failed to read history file at {:?}", self.history_path))?;
+
+        self.entries = content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()
+            .with_context(|| "Failed to parse history file")?;
+
+        Ok(())
+    }
+
+    pub fn append(&mut self, entry: HistoryEntry) -> Result<()> {
+        if let Some(parent) = self.history_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create history directory at {:?}", parent))?;
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.history_path)
+            .with_context(|| format!("Failed to open history file at {:?}", self.history_path))?;
+        writeln!(file, "{}", serde_json::to_string(&entry)?)
+            .with_context(|| format!("Failed to write history file at {:?}", self.history_path))?;
+
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    /// Moves the entries of the session `old` to the session `new`, rewriting the file.
+    pub fn rename(&mut self, old: &str, new: &str) -> Result<()> {
+        if !self.entries.iter().any(|e| e.session == old) {
+            return Ok(());
+        }
+        for entry in self.entries.iter_mut().filter(|e| e.session == old) {
+            entry.session = new.to_string();
+        }
+
+        let mut content = String::new();
+        for entry in self.entries.iter() {
+            content.push_str(&serde_json::to_string(entry)?);
+            content.push('\n');
+        }
+        let tmp_path = self.history_path.with_extension("jsonl.tmp");
+        fs::write(&tmp_path, content)
+            .and_then(|_| fs::rename(&tmp_path, &self.history_path))
+            .with_context(|| format!("Failed to write history file at {:?}", self.history_path))
+    }
+
+    /// Returns the most recent entry made by `command`.
+    pub fn last(&self, command: &str) -> Option<&HistoryEntry> {
+        self.entries.iter().rev().find(|e| e.command == command)
+    }
+
+    /// Returns when `session` was last connected to.
+    pub fn last_used(&self, session: &str) -> Option<u64> {
+        self.entries
+            .iter()
+            .filter(|e| e.session == session)
+            .map(|e| e.timestamp)
+            .max()
+    }
+
+    /// Scores `session` by how often and how recently it was used, higher is better.
+    pub fn frecency(&self, session: &str) -> f64 {
+        let now = time::now();
+        self.entries
+            .iter()
+            .filter(|e| e.session == session)
+            .map(|e| match now.saturating_sub(e.timestamp) / DAY_SECS {
+                0 => 4.0,
+                1..7 => 2.0,
+                7..30 => 1.0,
+                _ => 0.5,
+            })
+            .sum()
+    }
+
+    fn get_default_path() -> Result<PathBuf> {
+        let data_dir = dirs::data_dir().context("Failed to get data directory")?;
+        Ok(data_dir.join("devlg").join("history.jsonl"))
+    }
+}
+
+/// Runs `connect` and records it in the history as a `command` on `session`.
+/// Failing to write the history only prints a warning.
+pub fn track<F>(session: &Session, command: &str, connect: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()>,
+{
+    let timestamp = time::now();
+    let started = Instant::now();
+    let result = connect();
+
+    let exit_code = match &result {
+        Ok(()) => Some(0),
+        Err(e) => e.downcast_ref::<ExitError>().and_then(|e| e.status.code()),
+    };
+    let entry = HistoryEntry {
+        timestamp,
+        session: session.name.clone(),
+        host: session.host.clone(),
+        command: command.to_string(),
+        duration_secs: started.elapsed().as_secs(),
+        exit_code,
+    };
+
+    let mut store = HistoryStore::new(None);
+    if let Err(e) = store.append(entry) {
+        eprintln!("Warning: failed to record connection history: {:#}", e);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn entry(session: &str, age_days: u64) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: time::now() - age_days * DAY_SECS,
+            session: session.to_string(),
+            host: "example.com".to_string(),
+            command: "login".to_string(),
+            duration_secs: 10,
+            exit_code: Some(0),
+        }
+    }
+
+    #[test]
+    fn test_history_operations() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let history_path = temp_dir.path().join("history.jsonl");
+
+        let mut store = HistoryStore::new(Some(history_path.clone()));
+        store.load()?;
+        assert!(store.entries.is_empty());
+
+        store.append(entry("old", 60))?;
+        store.append(entry("old", 45))?;
+        store.append(entry("recent", 0))?;
+
+        let mut loaded = HistoryStore::new(Some(history_path));
+        loaded.load()?;
+        assert_eq!(loaded.entries, store.entries);
+        assert_eq!(loaded.last("login").unwrap().session, "recent");
+        assert!(loaded.frecency("recent") > loaded.frecency("old"));
+        assert_eq!(loaded.frecency("unknown"), 0.0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_history_rename() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let history_path = temp_dir.path().join("history.jsonl");
+
+        let mut store = HistoryStore::new(Some(history_path.clone()));
+        store.append(entry("old", 1))?;
+        store.append(entry("other", 0))?;
+        store.rename("old", "new")?;
+        store.rename("unknown", "old")?;
+
+        let mut loaded = HistoryStore::new(Some(history_path));
+        loaded.load()?;
+        let sessions: Vec<&str> = loaded.entries.iter().map(|e| e.session.as_str()).collect();
+        assert_eq!(sessions, vec!["new", "other"]);
+        assert!(!temp_dir.path().join("history.jsonl.tmp").exists());
+        Ok(())
+    }
+}
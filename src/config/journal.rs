@@ -0,0 +1,303 @@
+use crate::config::manager::Config;
+use crate::utils::secret::SecretKey;
+use crate::utils::time;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+/// Changes kept for undo, the oldest ones are dropped first.
+const MAX_ENTRIES: usize = 100;
+
+/// A change of the config, with the whole config before and after it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JournalEntry {
+    pub timestamp: u64,
+    /// Command line of the change, without passwords
+    pub command: String,
+    pub before: Config,
+    pub after: Config,
+}
+
+impl JournalEntry {
+    /// The sessions and templates added (+), removed (-) and changed (~) by this change.
+    pub fn summary(&self) -> String {
+        let mut changes = Vec::new();
+        let sessions = |config: &Config| -> BTreeMap<String, String> {
+            config
+                .sessions
+                .iter()
+                .map(|s| (s.name.clone(), serde_json::to_string(s).unwrap_or_default()))
+                .collect()
+        };
+        let templates = |config: &Config| -> BTreeMap<String, String> {
+            config
+                .templates
+                .iter()
+                .map(|t| (t.name.clone(), serde_json::to_string(t).unwrap_or_default()))
+                .collect()
+        };
+        for (kind, before, after) in [
+            ("", sessions(&self.before), sessions(&self.after)),
+            ("template ", templates(&self.before), templates(&self.after)),
+        ] {
+            for (name, value) in before.iter() {
+                match after.get(name) {
+                    None => changes.push(format!("-{}{}", kind, name)),
+                    Some(new) if new != value => changes.push(format!("~{}{}", kind, name)),
+                    _ => {}
+                }
+            }
+            for name in after.keys().filter(|name| !before.contains_key(*name)) {
+                changes.push(format!("+{}{}", kind, name));
+            }
+        }
+        if self.before.settings != self.after.settings {
+            changes.push("~settings".to_string());
+        }
+        if self.before.sync != self.after.sync {
+            changes.push("~sync".to_string());
+        }
+        changes.join(" ")
+    }
+}
+
+#[derive(Deserialize)]
+struct JournalFile {
+    entries: Vec<JournalEntry>,
+    #[serde(default)]
+    undone: usize,
+}
+
+/// The changes of the config, for undo and redo. Entries are oldest first, the last
+/// `undone` of them were undone and can be redone.
+pub struct Journal {
+    journal_path: PathBuf,
+    /// Encrypts the TOTP secrets of the configs, like in the config file
+    secret_key: SecretKey,
+    pub entries: Vec<JournalEntry>,
+    pub undone: usize,
+}
+
+impl Journal {
+    pub fn new(journal_path: Option<PathBuf>) -> Self {
+        let (path, key_path) = if let Some(p) = journal_path {
+            let key_path = p.with_extension("key");
+            (p, Some(key_path))
+        } else {
+            (Self::get_default_path().unwrap(), None)
+        };
+
+        Journal {
+            journal_path: path,
+            secret_key: SecretKey::new(key_path),
+            entries: Vec::new(),
+            undone: 0,
+        }
+    }
+
+    pub fn load(&mut self) -> Result<()> {
+        if !self.journal_path.exists() {
+            return Ok(());
+        }
+
+        let content = fs::read_to_string(&self.journal_path)
+            .with_context(|| format!("Failed to read journal file at {:?}", self.journal_path))?;
+        let file: JournalFile =
+            serde_json::from_str(&content).with_context(|| "Failed to parse journal file")?;
+        self.entries = file.entries;
+        for entry in self.entries.iter_mut() {
+            entry.before.open_secrets(&self.secret_key)?;
+            entry.after.open_secrets(&self.secret_key)?;
+        }
+        self.undone = file.undone.min(self.entries.len());
+
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.journal_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create journal directory at {:?}", parent))?;
+        }
+
+        let mut entries = self.entries.clone();
+        for entry in entries.iter_mut() {
+            entry.before.seal_secrets(&self.secret_key)?;
+            entry.after.seal_secrets(&self.secret_key)?;
+        }
+        let content = serde_json::to_string(&serde_json::json!({
+            "entries": entries,
+            "undone": self.undone,
+        }))?;
+
+        // the entries hold passwords, like the config
+        let tmp_path = self.journal_path.with_extension("json.tmp");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)
+            .with_context(|| format!("Failed to write journal file at {:?}", tmp_path))?;
+        file.write_all(content.as_bytes())
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&tmp_path, &self.journal_path))
+            .with_context(|| format!("Failed to write journal file at {:?}", self.journal_path))
+    }
+
+    /// Records a change made by `command`. The undone changes can't be redone anymore.
+    pub fn record(&mut self, command: String, before: &Config, after: &Config) {
+        self.entries.truncate(self.entries.len() - self.undone);
+        self.undone = 0;
+        self.entries.push(JournalEntry {
+            timestamp: time::now(),
+            command,
+            before: before.clone(),
+            after: after.clone(),
+        });
+        let excess = self.entries.len().saturating_sub(MAX_ENTRIES);
+        self.entries.drain(..excess);
+    }
+
+    /// Undoes the last change, returning it. `current` must be the config it left.
+    pub fn undo(&mut self, current: &Config) -> Result<&JournalEntry> {
+        let index = (self.entries.len() - self.undone)
+            .checked_sub(1)
+            .context("Nothing to undo")?;
+        let entry = &self.entries[index];
+        if entry.after != *current {
+            anyhow::bail!(
+                "The config was changed outside of devlg after `{}`, it can't be undone",
+                entry.command
+            );
+        }
+        self.undone += 1;
+        Ok(&self.entries[index])
+    }
+
+    /// Redoes the last undone change, returning it. `current` must be the config it left.
+    pub fn redo(&mut self, current: &Config) -> Result<&JournalEntry> {
+        if self.undone == 0 {
+            anyhow::bail!("Nothing to redo");
+        }
+        let index = self.entries.len() - self.undone;
+        let entry = &self.entries[index];
+        if entry.before != *current {
+            anyhow::bail!(
+                "The config was changed outside of devlg after undoing `{}`, it can't be redone",
+                entry.command
+            );
+        }
+        self.undone -= 1;
+        Ok(&self.entries[index])
+    }
+
+    fn get_default_path() -> Result<PathBuf> {
+        let data_dir = dirs::data_dir().context("Failed to get data directory")?;
+        Ok(data_dir.join("devlg").join("journal.json"))
+    }
+}
+
+/// The command line of devlg, with the passwords and TOTP secrets masked.
+pub fn command_line() -> String {
+    format!("devlg {}", mask_secrets(std::env::args().skip(1)).join(" "))
+}
+
+/// Masks the values of the password and TOTP secret flags, given apart or attached.
+fn mask_secrets(args: impl Iterator<Item = String>) -> Vec<String> {
+    let mut masked = Vec::new();
+    let mut mask = false;
+    for arg in args {
+        if mask {
+            masked.push("***".to_string());
+        } else if let Some(flag) = ["--password=", "--totp-secret="]
+            .into_iter()
+            .find(|flag| arg.starts_with(flag))
+        {
+            masked.push(format!("{}***", flag));
+        } else if arg.starts_with("-P") && arg.len() > 2 {
+            masked.push("-P***".to_string());
+        } else {
+            masked.push(arg.clone());
+        }
+        mask = arg == "-P" || arg == "--password" || arg == "--totp-secret";
+    }
+    masked
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::{AuthType, Session};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_mask_secrets() {
+        let args = [
+            "add",
+            "-P",
+            "x",
+            "-Px",
+            "--password=x",
+            "--totp-secret",
+            "x",
+            "-p",
+            "22",
+        ];
+        assert_eq!(
+            mask_secrets(args.into_iter().map(String::from)),
+            [
+                "add",
+                "-P",
+                "***",
+                "-P***",
+                "--password=***",
+                "--totp-secret",
+                "***",
+                "-p",
+                "22"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_undo_redo() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("journal.json");
+        let empty = Config::default();
+        let mut one = empty.clone();
+        one.sessions.push(Session::new(
+            "web".to_string(),
+            "example.com".to_string(),
+            "user".to_string(),
+            22,
+            AuthType::Password,
+            None,
+            Some("secret".to_string()),
+            None,
+        ));
+
+        let mut journal = Journal::new(Some(path.clone()));
+        journal.record("devlg add".to_string(), &empty, &one);
+        journal.save()?;
+        assert_eq!(journal.entries[0].summary(), "+web");
+
+        let mut journal = Journal::new(Some(path));
+        journal.load()?;
+        assert!(journal.undo(&empty).is_err());
+        assert_eq!(journal.undo(&one)?.before.sessions.len(), 0);
+        assert!(journal.undo(&empty).is_err());
+        assert_eq!(journal.redo(&empty)?.after.sessions.len(), 1);
+        assert!(journal.redo(&one).is_err());
+
+        // a new change after an undo drops the undone one
+        journal.undo(&one)?;
+        journal.record("devlg add".to_string(), &empty, &empty);
+        assert_eq!((journal.entries.len(), journal.undone), (1, 0));
+        Ok(())
+    }
+}
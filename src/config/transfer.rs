@@ -0,0 +1,536 @@
+use crate::config::manager::{Config, Settings};
+use crate::models::session::{AuthType, Session, Template};
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+
+/// File formats of exported configs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_lowercase().as_str() {
+            "json" => Format::Json,
+            "yaml" | "yml" => Format::Yaml,
+            "toml" => Format::Toml,
+            _ => anyhow::bail!("Invalid format. Use 'json', 'yaml' or 'toml'"),
+        })
+    }
+}
+
+impl Format {
+    /// Guesses the format of a file from its extension.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+}
+
+/// What to do with imported sessions and templates whose name is already taken.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Keep the existing one
+    Skip,
+    /// Replace the existing one
+    Overwrite,
+    /// Import under a new name
+    Rename,
+}
+
+impl FromStr for Strategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s.to_lowercase().as_str() {
+            "skip" => Strategy::Skip,
+            "overwrite" => Strategy::Overwrite,
+            "rename" => Strategy::Rename,
+            _ => anyhow::bail!("Invalid strategy. Use 'skip', 'overwrite' or 'rename'"),
+        })
+    }
+}
+
+/// An exported config. Every part is optional, so that e.g. a provisioning script can
+/// generate just a list of sessions.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Bundle {
+    #[serde(default)]
+    pub sessions: Vec<Session>,
+    #[serde(default)]
+    pub templates: Vec<Template>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<Settings>,
+}
+
+impl Bundle {
+    /// Bundles `sessions` with the templates based on them and the global settings.
+    pub fn new(config: &Config, sessions: &[&Session], include_secrets: bool) -> Self {
+        let names: HashSet<&str> = sessions.iter().map(|s| s.name.as_str()).collect();
+        let sessions = sessions
+            .iter()
+            .map(|s| {
+                let mut session = (*s).clone();
+                if !include_secrets {
+                    session.strip_secrets();
+                }
+                session
+            })
+            .collect();
+        let templates = config
+            .templates
+            .iter()
+            .filter(|t| names.contains(t.session.as_str()))
+            .cloned()
+            .collect();
+
+        Bundle {
+            sessions,
+            templates,
+            settings: Some(config.settings.clone()),
+        }
+    }
+
+    pub fn to_string(&self, format: Format) -> Result<String> {
+        Ok(match format {
+            Format::Json => serde_json::to_string_pretty(self)?,
+            Format::Yaml => serde_yaml::to_string(self)?,
+            Format::Toml => toml::to_string_pretty(self)?,
+        })
+    }
+
+    /// Parses a bundle in `format`, or in whichever format works if not given.
+    pub fn parse(content: &str, format: Option<Format>) -> Result<Self> {
+        let parse = |format| -> Result<Bundle> {
+            Ok(match format {
+                Format::Json => serde_json::from_str(content)?,
+                Format::Yaml => serde_yaml::from_str(content)?,
+                Format::Toml => toml::from_str(content)?,
+            })
+        };
+        match format {
+            Some(format) => parse(format).context("Failed to parse the import file"),
+            None => parse(Format::Json)
+                .or_else(|_| parse(Format::Toml))
+                .or_else(|_| parse(Format::Yaml))
+                .context("Failed to parse the import file as JSON, TOML or YAML"),
+        }
+    }
+}
+
+/// Merges `bundle` into a copy of `config` following `strategy`. Returns the merged config
+/// and a description of every change, one per line.
+pub fn merge(config: &Config, bundle: Bundle, strategy: Strategy) -> Result<(Config, Vec<String>)> {
+    let mut merged = config.clone();
+    let mut report = Vec::new();
+
+    let mut taken: HashSet<String> = config
+        .sessions
+        .iter()
+        .map(|s| s.name.clone())
+        .chain(bundle.sessions.iter().map(|s| s.name.clone()))
+        .collect();
+    let mut renamed: HashMap<String, String> = HashMap::new();
+
+    for mut session in bundle.sessions {
+        // an export without secrets must not wipe the local ones
+        if let Some(existing) = config.get_session(&session.name) {
+            session.restore_secrets(existing);
+        }
+        if let Err(e) = validate(&session, &mut report) {
+            report.push(format!(
+                "! session {:<20} skipped, invalid: {:#}",
+                session.name, e
+            ));
+            continue;
+        }
+
+        // against the merged sessions, so a name found twice in the bundle follows the
+        // strategy like one already in the config
+        let Some(existing) = merged.get_session(&session.name) else {
+            report.push(format!("+ session {:<20} new", session.name));
+            merged.sessions.push(session);
+            continue;
+        };
+        let changes = diff(existing, &session)?;
+        if changes.is_empty() {
+            report.push(format!("= session {:<20} unchanged", session.name));
+            continue;
+        }
+
+        match strategy {
+            Strategy::Skip => {
+                report.push(format!(
+                    "! session {:<20} skipped, exists with {}",
+                    session.name,
+                    changes.join(", ")
+                ));
+            }
+            Strategy::Overwrite => {
+                report.push(format!(
+                    "~ session {:<20} {}",
+                    session.name,
+                    changes.join(", ")
+                ));
+                merged.update_session(session)?;
+            }
+            Strategy::Rename => {
+                let name = free_name(&session.name, &taken);
+                report.push(format!(
+                    "+ session {:<20} new, renamed from {}",
+                    name, session.name
+                ));
+                taken.insert(name.clone());
+                renamed.insert(session.name.clone(), name.clone());
+                session.name = name;
+                merged.sessions.push(session);
+            }
+        }
+    }
+
+    let mut taken: HashSet<String> = config
+        .templates
+        .iter()
+        .map(|t| t.name.clone())
+        .chain(bundle.templates.iter().map(|t| t.name.clone()))
+        .collect();
+    for mut template in bundle.templates {
+        if let Some(name) = renamed.get(&template.session) {
+            template.session = name.clone();
+        }
+        if merged.get_session(&template.session).is_none() {
+            report.push(format!(
+                "! template {:<19} skipped, session '{}' does not exist",
+                template.name, template.session
+            ));
+            continue;
+        }
+
+        let Some(existing) = config.get_template(&template.name) else {
+            report.push(format!("+ template {:<19} new", template.name));
+            merged.templates.push(template);
+            continue;
+        };
+        let changes = diff(existing, &template)?;
+        if changes.is_empty() {
+            report.push(format!("= template {:<19} unchanged", template.name));
+            continue;
+        }
+
+        match strategy {
+            Strategy::Skip => report.push(format!(
+                "! template {:<19} skipped, exists with {}",
+                template.name,
+                changes.join(", ")
+            )),
+            Strategy::Overwrite => {
+                report.push(format!(
+                    "~ template {:<19} {}",
+                    template.name,
+                    changes.join(", ")
+                ));
+                if let Some(t) = merged
+                    .templates
+                    .iter_mut()
+                    .find(|t| t.name == template.name)
+                {
+                    *t = template;
+                }
+            }
+            Strategy::Rename => {
+                let name = free_name(&template.name, &taken);
+                report.push(format!(
+                    "+ template {:<19} new, renamed from {}",
+                    name, template.name
+                ));
+                taken.insert(name.clone());
+                template.name = name;
+                merged.templates.push(template);
+            }
+        }
+    }
+
+    // global settings only replace local ones that were never changed, or when asked to
+    if let Some(settings) = bundle.settings
+        && settings != config.settings
+    {
+        if strategy == Strategy::Overwrite || config.settings == Settings::default() {
+            let changes = diff(&config.settings, &settings)?;
+            report.push(format!("~ settings {:<19} {}", "", changes.join(", ")));
+            merged.settings = settings;
+        } else {
+            report.push(format!(
+                "! settings {:<19} skipped, local settings differ",
+                ""
+            ));
+        }
+    }
+
+    Ok((merged, report))
+}
+
+/// Drops the sessions of another client that point at the same host, user and port as an
+/// existing session or an earlier one of the import, and renames those whose name is used by
+/// an earlier one. Returns the remaining sessions and a line per dropped one.
+pub fn dedup(config: &Config, sessions: Vec<Session>) -> (Vec<Session>, Vec<String>) {
+    let target = |s: &Session| (s.host.to_lowercase(), s.user.clone(), s.port);
+    let mut seen: HashMap<_, String> = config
+        .sessions
+        .iter()
+        .map(|s| (target(s), s.name.clone()))
+        .collect();
+    let mut taken: HashSet<String> = config.sessions.iter().map(|s| s.name.clone()).collect();
+    let mut imported = HashSet::new();
+    let mut kept = Vec::new();
+    let mut report = Vec::new();
+
+    for mut session in sessions {
+        match seen.get(&target(&session)) {
+            Some(name) if *name != session.name => {
+                report.push(format!(
+                    "= session {:<20} skipped, same host as {}",
+                    session.name, name
+                ));
+                continue;
+            }
+            _ => {}
+        }
+        if imported.contains(&session.name) {
+            session.name = free_name(&session.name, &taken);
+        }
+        taken.insert(session.name.clone());
+        imported.insert(session.name.clone());
+        seen.insert(target(&session), session.name.clone());
+        kept.push(session);
+    }
+    (kept, report)
+}
+
+/// Validates an imported session. A missing password is only reported, since exports leave
+/// them out by default.
+fn validate(session: &Session, report: &mut Vec<String>) -> Result<()> {
+    let mut session = session.clone();
+    if session.auth_type != AuthType::Key && session.password.is_none() {
+        report.push(format!(
+            "! session {:<20} has no password, set one with `devlg modify {}`",
+            session.name, session.name
+        ));
+        session.password = Some(String::new());
+    }
+    if session.auth_type == AuthType::Totp && session.totp_secret.is_none() {
+        report.push(format!(
+            "! session {:<20} has no TOTP secret, set one with `devlg modify {} --totp-secret`",
+            session.name, session.name
+        ));
+        // any valid secret, only to validate the rest of the session
+        session.totp_secret = Some("AAAAAAAA".to_string());
+    }
+    session.validate()
+}
+
+/// The fields that differ between `old` and `new`, as `field: old -> new`. Passwords and
+/// TOTP secrets are masked.
+pub fn diff<T: Serialize>(old: &T, new: &T) -> Result<Vec<String>> {
+    let to_map = |value: &T| -> Result<BTreeMap<String, serde_json::Value>> {
+        Ok(match serde_json::to_value(value)? {
+            serde_json::Value::Object(map) => map.into_iter().collect(),
+            _ => BTreeMap::new(),
+        })
+    };
+    let (old, new) = (to_map(old)?, to_map(new)?);
+
+    let show = |key: &str, value: Option<&serde_json::Value>| match value {
+        None | Some(serde_json::Value::Null) => "none".to_string(),
+        Some(_) if key == "password" || key == "totp_secret" => "***".to_string(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    };
+    let keys: std::collections::BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    Ok(keys
+        .into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .map(|key| {
+            format!(
+                "{}: {} -> {}",
+                key,
+                show(key, old.get(key)),
+                show(key, new.get(key))
+            )
+        })
+        .collect())
+}
+
+/// The sessions, templates and settings that differ between `old` and `new`: a `+`, `-` or
+/// `~` line for each of them, followed by the changed fields. Passwords are masked.
+pub fn config_diff(old: &Config, new: &Config) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    let mut compare = |kind: &str,
+                       old: BTreeMap<String, serde_json::Value>,
+                       new: BTreeMap<String, serde_json::Value>|
+     -> Result<()> {
+        for (name, value) in old.iter() {
+            let (marker, fields) = match new.get(name) {
+                None => ("-", Vec::new()),
+                Some(new) if new != value => ("~", diff(value, new)?),
+                Some(_) => continue,
+            };
+            lines.push(format!("{} {} {}", marker, kind, name));
+            lines.extend(fields.into_iter().map(|line| format!("    {}", line)));
+        }
+        for (name, value) in new.iter().filter(|(name, _)| !old.contains_key(*name)) {
+            lines.push(format!("+ {} {}", kind, name));
+            let fields = diff(&serde_json::Value::Null, value)?;
+            lines.extend(fields.into_iter().map(|line| format!("    {}", line)));
+        }
+        Ok(())
+    };
+    let sessions = |config: &Config| -> Result<BTreeMap<String, serde_json::Value>> {
+        config
+            .sessions
+            .iter()
+            .map(|s| Ok((s.name.clone(), serde_json::to_value(s)?)))
+            .collect()
+    };
+    compare("session", sessions(old)?, sessions(new)?)?;
+    let templates = |config: &Config| -> Result<BTreeMap<String, serde_json::Value>> {
+        config
+            .templates
+            .iter()
+            .map(|t| Ok((t.name.clone(), serde_json::to_value(t)?)))
+            .collect()
+    };
+    compare("template", templates(old)?, templates(new)?)?;
+
+    if old.settings != new.settings {
+        lines.push("~ settings".to_string());
+        let fields = diff(&old.settings, &new.settings)?;
+        lines.extend(fields.into_iter().map(|line| format!("    {}", line)));
+    }
+    if old.sync != new.sync {
+        lines.push("~ sync".to_string());
+    }
+    Ok(lines)
+}
+
+/// `name-1`, `name-2`... whichever is not taken first.
+fn free_name(name: &str, taken: &HashSet<String>) -> String {
+    (1..)
+        .map(|i| format!("{}-{}", name, i))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session(name: &str, host: &str) -> Session {
+        Session::new(
+            name.to_string(),
+            host.to_string(),
+            "user".to_string(),
+            22,
+            AuthType::Password,
+            None,
+            Some("secret".to_string()),
+            None,
+        )
+    }
+
+    #[test]
+    fn test_export_and_merge() -> Result<()> {
+        let mut config = Config::default();
+        config.sessions.push(session("web", "a.example.com"));
+        config.templates.push(Template {
+            name: "web-template".to_string(),
+            session: "web".to_string(),
+            ssh_options: BTreeMap::new(),
+            extra_args: Vec::new(),
+        });
+
+        let sessions: Vec<&Session> = config.sessions.iter().collect();
+        for format in [Format::Json, Format::Yaml, Format::Toml] {
+            let content = Bundle::new(&config, &sessions, false).to_string(format)?;
+            assert!(!content.contains("secret"));
+            let bundle = Bundle::parse(&content, None)?;
+            assert_eq!(bundle.sessions[0].name, "web");
+            assert_eq!(bundle.templates.len(), 1);
+        }
+
+        let incoming = || Bundle {
+            sessions: vec![session("web", "b.example.com"), session("db", "db")],
+            templates: vec![Template {
+                name: "web-template".to_string(),
+                session: "web".to_string(),
+                ssh_options: BTreeMap::new(),
+                extra_args: vec!["-A".to_string()],
+            }],
+            settings: None,
+        };
+
+        let (merged, report) = merge(&config, incoming(), Strategy::Skip)?;
+        assert_eq!(merged.sessions.len(), 2);
+        assert_eq!(merged.get_session("web").unwrap().host, "a.example.com");
+        assert!(report[0].contains("host: a.example.com -> b.example.com"));
+
+        let (merged, _) = merge(&config, incoming(), Strategy::Overwrite)?;
+        assert_eq!(merged.get_session("web").unwrap().host, "b.example.com");
+        assert_eq!(merged.templates[0].extra_args, vec!["-A"]);
+
+        let (merged, _) = merge(&config, incoming(), Strategy::Rename)?;
+        assert_eq!(merged.get_session("web-1").unwrap().host, "b.example.com");
+        assert_eq!(
+            merged.get_template("web-template-1").unwrap().session,
+            "web-1"
+        );
+
+        // a name found twice in the bundle follows the strategy, an invalid session is
+        // skipped
+        let mut bad_port = session("bad", "bad.example.com");
+        bad_port.port = 0;
+        let duplicates = || Bundle {
+            sessions: vec![session("db", "a"), session("db", "b"), bad_port.clone()],
+            ..Default::default()
+        };
+        let (merged, report) = merge(&config, duplicates(), Strategy::Skip)?;
+        let hosts: Vec<&str> = merged.sessions.iter().map(|s| s.host.as_str()).collect();
+        assert_eq!(hosts, vec!["a.example.com", "a"]);
+        assert!(report[1].starts_with("! session db "));
+        assert!(report[2].ends_with("skipped, invalid: Port cannot be 0"));
+        let (merged, _) = merge(&config, duplicates(), Strategy::Rename)?;
+        assert_eq!(merged.get_session("db-1").unwrap().host, "b");
+
+        let (kept, report) = dedup(
+            &config,
+            vec![
+                session("web-copy", "A.example.com"),
+                session("db", "db"),
+                session("db", "db2"),
+            ],
+        );
+        assert_eq!(report.len(), 1);
+        let names: Vec<&str> = kept.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["db", "db-1"]);
+
+        let (merged, _) = merge(&config, incoming(), Strategy::Rename)?;
+        let lines = config_diff(&config, &merged)?;
+        let changed: Vec<&String> = lines.iter().filter(|l| !l.starts_with(' ')).collect();
+        assert_eq!(
+            changed,
+            vec![
+                "+ session db",
+                "+ session web-1",
+                "+ template web-template-1"
+            ]
+        );
+        assert!(lines.contains(&"    password: none -> ***".to_string()));
+        Ok(())
+    }
+}
@@ -1,6 +1,7 @@
 mod commands;
 mod config;
 mod models;
+mod tui;
 mod utils;
 
 #[cfg(test)]
@@ -11,5 +12,5 @@ use clap::Parser;
 
 fn main() -> Result<()> {
     let cli = commands::Cli::parse();
-    commands::handle_command(cli.command)
+    commands::handle_command(cli.command, cli.dry_run)
 }
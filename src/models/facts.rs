@@ -0,0 +1,288 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use crate::models::session::Session;
+
+/// What is known about the host of a session, as of `collected_at`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct HostFacts {
+    /// When the facts were collected, in seconds since the Unix epoch
+    pub collected_at: u64,
+    /// Distribution and version, e.g. `ubuntu-22.04`
+    pub os: Option<String>,
+    /// Human readable OS name, e.g. `Ubuntu 22.04.4 LTS`
+    pub os_name: Option<String>,
+    pub kernel: Option<String>,
+    pub arch: Option<String>,
+    pub cpus: Option<u32>,
+    pub mem_bytes: Option<u64>,
+    pub uptime_secs: Option<u64>,
+    #[serde(default)]
+    pub ips: Vec<String>,
+}
+
+impl HostFacts {
+    /// Parses the `key=value` lines printed by the fact gathering script.
+    pub fn parse(output: &str, collected_at: u64) -> Self {
+        let mut facts = HostFacts {
+            collected_at,
+            ..Default::default()
+        };
+        let non_empty = |value: &str| Some(value.to_string()).filter(|v| !v.is_empty());
+
+        for line in output.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim().trim_matches('"');
+            match key.trim() {
+                "os" => facts.os = non_empty(&value.to_lowercase()),
+                "os_name" => facts.os_name = non_empty(value),
+                "kernel" => facts.kernel = non_empty(value),
+                "arch" => facts.arch = non_empty(value),
+                "cpus" => facts.cpus = value.parse().ok(),
+                "mem_kb" => facts.mem_bytes = value.parse::<u64>().ok().map(|kb| kb * 1024),
+                "mem_bytes" => facts.mem_bytes = value.parse().ok(),
+                "uptime" => facts.uptime_secs = value.parse().ok(),
+                "ips" => facts.ips = value.split_whitespace().map(str::to_string).collect(),
+                _ => {}
+            }
+        }
+        facts
+    }
+}
+
+/// Shell script printing the facts of a Linux (or, partially, other Unix) host as
+/// `key=value` lines.
+pub const GATHER_SCRIPT: &str = r#"
+echo "kernel=$(uname -r)"
+echo "arch=$(uname -m)"
+if [ -r /etc/os-release ]; then
+    (. /etc/os-release; echo "os=$ID-$VERSION_ID"; echo "os_name=$PRETTY_NAME")
+else
+    echo "os=$(uname -s)-$(uname -r)"
+fi
+echo "cpus=$(getconf _NPROCESSORS_ONLN 2>/dev/null || nproc 2>/dev/null)"
+if [ -r /proc/meminfo ]; then
+    awk '/^MemTotal:/ { print "mem_kb=" $2 }' /proc/meminfo
+else
+    echo "mem_bytes=$(sysctl -n hw.memsize 2>/dev/null)"
+fi
+[ -r /proc/uptime ] && awk '{ print "uptime=" int($1) }' /proc/uptime
+echo "ips=$(hostname -I 2>/dev/null || ifconfig 2>/dev/null | awk '/inet / && $2 != "127.0.0.1" { printf "%s ", $2 }')"
+"#;
+
+/// Formats a size in bytes with a binary unit, e.g. `62.8G`.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "K", "M", "G", "T"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", bytes)
+    } else {
+        format!("{:.1}{}", size, UNITS[unit])
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+/// A condition on a session or its facts, e.g. `os=ubuntu-22.04` or `mem>64G`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    field: String,
+    op: Op,
+    value: String,
+}
+
+impl FromStr for Filter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // longest operators first so that `>=` is not read as `>`
+        const OPS: [(&str, Op); 6] = [
+            ("!=", Op::Ne),
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            ("=", Op::Eq),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+        ];
+        let (pos, len, op) = OPS
+            .iter()
+            .filter_map(|(text, op)| s.find(text).map(|pos| (pos, text.len(), *op)))
+            .min_by_key(|(pos, len, _)| (*pos, std::cmp::Reverse(*len)))
+            .with_context(|| format!("Invalid filter '{}', expected e.g. os=ubuntu-22.04", s))?;
+
+        let field = s[..pos].trim().to_lowercase();
+        let value = s[pos + len..].trim().to_string();
+        if !FIELDS.contains(&field.as_str()) {
+            anyhow::bail!(
+                "Unknown filter field '{}', expected one of: {}",
+                field,
+                FIELDS.join(", ")
+            );
+        }
+        if matches!(op, Op::Gt | Op::Ge | Op::Lt | Op::Le) && !NUMERIC_FIELDS.contains(&&*field) {
+            anyhow::bail!("Field '{}' can only be compared with = or !=", field);
+        }
+        if NUMERIC_FIELDS.contains(&field.as_str()) {
+            parse_number(&field, &value)?;
+        }
+
+        Ok(Filter { field, op, value })
+    }
+}
+
+const FIELDS: [&str; 12] = [
+    "name", "host", "user", "port", "os", "os_name", "kernel", "arch", "cpus", "mem", "uptime",
+    "ip",
+];
+const NUMERIC_FIELDS: [&str; 4] = ["port", "cpus", "mem", "uptime"];
+
+impl Filter {
+    /// Whether the session matches. Sessions without facts never match a fact filter.
+    pub fn matches(&self, session: &Session, facts: Option<&HostFacts>) -> bool {
+        let value: Option<Vec<String>> = match self.field.as_str() {
+            "name" => Some(vec![session.name.clone()]),
+            "host" => Some(vec![session.host.clone()]),
+            "user" => Some(vec![session.user.clone()]),
+            "port" => Some(vec![session.port.to_string()]),
+            field => facts.and_then(|facts| match field {
+                "os" => facts.os.clone().map(|v| vec![v]),
+                "os_name" => facts.os_name.clone().map(|v| vec![v]),
+                "kernel" => facts.kernel.clone().map(|v| vec![v]),
+                "arch" => facts.arch.clone().map(|v| vec![v]),
+                "cpus" => facts.cpus.map(|v| vec![v.to_string()]),
+                "mem" => facts.mem_bytes.map(|v| vec![v.to_string()]),
+                "uptime" => facts.uptime_secs.map(|v| vec![v.to_string()]),
+                "ip" => Some(facts.ips.clone()),
+                _ => None,
+            }),
+        };
+        let Some(values) = value else {
+            return false;
+        };
+
+        if self.op == Op::Ne {
+            return !values.iter().any(|v| self.equals(v));
+        }
+        values.iter().any(|v| self.compare(v))
+    }
+
+    fn equals(&self, actual: &str) -> bool {
+        if NUMERIC_FIELDS.contains(&self.field.as_str()) {
+            return self.ordering(actual) == Some(Ordering::Equal);
+        }
+        match self.value.strip_suffix('*') {
+            Some(prefix) => actual.to_lowercase().starts_with(&prefix.to_lowercase()),
+            None => actual.eq_ignore_ascii_case(&self.value),
+        }
+    }
+
+    fn compare(&self, actual: &str) -> bool {
+        match self.op {
+            Op::Eq => self.equals(actual),
+            Op::Ne => !self.equals(actual),
+            op => self.ordering(actual).is_some_and(|ordering| match op {
+                Op::Gt => ordering == Ordering::Greater,
+                Op::Ge => ordering != Ordering::Less,
+                Op::Lt => ordering == Ordering::Less,
+                _ => ordering != Ordering::Greater,
+            }),
+        }
+    }
+
+    /// How the actual value compares to the one of the filter.
+    fn ordering(&self, actual: &str) -> Option<Ordering> {
+        let actual: u64 = actual.parse().ok()?;
+        let expected = parse_number(&self.field, &self.value).ok()?;
+        Some(actual.cmp(&expected))
+    }
+}
+
+/// Parses a number with an optional unit: K/M/G/T for `mem`, s/m/h/d for `uptime`.
+fn parse_number(field: &str, value: &str) -> Result<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number
+        .parse()
+        .with_context(|| format!("Invalid number '{}' for field '{}'", value, field))?;
+
+    let multiplier: u64 = match (field, unit.to_uppercase().trim_end_matches(['B', 'I'])) {
+        (_, "") => 1,
+        ("mem", "K") => 1 << 10,
+        ("mem", "M") => 1 << 20,
+        ("mem", "G") => 1 << 30,
+        ("mem", "T") => 1 << 40,
+        ("uptime", "S") => 1,
+        ("uptime", "M") => 60,
+        ("uptime", "H") => 3600,
+        ("uptime", "D") => 86400,
+        _ => anyhow::bail!("Invalid unit '{}' for field '{}'", unit, field),
+    };
+    Ok((number * multiplier as f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::AuthType;
+
+    #[test]
+    fn test_facts_filters() -> Result<()> {
+        let output = "kernel=5.15.0-105-generic\narch=x86_64\nos=ubuntu-22.04\n\
+                      os_name=Ubuntu 22.04.4 LTS\ncpus=16\nmem_kb=65855520\nuptime=90061\n\
+                      ips=10.0.0.5 172.17.0.1\n";
+        let facts = HostFacts::parse(output, 1);
+        assert_eq!(facts.os.as_deref(), Some("ubuntu-22.04"));
+        assert_eq!(facts.mem_bytes, Some(65855520 * 1024));
+        assert_eq!(facts.ips, vec!["10.0.0.5", "172.17.0.1"]);
+        assert_eq!(format_size(facts.mem_bytes.unwrap()), "62.8G");
+
+        let session = Session::new(
+            "web".to_string(),
+            "example.com".to_string(),
+            "user".to_string(),
+            22,
+            AuthType::Key,
+            Some("~/.ssh/id_rsa".into()),
+            None,
+            None,
+        );
+        let matches = |filter: &str| -> Result<bool> {
+            Ok(filter.parse::<Filter>()?.matches(&session, Some(&facts)))
+        };
+        assert!(matches("os=Ubuntu-22.04")?);
+        assert!(matches("os=ubuntu*")?);
+        assert!(!matches("os!=ubuntu-22.04")?);
+        assert!(matches("mem>60G")?);
+        assert!(!matches("mem>64G")?);
+        assert!(matches("cpus>=16")?);
+        assert!(matches("uptime<2d")?);
+        assert!(matches("ip=172.17.0.1")?);
+        assert!(matches("port=22")?);
+        assert!(!"os=ubuntu".parse::<Filter>()?.matches(&session, None));
+
+        assert!("os>ubuntu".parse::<Filter>().is_err());
+        assert!("mem>lots".parse::<Filter>().is_err());
+        assert!("color=red".parse::<Filter>().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+use serde::{Deserialize, Serialize};
+
+/// A single connection made through devlg.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// Start of the connection, in seconds since the Unix epoch
+    pub timestamp: u64,
+    pub session: String,
+    pub host: String,
+    /// The devlg command that made the connection, e.g. `login` or `cp`
+    pub command: String,
+    pub duration_secs: u64,
+    /// Exit code of the ssh/scp process, `None` if it could not be run or was killed
+    pub exit_code: Option<i32>,
+}
@@ -1 +1,3 @@
+pub mod facts;
+pub mod history;
 pub mod session;
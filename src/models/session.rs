@@ -1,13 +1,15 @@
 use anyhow::{Result, bail};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashSet,
+    collections::{BTreeMap, HashSet},
     fmt::{self, Display},
     path::PathBuf,
     str::FromStr,
 };
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::utils::totp;
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Session {
     pub name: String,
     pub host: String,
@@ -18,14 +20,261 @@ pub struct Session {
     pub private_key_path: Option<PathBuf>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub password: Option<String>,
+    /// Base32 secret of the one-time codes of TOTP authentication, a secret like the password
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub totp_secret: Option<String>,
     #[serde(default)]
     pub tags: HashSet<String>,
+    /// Ask for the session name before connecting, and refuse bulk operations on the session
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub protected: bool,
+    /// Record the terminal of every login to this session
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub record: bool,
+    /// Seconds of inactivity before ssh checks the server is alive (ServerAliveInterval)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keepalive_interval: Option<u32>,
+    /// Unanswered keepalives before ssh gives up (ServerAliveCountMax)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keepalive_count_max: Option<u32>,
+    /// Reconnect when the connection drops, disabled if not set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconnect: Option<ReconnectPolicy>,
+    /// Terminal multiplexer to run the remote shell in, so it survives disconnections
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplexer: Option<Multiplexer>,
+    /// Name of the remote multiplexer session, defaults to the session name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub multiplexer_session: Option<String>,
+    /// Extra `-o` options for ssh and scp, e.g. `Compression = "yes"`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ssh_options: BTreeMap<String, String>,
+    /// Extra arguments for ssh, e.g. `["-A", "-X"]`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_args: Vec<String>,
+    /// Command to run instead of the login shell, e.g. `sudo -i`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_command: Option<String>,
+    /// Environment variables to set on the remote side (SetEnv)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Local environment variables to send to the remote side (SendEnv)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub send_env: Vec<String>,
+    /// Fingerprints of the trusted host keys, e.g. `SHA256:...`; when set, any other
+    /// host key is refused
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub host_key_fingerprints: Vec<String>,
+    /// How logins connect to the host, ssh by default
+    #[serde(default, skip_serializing_if = "Transport::is_ssh")]
+    pub transport: Transport,
+    /// Commands run before and after connecting to this session, after the global ones
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hooks: Vec<Hook>,
+}
+
+/// How logins connect to the host of a session.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Default)]
+pub enum Transport {
+    #[default]
+    #[serde(rename = "ssh")]
+    Ssh,
+    /// mosh, bootstrapped with the ssh command of the session
+    #[serde(rename = "mosh")]
+    Mosh,
+    /// A command of its own, e.g. `["kubectl", "exec", "-it", "{host}", "--", "bash"]`, with
+    /// `{host}`, `{user}` and `{port}` replaced by the ones of the session
+    #[serde(rename = "command")]
+    Command(Vec<String>),
+}
+
+impl Transport {
+    fn is_ssh(&self) -> bool {
+        *self == Transport::Ssh
+    }
+}
+
+/// A local command run before or after `login`, `exec` or `cp` connects to a session. It gets
+/// the session as JSON on stdin, a failing `pre` hook cancels the connection.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Hook {
+    pub on: HookEvent,
+    /// Shell command, run with `sh -c`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run: Option<String>,
+    /// Executable run without a shell, a bare name is looked up in the plugins directory
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub plugin: Option<PathBuf>,
+    /// Commands the hook runs for, among `login`, `exec` and `cp`; all of them if empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub commands: Vec<String>,
+    /// Only run for the sessions with one of these tags, for every session if empty
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub tags: HashSet<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum HookEvent {
+    #[serde(rename = "pre")]
+    Pre,
+    #[serde(rename = "post")]
+    Post,
+}
+
+/// The commands that run hooks.
+pub const HOOK_COMMANDS: [&str; 3] = ["login", "exec", "cp"];
+
+impl Hook {
+    pub fn validate(&self) -> Result<()> {
+        match (&self.run, &self.plugin) {
+            (Some(run), None) if !run.trim().is_empty() => {}
+            (None, Some(plugin)) if !plugin.as_os_str().is_empty() => {}
+            _ => bail!("A hook needs either a `run` command or a `plugin`"),
+        }
+        for command in self.commands.iter() {
+            if !HOOK_COMMANDS.contains(&command.as_str()) {
+                bail!(
+                    "Invalid hook command '{}', expected one of {}",
+                    command,
+                    HOOK_COMMANDS.join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the hook runs on `event` of `command` for `session`.
+    pub fn applies(&self, event: HookEvent, command: &str, session: &Session) -> bool {
+        self.on == event
+            && (self.commands.is_empty() || self.commands.iter().any(|c| c == command))
+            && (self.tags.is_empty() || !self.tags.is_disjoint(&session.tags))
+    }
+}
+
+/// How to reconnect after the connection dropped.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ReconnectPolicy {
+    #[serde(default = "ReconnectPolicy::default_max_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first attempt, doubled after each failed one
+    #[serde(default = "ReconnectPolicy::default_initial_delay")]
+    pub initial_delay_secs: u64,
+    #[serde(default = "ReconnectPolicy::default_max_delay")]
+    pub max_delay_secs: u64,
+}
+
+impl ReconnectPolicy {
+    fn default_max_attempts() -> u32 {
+        5
+    }
+
+    fn default_initial_delay() -> u64 {
+        1
+    }
+
+    fn default_max_delay() -> u64 {
+        60
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            max_attempts: Self::default_max_attempts(),
+            initial_delay_secs: Self::default_initial_delay(),
+            max_delay_secs: Self::default_max_delay(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub enum Multiplexer {
+    #[serde(rename = "tmux")]
+    Tmux,
+    #[serde(rename = "screen")]
+    Screen,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Template {
     pub name: String,
     pub session: String,
+    /// ssh options given to the sessions created from this template
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub ssh_options: BTreeMap<String, String>,
+    /// ssh arguments given to the sessions created from this template
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_args: Vec<String>,
+}
+
+impl Template {
+    /// Adds the ssh options and arguments of the template to `session`.
+    pub fn apply_to(&self, session: &mut Session) {
+        for (key, value) in self.ssh_options.iter() {
+            session
+                .ssh_options
+                .retain(|k, _| !k.eq_ignore_ascii_case(key));
+            session.ssh_options.insert(key.clone(), value.clone());
+        }
+        session.extra_args.extend(self.extra_args.iter().cloned());
+    }
+}
+
+/// ssh options that devlg sets itself from the session, in lowercase.
+const MANAGED_SSH_OPTIONS: [&str; 7] = [
+    "hostname",
+    "port",
+    "user",
+    "identityfile",
+    "controlpath",
+    "controlmaster",
+    "controlpersist",
+];
+
+/// ssh flags that set the same things as [`MANAGED_SSH_OPTIONS`].
+const MANAGED_SSH_FLAGS: [&str; 5] = ["-p", "-l", "-i", "-S", "-M"];
+
+/// Rejects ssh options and arguments that would override what devlg manages.
+pub fn validate_ssh_options(
+    ssh_options: &BTreeMap<String, String>,
+    extra_args: &[String],
+) -> Result<()> {
+    fn check_option(key: &str) -> Result<()> {
+        if key.is_empty() {
+            bail!("ssh option name cannot be empty");
+        }
+        if MANAGED_SSH_OPTIONS.contains(&key.to_lowercase().as_str()) {
+            bail!(
+                "ssh option '{}' is managed by devlg, set it on the session instead",
+                key
+            );
+        }
+        Ok(())
+    }
+
+    for key in ssh_options.keys() {
+        check_option(key)?;
+    }
+
+    let mut args = extra_args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(option) = arg.strip_prefix("-o") {
+            // both `-o Key=Value` and `-oKey=Value` are accepted by ssh
+            let option = if option.is_empty() {
+                args.next().map(String::as_str).unwrap_or_default()
+            } else {
+                option
+            };
+            check_option(option.split(['=', ' ']).next().unwrap_or_default())?;
+        } else if MANAGED_SSH_FLAGS.iter().any(|flag| arg.starts_with(flag)) {
+            bail!(
+                "ssh argument '{}' is managed by devlg, set it on the session instead",
+                arg
+            );
+        }
+    }
+
+    Ok(())
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
@@ -34,6 +283,9 @@ pub enum AuthType {
     Key,
     #[serde(rename = "password")]
     Password,
+    /// Keyboard-interactive password and one-time code, e.g. of a bastion
+    #[serde(rename = "totp")]
+    Totp,
 }
 
 impl FromStr for AuthType {
@@ -43,6 +295,7 @@ impl FromStr for AuthType {
         Ok(match s {
             "key" => AuthType::Key,
             "password" => AuthType::Password,
+            "totp" => AuthType::Totp,
             _ => bail!("Invalid auth type: {}", s),
         })
     }
@@ -53,6 +306,7 @@ impl From<AuthType> for String {
         match auth_type {
             AuthType::Key => "key".to_string(),
             AuthType::Password => "password".to_string(),
+            AuthType::Totp => "totp".to_string(),
         }
     }
 }
@@ -72,6 +326,7 @@ pub struct SessionBuilder {
     auth_type: Option<AuthType>,
     private_key_path: Option<PathBuf>,
     password: Option<String>,
+    totp_secret: Option<String>,
     tags: Option<HashSet<String>>,
 }
 
@@ -115,6 +370,11 @@ impl SessionBuilder {
         self
     }
 
+    pub fn totp_secret(mut self, totp_secret: Option<String>) -> Self {
+        self.totp_secret = totp_secret;
+        self
+    }
+
     pub fn tags(mut self, tags: Option<HashSet<String>>) -> Self {
         self.tags = tags;
         self
@@ -137,7 +397,23 @@ impl SessionBuilder {
                 .ok_or_else(|| anyhow::anyhow!("Auth type is required"))?,
             private_key_path: self.private_key_path,
             password: self.password,
+            totp_secret: self.totp_secret,
             tags: self.tags.unwrap_or_default(),
+            protected: false,
+            record: false,
+            keepalive_interval: None,
+            keepalive_count_max: None,
+            reconnect: None,
+            multiplexer: None,
+            multiplexer_session: None,
+            ssh_options: BTreeMap::new(),
+            extra_args: Vec::new(),
+            remote_command: None,
+            env: BTreeMap::new(),
+            send_env: Vec::new(),
+            host_key_fingerprints: Vec::new(),
+            transport: Transport::Ssh,
+            hooks: Vec::new(),
         };
 
         session.validate()?;
@@ -146,6 +422,8 @@ impl SessionBuilder {
 }
 
 impl Session {
+    /// A valid session for tests, panics otherwise. Use [`SessionBuilder`] on user input.
+    #[cfg(test)]
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: String,
@@ -184,6 +462,70 @@ impl Session {
             .expect("Failed to build empty template")
     }
 
+    /// Drops every secret from the session, e.g. before it leaves this machine.
+    pub fn strip_secrets(&mut self) {
+        self.password = None;
+        self.totp_secret = None;
+    }
+
+    /// Fills in the secrets missing from this session with the ones of `other`, as long as
+    /// its auth type still uses them.
+    pub fn restore_secrets(&mut self, other: &Session) {
+        let uses_password = matches!(self.auth_type, AuthType::Password | AuthType::Totp);
+        if self.password.is_none() && uses_password {
+            self.password = other.password.clone();
+        }
+        if self.totp_secret.is_none() && self.auth_type == AuthType::Totp {
+            self.totp_secret = other.totp_secret.clone();
+        }
+    }
+
+    /// The value of the ssh option `key` set by the session, in its options or its extra
+    /// arguments. Like ssh, the first value wins.
+    pub fn ssh_option(&self, key: &str) -> Option<&str> {
+        if let Some((_, value)) = self
+            .ssh_options
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        {
+            return Some(value);
+        }
+
+        let mut args = self.extra_args.iter();
+        while let Some(arg) = args.next() {
+            let Some(option) = arg.strip_prefix("-o") else {
+                continue;
+            };
+            let option = if option.is_empty() {
+                args.next().map(String::as_str).unwrap_or_default()
+            } else {
+                option
+            };
+            if let Some((k, value)) = option.split_once(['=', ' '])
+                && k.trim().eq_ignore_ascii_case(key)
+            {
+                return Some(value.trim());
+            }
+        }
+        None
+    }
+
+    /// The jump hosts the session connects through, from `ProxyJump` or `-J`.
+    pub fn proxy_jump(&self) -> Option<&str> {
+        if let Some(jump) = self.ssh_option("ProxyJump") {
+            return Some(jump).filter(|jump| !jump.eq_ignore_ascii_case("none"));
+        }
+        let mut args = self.extra_args.iter();
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("-J") {
+                Some("") => return args.next().map(String::as_str),
+                Some(jump) => return Some(jump),
+                None => {}
+            }
+        }
+        None
+    }
+
     pub fn validate(&self) -> Result<()> {
         if self.name.is_empty() {
             bail!("Session name cannot be empty");
@@ -198,6 +540,30 @@ impl Session {
             bail!("Port cannot be 0");
         }
 
+        validate_ssh_options(&self.ssh_options, &self.extra_args)?;
+
+        for name in self.env.keys().chain(self.send_env.iter()) {
+            if name.is_empty() || name.contains(|c: char| c == '=' || c.is_whitespace()) {
+                bail!("Invalid environment variable name: '{}'", name);
+            }
+        }
+
+        for hook in self.hooks.iter() {
+            hook.validate()?;
+        }
+
+        if self.multiplexer_session.is_some() && self.multiplexer.is_none() {
+            bail!("Multiplexer session requires a multiplexer");
+        }
+
+        if let Transport::Command(command) = &self.transport {
+            if command.first().is_none_or(|program| program.is_empty()) {
+                bail!("Transport command cannot be empty");
+            }
+            // the command authenticates on its own
+            return Ok(());
+        }
+
         match self.auth_type {
             AuthType::Key => {
                 if self.private_key_path.is_none() {
@@ -209,6 +575,15 @@ impl Session {
                     bail!("Password is required for password authentication");
                 }
             }
+            AuthType::Totp => {
+                if self.password.is_none() {
+                    bail!("Password is required for TOTP authentication");
+                }
+                let secret = self.totp_secret.as_ref().ok_or_else(|| {
+                    anyhow::anyhow!("TOTP secret is required for TOTP authentication")
+                })?;
+                totp::validate_secret(secret)?;
+            }
         }
 
         Ok(())
@@ -237,4 +612,20 @@ mod tests {
             .unwrap();
         assert!(valid_session.validate().is_ok());
     }
+
+    #[test]
+    fn test_ssh_options_validation() {
+        let options = BTreeMap::from([("Compression".to_string(), "yes".to_string())]);
+        let args = vec![
+            "-A".to_string(),
+            "-o".to_string(),
+            "RequestTTY=yes".to_string(),
+        ];
+        assert!(validate_ssh_options(&options, &args).is_ok());
+
+        let options = BTreeMap::from([("port".to_string(), "2222".to_string())]);
+        assert!(validate_ssh_options(&options, &[]).is_err());
+        assert!(validate_ssh_options(&BTreeMap::new(), &["-oUser=root".to_string()]).is_err());
+        assert!(validate_ssh_options(&BTreeMap::new(), &["-p2222".to_string()]).is_err());
+    }
 }
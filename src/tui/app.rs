@@ -0,0 +1,600 @@
+use anyhow::{Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Duration;
+
+use crate::commands::parse_tags;
+use crate::config::facts::FactsStore;
+use crate::config::history::HistoryStore;
+use crate::config::manager::ConfigManager;
+use crate::models::session::{AuthType, Session};
+use crate::utils::check::{self, CheckResult};
+use crate::utils::{doctor, runner};
+
+/// Group of the sessions without tags.
+pub const UNTAGGED: &str = "(untagged)";
+
+const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A line of the session tree.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Row {
+    Group {
+        tag: String,
+        sessions: usize,
+        collapsed: bool,
+    },
+    Session {
+        name: String,
+    },
+}
+
+pub enum Mode {
+    Browse,
+    Search,
+    Prompt(Prompt),
+    Edit(EditForm),
+    ConfirmDelete(String),
+}
+
+pub enum PromptKind {
+    Tags,
+    Copy,
+}
+
+/// A line of text typed for the selected session.
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub session: String,
+    pub input: String,
+}
+
+/// The editable fields of a session.
+pub struct EditForm {
+    pub session: String,
+    pub fields: Vec<(&'static str, String)>,
+    pub focused: usize,
+}
+
+/// What the dashboard has to leave the screen for.
+pub enum Action {
+    Login(Session),
+    Copy {
+        session: Session,
+        sources: Vec<PathBuf>,
+        destination: PathBuf,
+        upload: bool,
+    },
+}
+
+pub struct App {
+    pub manager: ConfigManager,
+    pub facts: FactsStore,
+    pub history: HistoryStore,
+    pub checks: HashMap<String, CheckResult>,
+    /// Sessions being checked
+    pub checking: HashSet<String>,
+    pub search: String,
+    pub collapsed: HashSet<String>,
+    pub selected: usize,
+    pub mode: Mode,
+    /// Message of the last action, and whether it failed
+    pub message: Option<(String, bool)>,
+    pub quit: bool,
+    check_tx: Sender<CheckResult>,
+    check_rx: Receiver<CheckResult>,
+}
+
+impl App {
+    pub fn new(manager: ConfigManager) -> Result<Self> {
+        let mut facts = FactsStore::new(None);
+        facts.load()?;
+        let mut history = HistoryStore::new(None);
+        history.load()?;
+        let (check_tx, check_rx) = mpsc::channel();
+
+        Ok(App {
+            manager,
+            facts,
+            history,
+            checks: HashMap::new(),
+            checking: HashSet::new(),
+            search: String::new(),
+            collapsed: HashSet::new(),
+            selected: 0,
+            mode: Mode::Browse,
+            message: None,
+            quit: false,
+            check_tx,
+            check_rx,
+        })
+    }
+
+    /// Whether `session` matches the search, by name, host, user or tag.
+    fn matches(&self, session: &Session) -> bool {
+        let search = self.search.to_lowercase();
+        search.is_empty()
+            || session.name.to_lowercase().contains(&search)
+            || session.host.to_lowercase().contains(&search)
+            || session.user.to_lowercase().contains(&search)
+            || session
+                .tags
+                .iter()
+                .any(|t| t.to_lowercase().contains(&search))
+    }
+
+    /// The session tree, one group per tag with the matching sessions in it. Sessions with
+    /// several tags are in several groups.
+    pub fn rows(&self) -> Vec<Row> {
+        let mut groups: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+        let mut untagged = Vec::new();
+        for session in self.manager.config.sessions.iter() {
+            if !self.matches(session) {
+                continue;
+            }
+            if session.tags.is_empty() {
+                untagged.push(session.name.as_str());
+            }
+            for tag in session.tags.iter() {
+                groups.entry(tag).or_default().push(&session.name);
+            }
+        }
+
+        let mut rows = Vec::new();
+        let groups = groups
+            .into_iter()
+            .chain((!untagged.is_empty()).then_some((UNTAGGED, untagged)));
+        for (tag, mut names) in groups {
+            names.sort();
+            // a search shows every match
+            let collapsed = self.search.is_empty() && self.collapsed.contains(tag);
+            rows.push(Row::Group {
+                tag: tag.to_string(),
+                sessions: names.len(),
+                collapsed,
+            });
+            if !collapsed {
+                rows.extend(names.into_iter().map(|name| Row::Session {
+                    name: name.to_string(),
+                }));
+            }
+        }
+        rows
+    }
+
+    /// The session on the selected row.
+    pub fn selected_session(&self) -> Option<&Session> {
+        match self.rows().get(self.selected) {
+            Some(Row::Session { name }) => self.manager.config.get_session(name),
+            _ => None,
+        }
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = index.min(self.rows().len().saturating_sub(1));
+    }
+
+    /// Collects the results of the checks that finished.
+    pub fn poll_checks(&mut self) {
+        while let Ok(result) = self.check_rx.try_recv() {
+            self.checking.remove(&result.name);
+            self.checks.insert(result.name.clone(), result);
+        }
+    }
+
+    fn check(&mut self, sessions: Vec<Session>) {
+        for session in sessions {
+            let session = match self.manager.config.settings.apply_defaults(&session) {
+                Ok(session) => session,
+                Err(e) => {
+                    self.message = Some((format!("{:#}", e), true));
+                    continue;
+                }
+            };
+            if !self.checking.insert(session.name.clone()) {
+                continue;
+            }
+            let tx = self.check_tx.clone();
+            runner::spawn(move || {
+                let _ = tx.send(check::check(&session, CHECK_TIMEOUT));
+            });
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyEvent) -> Option<Action> {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return None;
+        }
+
+        match std::mem::replace(&mut self.mode, Mode::Browse) {
+            Mode::Browse => return self.browse_key(key),
+            Mode::Search => self.search_key(key),
+            Mode::Prompt(prompt) => return self.prompt_key(prompt, key),
+            Mode::Edit(form) => self.edit_key(form, key),
+            Mode::ConfirmDelete(name) => {
+                if key.code == KeyCode::Char('y') {
+                    let result = self
+                        .manager
+                        .config
+                        .remove_session(&name)
+                        .and_then(|_| self.manager.save());
+                    self.report(result, format!("Session '{}' deleted.", name));
+                    self.select(self.selected);
+                }
+            }
+        }
+        None
+    }
+
+    fn browse_key(&mut self, key: KeyEvent) -> Option<Action> {
+        let rows = self.rows();
+        let row = rows.get(self.selected).cloned();
+        self.message = None;
+
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Esc if !self.search.is_empty() => {
+                self.search.clear();
+                self.select(0);
+            }
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Up | KeyCode::Char('k') => self.select(self.selected.saturating_sub(1)),
+            KeyCode::Down | KeyCode::Char('j') => self.select(self.selected + 1),
+            KeyCode::PageUp => self.select(self.selected.saturating_sub(10)),
+            KeyCode::PageDown => self.select(self.selected + 10),
+            KeyCode::Home | KeyCode::Char('g') => self.select(0),
+            KeyCode::End | KeyCode::Char('G') => self.select(rows.len()),
+            KeyCode::Char('/') => self.mode = Mode::Search,
+            KeyCode::Left | KeyCode::Char('h') => match row {
+                Some(Row::Group { tag, .. }) => {
+                    self.collapsed.insert(tag);
+                }
+                // back to the group of the session
+                Some(Row::Session { .. }) => {
+                    let group = rows[..self.selected]
+                        .iter()
+                        .rposition(|r| matches!(r, Row::Group { .. }));
+                    self.select(group.unwrap_or(0));
+                }
+                None => {}
+            },
+            KeyCode::Right | KeyCode::Char('l') => {
+                if let Some(Row::Group { tag, .. }) = row {
+                    self.collapsed.remove(&tag);
+                }
+            }
+            KeyCode::Enter => match row {
+                Some(Row::Group { tag, collapsed, .. }) => {
+                    if collapsed {
+                        self.collapsed.remove(&tag);
+                    } else {
+                        self.collapsed.insert(tag);
+                    }
+                }
+                Some(Row::Session { .. }) => {
+                    return self.selected_session().cloned().map(Action::Login);
+                }
+                None => {}
+            },
+            KeyCode::Char('R') => {
+                let sessions = self
+                    .manager
+                    .config
+                    .sessions
+                    .iter()
+                    .filter(|s| self.matches(s))
+                    .cloned()
+                    .collect();
+                self.check(sessions);
+            }
+            KeyCode::Char(c) => {
+                let session = self.selected_session().cloned()?;
+                let prompt = |kind, input| {
+                    Mode::Prompt(Prompt {
+                        kind,
+                        session: session.name.clone(),
+                        input,
+                    })
+                };
+                match c {
+                    'r' => self.check(vec![session.clone()]),
+                    'd' => self.mode = Mode::ConfirmDelete(session.name.clone()),
+                    'e' => self.mode = Mode::Edit(EditForm::new(&session)),
+                    't' => {
+                        let mut tags: Vec<&str> = session.tags.iter().map(String::as_str).collect();
+                        tags.sort();
+                        self.mode = prompt(PromptKind::Tags, tags.join(", "));
+                    }
+                    'c' => self.mode = prompt(PromptKind::Copy, String::new()),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => self.search.clear(),
+            KeyCode::Enter | KeyCode::Down => {}
+            KeyCode::Backspace => {
+                self.search.pop();
+                self.mode = Mode::Search;
+            }
+            KeyCode::Char(c) => {
+                self.search.push(c);
+                self.mode = Mode::Search;
+            }
+            _ => self.mode = Mode::Search,
+        }
+        // the first match
+        let first = self
+            .rows()
+            .iter()
+            .position(|r| matches!(r, Row::Session { .. }));
+        self.select(first.unwrap_or(0));
+    }
+
+    fn prompt_key(&mut self, mut prompt: Prompt, key: KeyEvent) -> Option<Action> {
+        match key.code {
+            KeyCode::Esc => {}
+            KeyCode::Enter => {
+                let session = self.manager.config.get_session(&prompt.session)?.clone();
+                match prompt.kind {
+                    PromptKind::Tags => {
+                        let tags = parse_tags(Some(&prompt.input));
+                        let result = self
+                            .manager
+                            .config
+                            .update_session(Session { tags, ..session });
+                        let result = result.and_then(|_| self.manager.save());
+                        self.report(result, format!("Tags of '{}' updated.", prompt.session));
+                    }
+                    PromptKind::Copy => match copy_paths(&prompt.input) {
+                        Ok((sources, destination, upload)) => {
+                            return Some(Action::Copy {
+                                session,
+                                sources,
+                                destination,
+                                upload,
+                            });
+                        }
+                        Err(e) => {
+                            self.message = Some((format!("{:#}", e), true));
+                            self.mode = Mode::Prompt(prompt);
+                        }
+                    },
+                }
+            }
+            KeyCode::Backspace => {
+                prompt.input.pop();
+                self.mode = Mode::Prompt(prompt);
+            }
+            KeyCode::Char(c) => {
+                prompt.input.push(c);
+                self.mode = Mode::Prompt(prompt);
+            }
+            _ => self.mode = Mode::Prompt(prompt),
+        }
+        None
+    }
+
+    fn edit_key(&mut self, mut form: EditForm, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => return,
+            KeyCode::Enter => {
+                let Some(session) = self.manager.config.get_session(&form.session) else {
+                    return;
+                };
+                let result = form.apply(session).and_then(|session| {
+                    if self.manager.config.settings.deep_validation {
+                        doctor::validate_deep(&session)?;
+                    } else {
+                        session.validate()?;
+                    }
+                    self.manager.config.update_session(session)?;
+                    self.manager.save()
+                });
+                if let Err(e) = result {
+                    self.message = Some((format!("{:#}", e), true));
+                    self.mode = Mode::Edit(form);
+                } else {
+                    self.message = Some((format!("Session '{}' modified.", form.session), false));
+                }
+                return;
+            }
+            KeyCode::Up | KeyCode::BackTab => {
+                form.focused = form.focused.checked_sub(1).unwrap_or(form.fields.len() - 1);
+            }
+            KeyCode::Down | KeyCode::Tab => form.focused = (form.focused + 1) % form.fields.len(),
+            KeyCode::Backspace => {
+                form.fields[form.focused].1.pop();
+            }
+            KeyCode::Char(c) => form.fields[form.focused].1.push(c),
+            _ => {}
+        }
+        self.mode = Mode::Edit(form);
+    }
+
+    pub fn report(&mut self, result: Result<()>, success: String) {
+        self.message = Some(match result {
+            Ok(()) => (success, false),
+            Err(e) => (format!("{:#}", e), true),
+        });
+    }
+}
+
+impl EditForm {
+    fn new(session: &Session) -> Self {
+        let key = session
+            .private_key_path
+            .as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        EditForm {
+            session: session.name.clone(),
+            fields: vec![
+                ("Host", session.host.clone()),
+                ("User", session.user.clone()),
+                ("Port", session.port.to_string()),
+                ("Auth", session.auth_type.to_string()),
+                ("Key", key),
+                // left empty to keep the password
+                ("Password", String::new()),
+            ],
+            focused: 0,
+        }
+    }
+
+    /// `session` with the values of the form.
+    fn apply(&self, session: &Session) -> Result<Session> {
+        let field = |label: &str| {
+            self.fields
+                .iter()
+                .find(|(l, _)| *l == label)
+                .map(|(_, value)| value.trim().to_string())
+                .unwrap_or_default()
+        };
+        let auth_type: AuthType = field("Auth").parse()?;
+        let port = field("Port")
+            .parse()
+            .with_context(|| format!("Invalid port '{}'", field("Port")))?;
+        let key = Some(field("Key"))
+            .filter(|k| !k.is_empty())
+            .map(PathBuf::from);
+        let password = Some(field("Password")).filter(|p| !p.is_empty());
+
+        Ok(Session {
+            host: field("Host"),
+            user: field("User"),
+            port,
+            auth_type,
+            private_key_path: key.filter(|_| auth_type == AuthType::Key),
+            password: password
+                .or_else(|| session.password.clone())
+                .filter(|_| auth_type != AuthType::Key),
+            ..session.clone()
+        })
+    }
+}
+
+/// Parses the copy prompt: local paths followed by `:remote` to upload, or `:remote` paths
+/// followed by a local one to download. Returns the sources, the destination and whether
+/// it is an upload.
+fn copy_paths(input: &str) -> Result<(Vec<PathBuf>, PathBuf, bool)> {
+    let mut paths: Vec<&str> = input.split_whitespace().collect();
+    let destination = paths.pop().filter(|_| !paths.is_empty()).context(
+        "Give the paths to copy and a destination, with ':' in front of the remote ones",
+    )?;
+    let remote = |path: &&str| path.starts_with(':');
+    let strip = |path: &str| PathBuf::from(path.trim_start_matches(':'));
+    if remote(&destination) && !paths.iter().any(remote) {
+        Ok((
+            paths.into_iter().map(strip).collect(),
+            strip(destination),
+            true,
+        ))
+    } else if !remote(&destination) && paths.iter().all(remote) {
+        Ok((
+            paths.into_iter().map(strip).collect(),
+            strip(destination),
+            false,
+        ))
+    } else {
+        anyhow::bail!("Copy from or to the session, with ':' in front of the remote paths")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyEvent;
+
+    fn session(name: &str, tags: &[&str]) -> Session {
+        Session::new(
+            name.to_string(),
+            format!("{}.example.com", name),
+            "user".to_string(),
+            22,
+            AuthType::Key,
+            Some(PathBuf::from("~/.ssh/id_ed25519")),
+            None,
+            Some(tags.iter().map(|t| t.to_string()).collect()),
+        )
+    }
+
+    #[test]
+    fn test_dashboard_tree_and_search() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut manager = ConfigManager::new(Some(dir.path().join("devlg.toml")));
+        manager.config.sessions = vec![
+            session("web", &["prod", "frontend"]),
+            session("db", &["prod"]),
+            session("laptop", &[]),
+        ];
+        let mut app = App {
+            facts: FactsStore::new(Some(dir.path().join("facts.json"))),
+            history: HistoryStore::new(Some(dir.path().join("history.jsonl"))),
+            ..App::new(manager)?
+        };
+
+        let names = |app: &App| -> Vec<String> {
+            app.rows()
+                .into_iter()
+                .map(|row| match row {
+                    Row::Group { tag, .. } => format!("[{}]", tag),
+                    Row::Session { name } => name,
+                })
+                .collect()
+        };
+        assert_eq!(
+            names(&app),
+            [
+                "[frontend]",
+                "web",
+                "[prod]",
+                "db",
+                "web",
+                "[(untagged)]",
+                "laptop"
+            ]
+        );
+
+        // collapse the first group and select the next one
+        app.handle_key(KeyEvent::from(KeyCode::Left));
+        app.handle_key(KeyEvent::from(KeyCode::Down));
+        assert_eq!(names(&app)[..3], ["[frontend]", "[prod]", "db"]);
+        assert!(app.selected_session().is_none());
+
+        for key in [KeyCode::Char('/'), KeyCode::Char('d'), KeyCode::Char('b')] {
+            app.handle_key(KeyEvent::from(key));
+        }
+        assert_eq!(names(&app), ["[prod]", "db"]);
+        assert_eq!(app.selected_session().unwrap().name, "db");
+
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        app.handle_key(KeyEvent::from(KeyCode::Char('t')));
+        for c in ", staging".chars() {
+            app.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+        app.handle_key(KeyEvent::from(KeyCode::Enter));
+        let db = app.manager.config.get_session("db").unwrap();
+        assert!(db.tags.contains("staging") && db.tags.contains("prod"));
+
+        assert_eq!(
+            copy_paths("a b :/tmp")?,
+            (
+                vec![PathBuf::from("a"), PathBuf::from("b")],
+                PathBuf::from("/tmp"),
+                true
+            )
+        );
+        assert!(!copy_paths(":/var/log/syslog .")?.2);
+        assert!(copy_paths("a b").is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,113 @@
+//! Full-screen dashboard of the sessions.
+
+pub mod app;
+mod ui;
+
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyEventKind};
+use crossterm::execute;
+use crossterm::terminal::{
+    EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
+};
+use ratatui::DefaultTerminal;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::commands::login;
+use crate::config::history;
+use crate::config::manager::ConfigManager;
+use crate::tui::app::{Action, App};
+use crate::utils::{hooks, protected, scp};
+
+pub fn run(manager: ConfigManager) -> Result<()> {
+    let mut app = App::new(manager)?;
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut app);
+    ratatui::restore();
+    result
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<()> {
+    while !app.quit {
+        app.poll_checks();
+        terminal.draw(|frame| ui::draw(frame, app))?;
+
+        // wake up regularly for the results of the checks
+        if !event::poll(Duration::from_millis(200))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if let Some(action) = app.handle_key(key) {
+            let result = suspend(terminal, || run_action(app, action));
+            // the action was tracked in the history
+            let result = result.and_then(|message| app.history.load().map(|_| message));
+            app.message = Some(match result {
+                Ok(message) => (message, false),
+                Err(e) => (format!("{:#}", e), true),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Runs `f` on the normal screen, then returns to the dashboard.
+fn suspend<T>(terminal: &mut DefaultTerminal, f: impl FnOnce() -> Result<T>) -> Result<T> {
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)?;
+
+    let result = f();
+
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    terminal.clear()?;
+    result
+}
+
+/// Runs `action`, returning a message for the status line.
+fn run_action(app: &App, action: Action) -> Result<String> {
+    match action {
+        Action::Login(session) => {
+            login::login(&app.manager.config.settings, &session, false)?;
+            Ok(format!("Logged out of '{}'.", session.name))
+        }
+        Action::Copy {
+            session,
+            sources,
+            destination,
+            upload,
+        } => {
+            let settings = &app.manager.config.settings;
+            let session = settings.apply_defaults(&session)?;
+            let sources: Vec<&Path> = sources.iter().map(|p| p.as_path()).collect();
+            let (src, dst) = if upload {
+                (None, Some(&session))
+            } else {
+                (Some(&session), None)
+            };
+            let copy = || {
+                hooks::around(settings, &[&session], "cp", false, || {
+                    history::track(&session, "cp", || {
+                        scp::copy_file(src, dst, sources, &destination, true, false)
+                    })
+                })
+            };
+            let result = if upload && settings.is_protected(&session) {
+                protected::confirm(&session, "copy to")
+                    .and_then(|_| protected::marked(&session, copy))
+            } else {
+                copy()
+            };
+            // leave the output of scp on screen until the user is done reading it
+            print!("Press Enter to return to the dashboard.");
+            io::stdout().flush()?;
+            io::stdin().read_line(&mut String::new())?;
+            result.map(|_| "Copy complete.".to_string())
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use ratatui::Frame;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
+
+use crate::models::facts::format_size;
+use crate::models::session::Session;
+use crate::tui::app::{App, Mode, PromptKind, Row};
+use crate::utils::check::Status;
+use crate::utils::time::{format_age, format_duration};
+
+const HELP: &str =
+    "enter login  / search  c copy  e edit  t tag  d delete  r check  R check all  q quit";
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [search, main, status] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(5),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+    let [tree, details] =
+        Layout::horizontal([Constraint::Percentage(40), Constraint::Percentage(60)]).areas(main);
+
+    let searching = matches!(app.mode, Mode::Search);
+    let search_style = if searching {
+        Style::default().fg(Color::Yellow)
+    } else {
+        Style::default()
+    };
+    let cursor = if searching { "_" } else { "" };
+    frame.render_widget(
+        Paragraph::new(format!("{}{}", app.search, cursor)).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(search_style)
+                .title(" Search (/) "),
+        ),
+        search,
+    );
+
+    draw_tree(frame, app, tree);
+    draw_details(frame, app, details);
+
+    let status_line = match &app.message {
+        Some((message, true)) => Line::from(message.as_str().red()),
+        Some((message, false)) => Line::from(message.as_str().green()),
+        None => Line::from(HELP.dark_gray()),
+    };
+    frame.render_widget(Paragraph::new(status_line), status);
+
+    match &app.mode {
+        Mode::Prompt(prompt) => {
+            let title = match prompt.kind {
+                PromptKind::Tags => format!(" Tags of {}, separated by commas ", prompt.session),
+                PromptKind::Copy => format!(
+                    " Copy, ':' marks the paths on {}: local... :remote or :remote... local ",
+                    prompt.session
+                ),
+            };
+            let area = popup(frame.area(), 3);
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(format!("{}_", prompt.input))
+                    .block(Block::default().borders(Borders::ALL).title(title)),
+                area,
+            );
+        }
+        Mode::Edit(form) => {
+            let area = popup(frame.area(), form.fields.len() as u16 + 3);
+            let lines: Vec<Line> = form
+                .fields
+                .iter()
+                .enumerate()
+                .map(|(i, (label, value))| {
+                    let value = if *label == "Password" {
+                        "*".repeat(value.chars().count())
+                    } else {
+                        value.clone()
+                    };
+                    let line =
+                        Line::from(vec![Span::raw(format!("{:<10}", label)), Span::raw(value)]);
+                    if i == form.focused {
+                        line.style(Style::default().add_modifier(Modifier::REVERSED))
+                    } else {
+                        line
+                    }
+                })
+                .chain([Line::from(
+                    "tab next field  enter save  esc cancel".dark_gray(),
+                )])
+                .collect();
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(lines).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(format!(" Edit {} ", form.session)),
+                ),
+                area,
+            );
+        }
+        Mode::ConfirmDelete(name) => {
+            let area = popup(frame.area(), 3);
+            frame.render_widget(Clear, area);
+            frame.render_widget(
+                Paragraph::new(format!("Delete session '{}'? (y/n)", name))
+                    .block(Block::default().borders(Borders::ALL).title(" Delete ")),
+                area,
+            );
+        }
+        Mode::Browse | Mode::Search => {}
+    }
+}
+
+fn draw_tree(frame: &mut Frame, app: &App, area: Rect) {
+    let items: Vec<ListItem> = app
+        .rows()
+        .into_iter()
+        .map(|row| match row {
+            Row::Group {
+                tag,
+                sessions,
+                collapsed,
+            } => {
+                let marker = if collapsed { "▸" } else { "▾" };
+                ListItem::new(Line::from(vec![
+                    Span::raw(format!("{} ", marker)),
+                    Span::styled(tag, Style::default().add_modifier(Modifier::BOLD)),
+                    Span::raw(format!(" ({})", sessions)).dark_gray(),
+                ]))
+            }
+            Row::Session { name } => {
+                let status = if app.checking.contains(&name) {
+                    Span::raw("… ").yellow()
+                } else {
+                    match app.checks.get(&name).map(|c| c.status) {
+                        Some(Status::Up) => Span::raw("● ").green(),
+                        Some(_) => Span::raw("● ").red(),
+                        None => Span::raw("  "),
+                    }
+                };
+                let protected = app
+                    .manager
+                    .config
+                    .get_session(&name)
+                    .is_some_and(|s| app.manager.config.settings.is_protected(s));
+                let name = if protected {
+                    Span::raw(name).red().bold()
+                } else {
+                    Span::raw(name)
+                };
+                ListItem::new(Line::from(vec![Span::raw("  "), status, name]))
+            }
+        })
+        .collect();
+
+    let mut state = ListState::default().with_selected(Some(app.selected));
+    frame.render_stateful_widget(
+        List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(" Sessions "))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED)),
+        area,
+        &mut state,
+    );
+}
+
+fn draw_details(frame: &mut Frame, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::ALL).title(" Details ");
+    let Some(session) = app.selected_session() else {
+        frame.render_widget(block, area);
+        return;
+    };
+    frame.render_widget(
+        Paragraph::new(details(app, session))
+            .block(block)
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}
+
+fn details<'a>(app: &App, session: &'a Session) -> Vec<Line<'a>> {
+    let field = |label: &'static str, value: String| {
+        Line::from(vec![
+            Span::raw(format!("{:<12}", label)).bold(),
+            Span::raw(value),
+        ])
+    };
+    let mut tags: Vec<&str> = session.tags.iter().map(String::as_str).collect();
+    tags.sort();
+
+    let mut lines = vec![
+        field("Name", session.name.clone()),
+        field("Host", session.host.clone()),
+        field("User", session.user.clone()),
+        field("Port", session.port.to_string()),
+        field("Auth", session.auth_type.to_string()),
+    ];
+    if let Some(key) = &session.private_key_path {
+        lines.push(field("Key", key.display().to_string()));
+    }
+    lines.push(field("Tags", tags.join(", ")));
+    if app.manager.config.settings.is_protected(session) {
+        lines.push(Line::from(vec![
+            Span::raw(format!("{:<12}", "Protected")).bold(),
+            Span::raw("yes").red().bold(),
+        ]));
+    }
+    for (key, value) in session.ssh_options.iter() {
+        lines.push(field("Option", format!("{}={}", key, value)));
+    }
+    if !session.extra_args.is_empty() {
+        lines.push(field("Extra args", session.extra_args.join(" ")));
+    }
+    if let Some(last) = app.history.last_used(&session.name) {
+        lines.push(field("Last used", format_age(last)));
+    }
+
+    if let Some(facts) = app.facts.get(&session.name) {
+        lines.push(Line::default());
+        let or_na = |value: Option<String>| value.unwrap_or_else(|| "N/A".to_string());
+        lines.push(field(
+            "OS",
+            or_na(facts.os_name.clone().or(facts.os.clone())),
+        ));
+        lines.push(field("Kernel", or_na(facts.kernel.clone())));
+        lines.push(field("CPUs", or_na(facts.cpus.map(|c| c.to_string()))));
+        lines.push(field("Memory", or_na(facts.mem_bytes.map(format_size))));
+        lines.push(field(
+            "Uptime",
+            or_na(facts.uptime_secs.map(format_duration)),
+        ));
+        lines.push(field("Facts", format_age(facts.collected_at)));
+    }
+
+    if let Some(check) = app.checks.get(&session.name) {
+        lines.push(Line::default());
+        let status = Span::raw(check.status.to_string());
+        let status = if check.status == Status::Up {
+            status.green()
+        } else {
+            status.red()
+        };
+        lines.push(Line::from(vec![
+            Span::raw(format!("{:<12}", "Status")).bold(),
+            status,
+        ]));
+        if let Some(ms) = check.latency_ms {
+            lines.push(field("Latency", format!("{}ms", ms)));
+        }
+        if let Some(details) = check
+            .error
+            .as_ref()
+            .or(check.banner.as_ref())
+            .or(check.via.as_ref())
+        {
+            lines.push(field("Details", details.clone()));
+        }
+    }
+    lines
+}
+
+/// A box of `height` lines in the middle of `area`.
+fn popup(area: Rect, height: u16) -> Rect {
+    let width = area.width.saturating_sub(4).min(90);
+    Rect {
+        x: area.x + (area.width - width) / 2,
+        y: area.y + area.height.saturating_sub(height) / 2,
+        width,
+        height: height.min(area.height),
+    }
+}
@@ -0,0 +1,202 @@
+use crate::utils::pty::PtyObserver;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+/// Header line of an asciicast v2 file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Header {
+    pub version: u8,
+    pub width: u16,
+    pub height: u16,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub env: HashMap<String, String>,
+}
+
+/// An event line: seconds since the start, event type (`o` for output, `r` for resize)
+/// and its data.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Event(pub f64, pub String, pub String);
+
+/// A recording read back from disk.
+pub struct Recording {
+    pub header: Header,
+    pub events: Vec<Event>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read recording at {:?}", path))?;
+        let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+
+        let header: Header = serde_json::from_str(lines.next().context("Recording is empty")?)
+            .with_context(|| "Failed to parse recording header")?;
+        if header.version != 2 {
+            anyhow::bail!("Unsupported asciicast version: {}", header.version);
+        }
+
+        let events = lines
+            .map(serde_json::from_str)
+            .collect::<Result<_, _>>()
+            .with_context(|| "Failed to parse recording events")?;
+
+        Ok(Recording { header, events })
+    }
+
+    /// Length of the recording in seconds.
+    pub fn duration(&self) -> f64 {
+        self.events.last().map(|e| e.0).unwrap_or_default()
+    }
+
+    /// Everything that was printed, in order.
+    pub fn output(&self) -> String {
+        self.events
+            .iter()
+            .filter(|e| e.1 == "o")
+            .map(|e| e.2.as_str())
+            .collect()
+    }
+}
+
+/// Writes the output of a pty to an asciicast v2 file.
+pub struct Recorder {
+    file: BufWriter<File>,
+    started: Instant,
+    /// Trailing bytes of an UTF-8 sequence split across two reads
+    pending: Vec<u8>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, header: &Header) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("Failed to create recordings directory at {:?}", parent)
+            })?;
+        }
+
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create recording at {:?}", path))?;
+        let mut recorder = Recorder {
+            file: BufWriter::new(file),
+            started: Instant::now(),
+            pending: Vec::new(),
+        };
+        writeln!(recorder.file, "{}", serde_json::to_string(header)?)?;
+        recorder.file.flush()?;
+        Ok(recorder)
+    }
+
+    fn write_event(&mut self, kind: &str, data: String) -> Result<()> {
+        let event = Event(self.started.elapsed().as_secs_f64(), kind.to_string(), data);
+        writeln!(self.file, "{}", serde_json::to_string(&event)?)?;
+        // keep the file usable even if devlg gets killed mid-session
+        self.file.flush()?;
+        Ok(())
+    }
+}
+
+impl PtyObserver for Recorder {
+    fn output(&mut self, data: &[u8]) -> Result<()> {
+        self.pending.extend_from_slice(data);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Ok(_) => self.pending.len(),
+            // an incomplete sequence at the end, wait for the rest of it
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => self.pending.len(),
+        };
+
+        let rest = self.pending.split_off(valid);
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending = rest;
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.write_event("o", text)
+    }
+
+    fn resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        self.write_event("r", format!("{}x{}", cols, rows))
+    }
+}
+
+/// Removes terminal escape sequences and carriage returns, leaving the plain text.
+pub fn strip_escapes(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\x1b' => match chars.next() {
+                // CSI: parameters up to a final byte in @..~
+                Some('[') => {
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+                // OSC: up to BEL or ESC \
+                Some(']') => {
+                    while let Some(c) = chars.next() {
+                        if c == '\x07' {
+                            break;
+                        }
+                        if c == '\x1b' {
+                            chars.next_if_eq(&'\\');
+                            break;
+                        }
+                    }
+                }
+                _ => {}
+            },
+            '\r' => {}
+            _ => plain.push(c),
+        }
+    }
+
+    plain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_record_and_load() -> Result<()> {
+        let temp_dir = tempdir()?;
+        let path = temp_dir.path().join("session").join("1.cast");
+        let header = Header {
+            version: 2,
+            width: 80,
+            height: 24,
+            timestamp: Some(1),
+            title: Some("session".to_string()),
+            env: HashMap::new(),
+        };
+
+        let mut recorder = Recorder::create(&path, &header)?;
+        // "é" split in the middle of its two bytes
+        recorder.output(b"caf\xc3")?;
+        recorder.output(b"\xa9\r\n\x1b[1;31mred\x1b[0m")?;
+        recorder.resize(100, 30)?;
+        drop(recorder);
+
+        let recording = Recording::load(&path)?;
+        assert_eq!(recording.header, header);
+        assert_eq!(recording.events.len(), 3);
+        assert_eq!(recording.events[2].2, "100x30");
+        assert_eq!(strip_escapes(&recording.output()), "café\nred");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,235 @@
+use crate::models::session::{Session, Transport};
+use crate::utils::ssh::{CONNECTION_ERROR_CODE, batch_options, exec_ssh_output};
+use serde::Serialize;
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, BufReader};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+/// Lines a server may send before its SSH version banner.
+const MAX_BANNER_LINES: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Status {
+    Up,
+    AuthFailed,
+    Unreachable,
+    Timeout,
+    Error,
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Status::Up => "up",
+            Status::AuthFailed => "auth-failed",
+            Status::Unreachable => "unreachable",
+            Status::Timeout => "timeout",
+            Status::Error => "error",
+        })
+    }
+}
+
+/// The health of one session.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: String,
+    pub host: String,
+    pub port: u16,
+    pub status: Status,
+    /// Time to log in and run `true` over ssh, in milliseconds
+    pub latency_ms: Option<u64>,
+    /// SSH version banner of the server
+    pub banner: Option<String>,
+    /// The jump host or proxy command the session connects through, whose host is only
+    /// checked by the login
+    pub via: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Checks that the host of `session` is reachable, speaks SSH and accepts the session's
+/// credentials, without opening a shell.
+pub fn check(session: &Session, timeout: Duration) -> CheckResult {
+    let mut result = CheckResult {
+        name: session.name.clone(),
+        host: session.host.clone(),
+        port: session.port,
+        status: Status::Up,
+        latency_ms: None,
+        banner: None,
+        via: proxy(session),
+        error: None,
+    };
+    let fail = |mut result: CheckResult, status, error: String| {
+        result.status = status;
+        result.error = Some(error);
+        result
+    };
+
+    if let Transport::Command(_) = session.transport {
+        let error = "connects with a command of its own, not with ssh".to_string();
+        return fail(result, Status::Error, error);
+    }
+
+    // the host may only be reachable from the jump host
+    if result.via.is_none() {
+        let stream = match connect(&session.host, session.port, timeout) {
+            Ok(stream) => stream,
+            Err(e) => return fail(result, io_status(&e), e.to_string()),
+        };
+
+        match read_banner(stream, timeout) {
+            Ok(banner) => result.banner = Some(banner),
+            Err(e) => return fail(result, io_status(&e), e.to_string()),
+        }
+    }
+
+    let connect_timeout = format!("ConnectTimeout={}", timeout.as_secs().max(1));
+    let mut options = vec!["-o", connect_timeout.as_str()];
+    options.extend(batch_options(session));
+    let start = Instant::now();
+    let output = match exec_ssh_output(session, &options, "true") {
+        Ok(output) => output,
+        Err(e) => return fail(result, Status::Error, format!("{:#}", e)),
+    };
+    if output.status.success() {
+        result.latency_ms = Some(start.elapsed().as_millis() as u64);
+        return result;
+    }
+
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    let status = if stderr.contains("Permission denied") || stderr.contains("Authentication") {
+        Status::AuthFailed
+    } else if stderr.contains("timed out") {
+        Status::Timeout
+    } else if output.status.code() == Some(CONNECTION_ERROR_CODE) {
+        Status::Unreachable
+    } else {
+        Status::Error
+    };
+    let error = stderr.lines().last().map_or_else(
+        || format!("ssh exited with {}", output.status),
+        str::to_string,
+    );
+    fail(result, status, error)
+}
+
+/// How `session` reaches its host when not directly, e.g. `via bastion`.
+fn proxy(session: &Session) -> Option<String> {
+    if let Some(jump) = session.proxy_jump() {
+        return Some(format!("via {}", jump));
+    }
+    session
+        .ssh_option("ProxyCommand")
+        .filter(|command| !command.eq_ignore_ascii_case("none"))
+        .map(|_| "via ProxyCommand".to_string())
+}
+
+fn connect(host: &str, port: u16, timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_error = io::Error::new(io::ErrorKind::NotFound, "Host not found");
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = e,
+        }
+    }
+    Err(last_error)
+}
+
+fn read_banner(stream: TcpStream, timeout: Duration) -> io::Result<String> {
+    stream.set_read_timeout(Some(timeout))?;
+    let mut reader = BufReader::new(stream);
+    for _ in 0..MAX_BANNER_LINES {
+        let mut line = String::new();
+        let read = reader.read_line(&mut line).map_err(|e| match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => io::Error::new(
+                io::ErrorKind::TimedOut,
+                "Timed out waiting for the SSH banner",
+            ),
+            _ => e,
+        })?;
+        if read == 0 {
+            break;
+        }
+        if line.starts_with("SSH-") {
+            return Ok(line.trim().to_string());
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidData,
+        "No SSH banner received",
+    ))
+}
+
+fn io_status(e: &io::Error) -> Status {
+    match e.kind() {
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => Status::Timeout,
+        io::ErrorKind::InvalidData => Status::Error,
+        _ => Status::Unreachable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::AuthType;
+    use crate::utils::runner::{self, RecordingRunner};
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn test_check_banner() -> anyhow::Result<()> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let port = listener.local_addr()?.port();
+        let server = thread::spawn(move || -> io::Result<()> {
+            let (mut stream, _) = listener.accept()?;
+            stream.write_all(b"hello\r\nSSH-2.0-Test_1.0\r\n")?;
+            let (mut stream, _) = listener.accept()?;
+            stream.write_all(b"HTTP/1.1 400 Bad Request\r\n")?;
+            Ok(())
+        });
+
+        let timeout = Duration::from_secs(2);
+        let stream = connect("127.0.0.1", port, timeout)?;
+        assert_eq!(read_banner(stream, timeout)?, "SSH-2.0-Test_1.0");
+
+        let stream = connect("127.0.0.1", port, timeout)?;
+        let e = read_banner(stream, timeout).unwrap_err();
+        assert_eq!(io_status(&e), Status::Error);
+
+        server.join().unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_via_jump() {
+        let mut session = Session::new(
+            "db".to_string(),
+            "db.internal".to_string(),
+            "deploy".to_string(),
+            22,
+            AuthType::Key,
+            Some("/keys/id_ed25519".into()),
+            None,
+            None,
+        );
+        assert_eq!(proxy(&session), None);
+        session.extra_args = vec!["-o".to_string(), "ProxyCommand=nc %h %p".to_string()];
+        assert_eq!(proxy(&session).as_deref(), Some("via ProxyCommand"));
+        session.extra_args = vec!["-J".to_string(), "bastion".to_string()];
+        assert_eq!(proxy(&session).as_deref(), Some("via bastion"));
+
+        // db.internal is not resolved, only the login through the jump host is checked
+        let recorder = RecordingRunner::new(0);
+        let result =
+            runner::with_runner(recorder.clone(), || check(&session, Duration::from_secs(1)));
+        assert_eq!(result.status, Status::Up);
+        assert!(result.latency_ms.is_some());
+        let commands = recorder.commands();
+        assert_eq!(commands.len(), 1);
+        assert!(commands[0].contains(" -J bastion "));
+        assert!(commands[0].ends_with(" db.internal true"));
+    }
+}
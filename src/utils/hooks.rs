@@ -0,0 +1,226 @@
+//! Runs the hooks of the sessions around their connections: the global hooks of the settings
+//! first, then the ones of the session.
+
+use crate::config::manager::Settings;
+use crate::models::session::{Hook, HookEvent, Session};
+use crate::utils::ssh::{self, ExitError};
+use crate::utils::{keys, runner};
+use anyhow::{Context, Result, bail};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus};
+
+/// Runs the `pre` hooks of `sessions` for `command`, then `connect` unless one of them failed,
+/// then the `post` hooks. A dry run only prints the hook command lines.
+pub fn around<F>(
+    settings: &Settings,
+    sessions: &[&Session],
+    command: &str,
+    dry_run: bool,
+    connect: F,
+) -> Result<()>
+where
+    F: FnOnce() -> Result<()>,
+{
+    for session in sessions {
+        for hook in hooks(settings, session, HookEvent::Pre, command)? {
+            let mut cmd = hook_command(hook, session, HookEvent::Pre, command, None)?;
+            if dry_run {
+                println!("{}", ssh::display_command(&cmd));
+                continue;
+            }
+            let status = run(&mut cmd, session)
+                .with_context(|| format!("Failed to run hook `{}`", describe(hook)))?;
+            if !status.success() {
+                bail!(
+                    "Hook `{}` failed with {}, not connecting to '{}'",
+                    describe(hook),
+                    status,
+                    session.name
+                );
+            }
+        }
+    }
+
+    let result = connect();
+
+    let exit_code = match &result {
+        Ok(()) => Some(0),
+        Err(e) => e.downcast_ref::<ExitError>().and_then(|e| e.status.code()),
+    };
+    for session in sessions {
+        for hook in hooks(settings, session, HookEvent::Post, command)? {
+            let mut cmd = hook_command(hook, session, HookEvent::Post, command, exit_code)?;
+            if dry_run {
+                println!("{}", ssh::display_command(&cmd));
+                continue;
+            }
+            // the connection is over, a failing post hook can only be reported
+            match run(&mut cmd, session) {
+                Ok(status) if status.success() => {}
+                Ok(status) => {
+                    eprintln!("Warning: hook `{}` failed with {}", describe(hook), status)
+                }
+                Err(e) => eprintln!("Warning: failed to run hook `{}`: {:#}", describe(hook), e),
+            }
+        }
+    }
+
+    result
+}
+
+/// The hooks to run on `event` of `command` for `session`, the global ones first.
+fn hooks<'a>(
+    settings: &'a Settings,
+    session: &'a Session,
+    event: HookEvent,
+    command: &str,
+) -> Result<Vec<&'a Hook>> {
+    let mut hooks = Vec::new();
+    for hook in settings.hooks.iter().chain(session.hooks.iter()) {
+        hook.validate().context("Invalid hook")?;
+        if hook.applies(event, command, session) {
+            hooks.push(hook);
+        }
+    }
+    Ok(hooks)
+}
+
+fn hook_command(
+    hook: &Hook,
+    session: &Session,
+    event: HookEvent,
+    command: &str,
+    exit_code: Option<i32>,
+) -> Result<Command> {
+    let mut cmd = match (&hook.run, &hook.plugin) {
+        (Some(run), _) => {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(run);
+            cmd
+        }
+        (None, Some(plugin)) => Command::new(plugin_path(plugin)?),
+        (None, None) => bail!("A hook needs either a `run` command or a `plugin`"),
+    };
+    let event = match event {
+        HookEvent::Pre => "pre",
+        HookEvent::Post => "post",
+    };
+    cmd.env("DEVLG_HOOK", event)
+        .env("DEVLG_COMMAND", command)
+        .env("DEVLG_SESSION", &session.name)
+        .env("DEVLG_HOST", &session.host)
+        .env("DEVLG_USER", &session.user)
+        .env("DEVLG_PORT", session.port.to_string());
+    if let Some(code) = exit_code {
+        cmd.env("DEVLG_EXIT_CODE", code.to_string());
+    }
+    Ok(cmd)
+}
+
+/// Runs a hook with the session, without its secrets, as JSON on stdin.
+fn run(cmd: &mut Command, session: &Session) -> Result<ExitStatus> {
+    let mut session = session.clone();
+    session.strip_secrets();
+    let json = serde_json::to_string(&session)?;
+
+    Ok(runner::pipe(cmd, json.as_bytes())?.status)
+}
+
+/// Path of a plugin, in the plugins directory when given as a bare name.
+fn plugin_path(plugin: &std::path::Path) -> Result<PathBuf> {
+    if plugin.components().count() > 1 {
+        return Ok(keys::expand_home(plugin));
+    }
+    Ok(plugins_dir()?.join(plugin))
+}
+
+/// `~/.config/devlg/plugins`, next to the config.
+pub fn plugins_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir().context("Failed to get home directory")?;
+    Ok(home.join(".config").join("devlg").join("plugins"))
+}
+
+fn describe(hook: &Hook) -> String {
+    match (&hook.run, &hook.plugin) {
+        (Some(run), _) => run.clone(),
+        (None, Some(plugin)) => plugin.display().to_string(),
+        (None, None) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::AuthType;
+    use std::cell::Cell;
+    use std::collections::HashSet;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn hook(on: HookEvent, run: &str) -> Hook {
+        Hook {
+            on,
+            run: Some(run.to_string()),
+            plugin: None,
+            commands: Vec::new(),
+            tags: HashSet::new(),
+        }
+    }
+
+    #[test]
+    fn test_hooks() -> Result<()> {
+        let dir = tempdir()?;
+        let log = dir.path().join("log");
+        let log = log.display();
+        let mut session = Session::new(
+            "web".to_string(),
+            "example.com".to_string(),
+            "deploy".to_string(),
+            22,
+            AuthType::Password,
+            None,
+            Some("s3cret".to_string()),
+            Some(HashSet::from(["prod".to_string()])),
+        );
+        session.hooks.push(hook(
+            HookEvent::Post,
+            &format!("echo post $DEVLG_COMMAND $DEVLG_EXIT_CODE >> {}", log),
+        ));
+        let mut settings = Settings::default();
+        settings.hooks.push(hook(
+            HookEvent::Pre,
+            &format!("echo pre $DEVLG_SESSION $(cat) >> {}", log),
+        ));
+        let mut staging = hook(HookEvent::Pre, "exit 1");
+        staging.tags.insert("staging".to_string());
+        settings.hooks.push(staging);
+        let mut cp = hook(HookEvent::Pre, "exit 1");
+        cp.commands.push("cp".to_string());
+        settings.hooks.push(cp);
+
+        let connected = Cell::new(false);
+        around(&settings, &[&session], "login", false, || {
+            connected.set(true);
+            Ok(())
+        })?;
+        assert!(connected.get());
+        let output = fs::read_to_string(dir.path().join("log"))?;
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("pre web {\"name\":\"web\""));
+        assert!(!lines[0].contains("s3cret"));
+        assert_eq!(lines[1], "post login 0");
+
+        // a failing pre hook cancels the connection
+        connected.set(false);
+        let result = around(&settings, &[&session], "cp", false, || {
+            connected.set(true);
+            Ok(())
+        });
+        assert!(result.is_err());
+        assert!(!connected.get());
+
+        settings.hooks.push(hook(HookEvent::Pre, ""));
+        assert!(around(&settings, &[&session], "login", true, || Ok(())).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,375 @@
+use crate::models::session::Session;
+use crate::utils::runner;
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Seconds ssh-keyscan waits for the host.
+const SCAN_TIMEOUT_SECS: &str = "5";
+
+/// A public host key as offered by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostKey {
+    /// The key as a known_hosts line
+    pub line: String,
+    /// e.g. `ED25519`
+    pub key_type: String,
+    /// e.g. `SHA256:+DiY3wvvV6TuJJhbpZisF/zLDA0zPMSvHdkr4UvCOqU`
+    pub fingerprint: String,
+}
+
+/// Path of the known_hosts file devlg manages for sessions with pinned host keys.
+pub fn known_hosts_path() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir().context("Failed to get data directory")?;
+    Ok(data_dir.join("devlg").join("known_hosts"))
+}
+
+/// Fetches the host keys currently offered by the server of `session`.
+pub fn scan(session: &Session) -> Result<Vec<HostKey>> {
+    let output =
+        runner::output(&mut scan_command(session)).context("Failed to execute ssh-keyscan")?;
+
+    let lines: Vec<String> = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect();
+    if lines.is_empty() {
+        anyhow::bail!(
+            "No host keys received from {}:{}",
+            session.host,
+            session.port
+        );
+    }
+
+    lines.into_iter().map(fingerprint).collect()
+}
+
+/// The ssh-keyscan command of the host of `session`. Behind jump hosts, ssh-keyscan can't
+/// reach the host, so it runs on the last jump host.
+fn scan_command(session: &Session) -> Command {
+    let mut cmd = match session.proxy_jump() {
+        Some(jump) => {
+            let mut cmd = Command::new("ssh");
+            cmd.args(["-o", "BatchMode=yes"]);
+            let last = match jump.rsplit_once(',') {
+                Some((via, last)) => {
+                    cmd.arg("-J").arg(via);
+                    last
+                }
+                None => jump,
+            };
+            cmd.arg(format!("ssh://{}", last)).arg("ssh-keyscan");
+            cmd
+        }
+        None => Command::new("ssh-keyscan"),
+    };
+    cmd.args(["-T", SCAN_TIMEOUT_SECS, "-p", &session.port.to_string()])
+        .arg(&session.host)
+        .stderr(Stdio::null());
+    cmd
+}
+
+/// Computes the fingerprint of a known_hosts line with ssh-keygen.
+fn fingerprint(line: String) -> Result<HostKey> {
+    let mut cmd = Command::new("ssh-keygen");
+    cmd.args(["-l", "-f", "-"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let output = runner::pipe(&mut cmd, line.as_bytes()).context("Failed to execute ssh-keygen")?;
+
+    // e.g. `256 SHA256:+DiY... [host]:2222 (ED25519)`
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = stdout.split_whitespace().collect();
+    if !output.status.success() || fields.len() < 3 {
+        anyhow::bail!("Failed to compute the fingerprint of host key '{}'", line);
+    }
+
+    Ok(HostKey {
+        key_type: fields[fields.len() - 1]
+            .trim_matches(|c| c == '(' || c == ')')
+            .to_string(),
+        fingerprint: fields[1].to_string(),
+        line,
+    })
+}
+
+/// Replaces the entries of the session's host in the managed known_hosts with `keys`.
+pub fn pin(session: &Session, keys: &[HostKey]) -> Result<()> {
+    let path = known_hosts_path()?;
+    let _lock = lock(&path)?;
+    pin_in(&path, session, keys)
+}
+
+fn pin_in(path: &Path, session: &Session, keys: &[HostKey]) -> Result<()> {
+    forget_in(path, session)?;
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open known hosts file at {:?}", path))?;
+    for key in keys {
+        writeln!(file, "{}", key.line)?;
+    }
+    Ok(())
+}
+
+/// Removes the entries of the session's host from the managed known_hosts.
+pub fn forget(session: &Session) -> Result<()> {
+    let path = known_hosts_path()?;
+    let _lock = lock(&path)?;
+    forget_in(&path, session)
+}
+
+fn forget_in(path: &Path, session: &Session) -> Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let mut cmd = Command::new("ssh-keygen");
+    cmd.arg("-R")
+        .arg(host_pattern(session))
+        .arg("-f")
+        .arg(path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    let status = runner::status(&mut cmd).context("Failed to execute ssh-keygen")?;
+    if !status.success() {
+        anyhow::bail!("ssh-keygen failed with exit code: {}", status);
+    }
+    // ssh-keygen keeps a backup of the previous file
+    let _ = fs::remove_file(path.with_extension("old"));
+    Ok(())
+}
+
+/// Locks the known_hosts at `path` until the returned file is dropped. ssh-keygen rewrites
+/// the whole file, so concurrent changes, e.g. of the threads of `devlg check`, would
+/// otherwise lose entries.
+fn lock(path: &Path) -> Result<File> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory at {:?}", parent))?;
+    }
+    let lock_path = path.with_extension("lock");
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open lock file at {:?}", lock_path))?;
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(io::Error::last_os_error())
+            .with_context(|| format!("Failed to lock {:?}", lock_path));
+    }
+    Ok(file)
+}
+
+/// Makes sure the managed known_hosts has the pinned keys of `session`, e.g. after the
+/// config was synced from another machine. Only keys matching a pinned fingerprint are
+/// added. A dry run leaves the known_hosts as it is.
+pub fn ensure_known(session: &Session) -> Result<()> {
+    if session.host_key_fingerprints.is_empty() || runner::is_dry_run() {
+        return Ok(());
+    }
+    let path = known_hosts_path()?;
+    let _lock = lock(&path)?;
+    if path.exists() {
+        let mut cmd = Command::new("ssh-keygen");
+        cmd.arg("-F")
+            .arg(host_pattern(session))
+            .arg("-f")
+            .arg(&path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        let found = runner::status(&mut cmd).context("Failed to execute ssh-keygen")?;
+        if found.success() {
+            return Ok(());
+        }
+    }
+
+    let keys = pinned_keys(session, scan(session)?);
+    if keys.is_empty() {
+        verify(session)?;
+    }
+    pin_in(&path, session, &keys)
+}
+
+/// The `keys` matching a pinned fingerprint of `session`.
+fn pinned_keys(session: &Session, keys: Vec<HostKey>) -> Vec<HostKey> {
+    keys.into_iter()
+        .filter(|k| session.host_key_fingerprints.contains(&k.fingerprint))
+        .collect()
+}
+
+/// Compares the keys offered by the server with the pinned ones, failing with both sets
+/// of fingerprints if any differs.
+pub fn verify(session: &Session) -> Result<()> {
+    let keys = scan(session)?;
+    let pinned = &session.host_key_fingerprints;
+
+    let matches = keys.iter().all(|k| pinned.contains(&k.fingerprint))
+        && pinned
+            .iter()
+            .all(|fp| keys.iter().any(|k| &k.fingerprint == fp));
+    if matches {
+        return Ok(());
+    }
+
+    let mut message = format!(
+        "HOST KEY MISMATCH for session '{}' ({}:{})! Someone could be eavesdropping, or the host key was changed.\n  pinned:",
+        session.name, session.host, session.port
+    );
+    for fp in pinned {
+        message.push_str(&format!("\n    {}", fp));
+    }
+    message.push_str("\n  offered:");
+    for key in keys.iter() {
+        message.push_str(&format!("\n    {} ({})", key.fingerprint, key.key_type));
+    }
+    message.push_str(&format!(
+        "\nRun `devlg hostkey pin {}` once the new key is confirmed to be legitimate.",
+        session.name
+    ));
+    anyhow::bail!(message)
+}
+
+/// How the host of `session` appears in known_hosts.
+fn host_pattern(session: &Session) -> String {
+    if session.port == 22 {
+        session.host.clone()
+    } else {
+        format!("[{}]:{}", session.host, session.port)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::AuthType;
+    use crate::utils::runner::RecordingRunner;
+    use std::thread;
+    use tempfile::tempdir;
+
+    const KEY: &str = "web.internal ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAICUdNhJ4zkFzYcd3qj3Q4y17MCBkkcXlp2sl2yhzfnat";
+
+    fn session(port: u16) -> Session {
+        Session::new(
+            "web".to_string(),
+            "web.internal".to_string(),
+            "deploy".to_string(),
+            port,
+            AuthType::Key,
+            Some(PathBuf::from("/keys/id_ed25519")),
+            None,
+            None,
+        )
+    }
+
+    #[test]
+    fn test_host_keys() -> Result<()> {
+        assert_eq!(host_pattern(&session(22)), "web.internal");
+        assert_eq!(host_pattern(&session(2222)), "[web.internal]:2222");
+
+        let key = fingerprint(KEY.to_string())?;
+        assert_eq!(key.key_type, "ED25519");
+        assert_eq!(
+            key.fingerprint,
+            "SHA256:gfS2bTiR8XpoEvASVog2Sa4to4m6cTN8mpX8mih4DKs"
+        );
+        assert_eq!(key.line, KEY);
+        assert!(fingerprint("web.internal ssh-ed25519 AAAA".to_string()).is_err());
+
+        let mut session = session(22);
+        session
+            .ssh_options
+            .insert("ProxyJump".to_string(), "admin@gw:2200,bastion".to_string());
+        session.host_key_fingerprints = vec!["SHA256:ed".to_string()];
+        let recorder = RecordingRunner::with_replies(
+            1,
+            &[
+                (
+                    0,
+                    "# web.internal:22 SSH-2.0-OpenSSH_9.6\n\
+                     web.internal ssh-ed25519 AAAAed\n\
+                     web.internal ssh-rsa AAAArsa\n",
+                ),
+                (0, "256 SHA256:ed web.internal (ED25519)\n"),
+                (0, "3072 SHA256:rsa web.internal (RSA)\n"),
+            ],
+        );
+        let keys = runner::with_runner(recorder.clone(), || scan(&session))?;
+        assert_eq!(
+            recorder.commands()[0],
+            "ssh -o BatchMode=yes -J admin@gw:2200 ssh://bastion ssh-keyscan -T 5 -p 22 \
+             web.internal"
+        );
+        assert_eq!(keys.len(), 2);
+        assert_eq!(keys[1].key_type, "RSA");
+
+        // only the pinned keys are trusted
+        let pinned = pinned_keys(&session, keys);
+        assert_eq!(pinned.len(), 1);
+        assert_eq!(pinned[0].line, "web.internal ssh-ed25519 AAAAed");
+
+        // a host offering no pinned key fails loudly
+        let recorder = RecordingRunner::with_replies(
+            1,
+            &[
+                (0, "web.internal ssh-rsa AAAArsa\n"),
+                (0, "3072 SHA256:rsa web.internal (RSA)\n"),
+            ],
+        );
+        let error = runner::with_runner(recorder, || verify(&session)).unwrap_err();
+        assert!(error.to_string().starts_with("HOST KEY MISMATCH"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_pins() -> Result<()> {
+        let dir = tempdir()?;
+        let path = dir.path().join("known_hosts");
+        let key = |port| HostKey {
+            line: format!(
+                "{} {}",
+                host_pattern(&session(port)),
+                KEY.split_once(' ').unwrap().1
+            ),
+            key_type: "ED25519".to_string(),
+            fingerprint: String::new(),
+        };
+        let ports = 2200..2216;
+        let lines: Vec<String> = ports.clone().map(|port| key(port).line).collect();
+        fs::write(&path, lines.join("\n") + "\n")?;
+
+        // each pin rewrites the whole file, none of the others may get lost
+        thread::scope(|scope| {
+            for port in ports {
+                let (path, key) = (&path, &key);
+                scope.spawn(move || {
+                    let _lock = lock(path).unwrap();
+                    pin_in(path, &session(port), &[key(port)]).unwrap();
+                });
+            }
+        });
+        let content = fs::read_to_string(&path)?;
+        assert_eq!(content.lines().count(), 16);
+        assert!(content.contains("[web.internal]:2215 ssh-ed25519"));
+
+        // forget only drops the entries of its host
+        let recorder = RecordingRunner::new(0);
+        runner::with_runner(recorder.clone(), || forget_in(&path, &session(2200)))?;
+        assert_eq!(
+            recorder.commands()[0],
+            format!("ssh-keygen -R '[web.internal]:2200' -f {}", path.display())
+        );
+        forget_in(&path, &session(2200))?;
+        assert_eq!(fs::read_to_string(&path)?.lines().count(), 15);
+        assert!(!dir.path().join("known_hosts.old").exists());
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+use crate::models::session::{AuthType, Session};
+use crate::utils::runner;
+use crate::utils::ssh::{exec_ssh, shell_quote};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Directory of the keypairs generated by devlg.
+pub fn keys_dir() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir().context("Failed to get data directory")?;
+    Ok(data_dir.join("devlg").join("keys"))
+}
+
+/// Path of the private key devlg generates for the session `name`.
+pub fn key_path(name: &str) -> Result<PathBuf> {
+    Ok(keys_dir()?.join(name))
+}
+
+/// Whether `path` is a key generated by devlg, as opposed to one of the user's own.
+pub fn is_managed(path: &Path) -> bool {
+    keys_dir().is_ok_and(|dir| expand_home(path).starts_with(dir))
+}
+
+/// Generates a new ed25519 keypair without passphrase at `path` and `path.pub`.
+pub fn generate(path: &Path, comment: &str) -> Result<()> {
+    if let Some(parent) = path.parent()
+        && !runner::is_dry_run()
+    {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create directory at {:?}", parent))?;
+    }
+
+    let status = runner::status(
+        Command::new("ssh-keygen")
+            .args(["-q", "-t", "ed25519", "-N", "", "-C", comment, "-f"])
+            .arg(path)
+            .stdin(Stdio::null()),
+    )
+    .context("Failed to execute ssh-keygen")?;
+    if !status.success() {
+        anyhow::bail!("ssh-keygen failed with exit code: {}", status);
+    }
+    Ok(())
+}
+
+/// Returns the public key of the private key at `path`, as an authorized_keys line.
+pub fn public_key(path: &Path) -> Result<String> {
+    let path = expand_home(path);
+    let pub_path = PathBuf::from(format!("{}.pub", path.display()));
+    if let Ok(content) = fs::read_to_string(&pub_path) {
+        return Ok(content.trim().to_string());
+    }
+
+    let output = runner::output(
+        Command::new("ssh-keygen")
+            .arg("-y")
+            .arg("-f")
+            .arg(&path)
+            .stdin(Stdio::null()),
+    )
+    .context("Failed to execute ssh-keygen")?;
+    if !output.status.success() {
+        anyhow::bail!("Failed to read the public key of {:?}", path);
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Adds `public_key` to the authorized_keys of the session's user, unless it is already
+/// there.
+pub fn authorize(session: &Session, public_key: &str) -> Result<()> {
+    let key = shell_quote(public_key);
+    let script = format!(
+        "umask 077 && mkdir -p ~/.ssh && touch ~/.ssh/authorized_keys && \
+         {{ grep -qxF {key} ~/.ssh/authorized_keys || echo {key} >> ~/.ssh/authorized_keys; }}"
+    );
+    exec_ssh(session, &[], &script)
+}
+
+/// Removes every authorized_keys entry of `public_key` from the session's user.
+pub fn revoke(session: &Session, public_key: &str) -> Result<()> {
+    // match on the key data alone, the comment may have been edited
+    let blob = public_key
+        .split_whitespace()
+        .nth(1)
+        .context("Invalid public key")?;
+    let script = format!(
+        "umask 077 && {{ grep -vF {blob} ~/.ssh/authorized_keys || true; }} > ~/.ssh/authorized_keys.devlg && \
+         mv ~/.ssh/authorized_keys.devlg ~/.ssh/authorized_keys",
+        blob = shell_quote(blob)
+    );
+    exec_ssh(session, &[], &script)
+}
+
+/// Checks that the session can log in with its private key alone, without any password
+/// or agent key.
+pub fn check_login(session: &Session) -> Result<()> {
+    if session.auth_type != AuthType::Key {
+        anyhow::bail!("Session '{}' does not use key authentication", session.name);
+    }
+    exec_ssh(
+        session,
+        &[
+            "-o",
+            "BatchMode=yes",
+            "-o",
+            "PasswordAuthentication=no",
+            "-o",
+            "IdentitiesOnly=yes",
+        ],
+        "true",
+    )
+    .with_context(|| format!("Failed to log into '{}' with the new key", session.name))
+}
+
+/// Expands a leading `~/`, which is how key paths are usually written.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// The key ssh uses when none is given, as a `~` path.
+pub fn default_key_path() -> PathBuf {
+    let ed25519 = dirs::home_dir().map(|home| home.join(".ssh").join("id_ed25519"));
+    if ed25519.is_some_and(|path| path.exists()) {
+        PathBuf::from("~/.ssh/id_ed25519")
+    } else {
+        PathBuf::from("~/.ssh/id_rsa")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate_and_public_key() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("key");
+
+        generate(&path, "devlg-test")?;
+        let public = public_key(&path)?;
+        assert!(public.starts_with("ssh-ed25519 "));
+        assert!(public.ends_with("devlg-test"));
+
+        // without the .pub file the key is derived from the private key
+        fs::remove_file(dir.path().join("key.pub"))?;
+        let derived = public_key(&path)?;
+        assert_eq!(
+            derived.split_whitespace().nth(1),
+            public.split_whitespace().nth(1)
+        );
+        Ok(())
+    }
+}
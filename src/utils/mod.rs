@@ -1,4 +1,16 @@
 // Utility functions will be added here as needed
 
+pub mod asciicast;
+pub mod check;
+pub mod doctor;
+pub mod hooks;
+pub mod hostkey;
+pub mod keys;
+pub mod protected;
+pub mod pty;
+pub mod runner;
 pub mod scp;
+pub mod secret;
 pub mod ssh;
+pub mod time;
+pub mod totp;
@@ -0,0 +1,57 @@
+//! Guardrails of the protected sessions, e.g. the production ones: connecting to them needs
+//! their name typed in, and the terminal is marked while connected.
+
+use crate::models::session::Session;
+use anyhow::{Result, bail};
+use dialoguer::Input;
+use std::io::{self, IsTerminal, Write};
+
+/// Saves the window title, sets a title of its own and a dark red background.
+const MARK: &str = "\x1b[22;0t\x1b]0;PROTECTED: {name}\x07\x1b]11;#3a0000\x07";
+/// Resets the background and restores the saved window title.
+const UNMARK: &str = "\x1b]111\x07\x1b[23;0t";
+
+/// Asks the user to type the name of `session` before `action` on it.
+pub fn confirm(session: &Session, action: &str) -> Result<()> {
+    if !io::stdin().is_terminal() {
+        bail!(
+            "Session '{}' is protected, its name must be typed in a terminal to {} it",
+            session.name,
+            action
+        );
+    }
+    eprintln!(
+        "\x1b[1;31mSession '{}' ({}@{}) is protected.\x1b[0m",
+        session.name, session.user, session.host
+    );
+    let typed: String = Input::new()
+        .with_prompt(format!("Type its name to {} it", action))
+        .allow_empty(true)
+        .interact_text()?;
+    if typed.trim() != session.name {
+        bail!(
+            "The name didn't match, not connecting to '{}'",
+            session.name
+        );
+    }
+    Ok(())
+}
+
+/// Runs `connect` with the terminal marked as connected to the protected `session`.
+pub fn marked<F>(session: &Session, connect: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()>,
+{
+    let mut stdout = io::stdout();
+    let terminal = stdout.is_terminal();
+    if terminal {
+        let _ = write!(stdout, "{}", MARK.replace("{name}", &session.name));
+        let _ = stdout.flush();
+    }
+    let result = connect();
+    if terminal {
+        let _ = write!(stdout, "{}", UNMARK);
+        let _ = stdout.flush();
+    }
+    result
+}
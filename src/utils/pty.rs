@@ -0,0 +1,195 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, ExitStatus, Stdio};
+
+const STDIN: RawFd = 0;
+const STDOUT: RawFd = 1;
+/// How often the terminal size is checked while nothing happens, in milliseconds.
+const POLL_TIMEOUT_MS: i32 = 200;
+
+/// Receives everything that goes through a pseudo-terminal run by [`run_in_pty`].
+pub trait PtyObserver {
+    /// Called with every chunk the child wrote to the terminal.
+    fn output(&mut self, data: &[u8]) -> Result<()>;
+
+    /// Called when the local terminal was resized.
+    fn resize(&mut self, cols: u16, rows: u16) -> Result<()>;
+
+    /// Input to send to the child as if it was typed, asked after every output.
+    fn input(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+}
+
+/// Returns the size of the local terminal as `(cols, rows)`, if stdout is one.
+pub fn terminal_size() -> Option<(u16, u16)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::ioctl(STDOUT, libc::TIOCGWINSZ, &mut size) };
+    if ret == -1 || size.ws_col == 0 {
+        return None;
+    }
+    Some((size.ws_col, size.ws_row))
+}
+
+/// Runs `cmd` attached to a new pseudo-terminal and proxies the local terminal to it,
+/// like a plain `cmd.status()` would do, while `observer` sees all the output.
+pub fn run_in_pty(mut cmd: Command, observer: &mut dyn PtyObserver) -> Result<ExitStatus> {
+    let (mut cols, mut rows) = terminal_size().unwrap_or((80, 24));
+    let (master, slave) = open_pty(cols, rows)?;
+
+    cmd.stdin(Stdio::from(slave.try_clone()?))
+        .stdout(Stdio::from(slave.try_clone()?))
+        .stderr(Stdio::from(slave));
+    // SAFETY: only async-signal-safe calls between fork and exec
+    unsafe {
+        cmd.pre_exec(|| {
+            if libc::setsid() == -1 || libc::ioctl(STDIN, libc::TIOCSCTTY, 0) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let mut child = cmd.spawn().context("Failed to execute command in a pty")?;
+    // close our copies of the slave so reading the master ends when the child exits
+    drop(cmd);
+
+    let mut master = File::from(master);
+    let _raw_mode = RawMode::enable();
+    let mut stdout = io::stdout();
+    let mut stdin_open = true;
+    let mut buf = [0u8; 8192];
+
+    loop {
+        let mut fds = [
+            libc::pollfd {
+                fd: master.as_raw_fd(),
+                events: libc::POLLIN,
+                revents: 0,
+            },
+            libc::pollfd {
+                fd: if stdin_open { STDIN } else { -1 },
+                events: libc::POLLIN,
+                revents: 0,
+            },
+        ];
+        let ret =
+            unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, POLL_TIMEOUT_MS) };
+        if ret == -1 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err).context("Failed to poll the pty");
+        }
+
+        if let Some((new_cols, new_rows)) = terminal_size()
+            && (new_cols, new_rows) != (cols, rows)
+        {
+            (cols, rows) = (new_cols, new_rows);
+            set_size(master.as_raw_fd(), cols, rows)?;
+            observer.resize(cols, rows)?;
+        }
+
+        if fds[0].revents != 0 {
+            match master.read(&mut buf) {
+                // EIO is how Linux reports the child side being closed
+                Ok(0) | Err(_) => break,
+                Ok(n) => {
+                    stdout.write_all(&buf[..n])?;
+                    stdout.flush()?;
+                    observer.output(&buf[..n])?;
+                    if let Some(input) = observer.input() {
+                        master.write_all(&input)?;
+                    }
+                }
+            }
+        }
+
+        if fds[1].revents != 0 {
+            let n = unsafe { libc::read(STDIN, buf.as_mut_ptr().cast(), buf.len()) };
+            if n <= 0 {
+                stdin_open = false;
+            } else {
+                master.write_all(&buf[..n as usize])?;
+            }
+        }
+    }
+
+    child.wait().context("Failed to wait for command")
+}
+
+fn open_pty(cols: u16, rows: u16) -> Result<(OwnedFd, OwnedFd)> {
+    let mut master: RawFd = -1;
+    let mut slave: RawFd = -1;
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    let ret = unsafe {
+        libc::openpty(
+            &mut master,
+            &mut slave,
+            std::ptr::null_mut(),
+            std::ptr::null(),
+            &size,
+        )
+    };
+    if ret == -1 {
+        return Err(io::Error::last_os_error()).context("Failed to open a pty");
+    }
+    // SAFETY: openpty succeeded, so both descriptors are open and owned by us
+    Ok(unsafe { (OwnedFd::from_raw_fd(master), OwnedFd::from_raw_fd(slave)) })
+}
+
+fn set_size(fd: RawFd, cols: u16, rows: u16) -> Result<()> {
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    if unsafe { libc::ioctl(fd, libc::TIOCSWINSZ, &size) } == -1 {
+        return Err(io::Error::last_os_error()).context("Failed to resize the pty");
+    }
+    Ok(())
+}
+
+/// Puts the local terminal in raw mode, so that every key goes to the child unmodified,
+/// and restores the previous mode when dropped.
+struct RawMode {
+    original: Option<libc::termios>,
+}
+
+impl RawMode {
+    fn enable() -> Self {
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(STDIN, &mut original) } == -1 {
+            // stdin is not a terminal, nothing to do
+            return RawMode { original: None };
+        }
+
+        let mut raw = original;
+        unsafe {
+            libc::cfmakeraw(&mut raw);
+            libc::tcsetattr(STDIN, libc::TCSANOW, &raw);
+        }
+        RawMode {
+            original: Some(original),
+        }
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if let Some(original) = &self.original {
+            unsafe {
+                libc::tcsetattr(STDIN, libc::TCSANOW, original);
+            }
+        }
+    }
+}
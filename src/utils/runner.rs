@@ -0,0 +1,287 @@
+//! Runs the external processes of devlg: ssh, scp, ssh-keygen, git, the editor and the
+//! hooks. The runner of the current thread can be replaced, so tests can check the command
+//! lines without running anything. Threads spawned with [`spawn`] or [`spawn_scoped`] use
+//! the runner of the thread spawning them.
+
+use crate::utils::pty::{self, PtyObserver};
+use std::cell::RefCell;
+#[cfg(test)]
+use std::collections::VecDeque;
+use std::io::{self, Write};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle, Scope, ScopedJoinHandle};
+
+/// Runs commands to completion, like [`Command::status`] and [`Command::output`].
+pub trait Runner: Send + Sync {
+    fn status(&self, cmd: &mut Command) -> io::Result<ExitStatus>;
+    fn output(&self, cmd: &mut Command) -> io::Result<Output>;
+    /// Runs `cmd` with `input` written to its stdin. Its stdout and stderr are inherited
+    /// unless `cmd` pipes them, in which case they are returned.
+    fn pipe(&self, cmd: &mut Command, input: &[u8]) -> io::Result<Output>;
+    /// Runs `cmd` attached to a new pseudo-terminal, see [`pty::run_in_pty`].
+    fn pty(&self, cmd: Command, observer: &mut dyn PtyObserver) -> anyhow::Result<ExitStatus>;
+    /// Whether the commands are only printed, see [`DryRunner`].
+    fn is_dry_run(&self) -> bool {
+        false
+    }
+}
+
+/// Runs the commands for real.
+pub struct SystemRunner;
+
+impl Runner for SystemRunner {
+    fn status(&self, cmd: &mut Command) -> io::Result<ExitStatus> {
+        cmd.status()
+    }
+
+    fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        cmd.output()
+    }
+
+    fn pipe(&self, cmd: &mut Command, input: &[u8]) -> io::Result<Output> {
+        let mut child = cmd.stdin(Stdio::piped()).spawn()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            // a command that doesn't read its stdin closes it early, which is fine
+            match stdin.write_all(input) {
+                Err(e) if e.kind() != io::ErrorKind::BrokenPipe => return Err(e),
+                _ => {}
+            }
+        }
+        child.wait_with_output()
+    }
+
+    fn pty(&self, cmd: Command, observer: &mut dyn PtyObserver) -> anyhow::Result<ExitStatus> {
+        pty::run_in_pty(cmd, observer)
+    }
+}
+
+/// Prints the command lines instead of running them, for `--dry-run`. The commands succeed
+/// without output.
+pub struct DryRunner;
+
+impl Runner for DryRunner {
+    fn status(&self, cmd: &mut Command) -> io::Result<ExitStatus> {
+        Ok(self.output(cmd)?.status)
+    }
+
+    fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        println!("{}", crate::utils::ssh::display_command(cmd));
+        Ok(Output {
+            status: ExitStatus::default(),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        })
+    }
+
+    fn pipe(&self, cmd: &mut Command, _input: &[u8]) -> io::Result<Output> {
+        self.output(cmd)
+    }
+
+    fn pty(&self, mut cmd: Command, _observer: &mut dyn PtyObserver) -> anyhow::Result<ExitStatus> {
+        Ok(self.status(&mut cmd)?)
+    }
+
+    fn is_dry_run(&self) -> bool {
+        true
+    }
+}
+
+thread_local! {
+    static RUNNER: RefCell<Arc<dyn Runner>> = RefCell::new(Arc::new(SystemRunner));
+}
+
+/// Runs `cmd` with the runner of the current thread, returning its exit status.
+pub fn status(cmd: &mut Command) -> io::Result<ExitStatus> {
+    current().status(cmd)
+}
+
+/// Runs `cmd` with the runner of the current thread, capturing its output.
+pub fn output(cmd: &mut Command) -> io::Result<Output> {
+    current().output(cmd)
+}
+
+/// Runs `cmd` with the runner of the current thread, with `input` on its stdin.
+pub fn pipe(cmd: &mut Command, input: &[u8]) -> io::Result<Output> {
+    current().pipe(cmd, input)
+}
+
+/// Runs `cmd` with the runner of the current thread in a pseudo-terminal seen by `observer`.
+pub fn pty(cmd: Command, observer: &mut dyn PtyObserver) -> anyhow::Result<ExitStatus> {
+    current().pty(cmd, observer)
+}
+
+/// Whether the commands of the current thread are only printed.
+pub fn is_dry_run() -> bool {
+    current().is_dry_run()
+}
+
+fn current() -> Arc<dyn Runner> {
+    RUNNER.with(|runner| runner.borrow().clone())
+}
+
+/// Runs `f` with `runner` running the commands of the current thread.
+pub fn with_runner<T>(runner: Arc<dyn Runner>, f: impl FnOnce() -> T) -> T {
+    let previous = RUNNER.with(|current| current.replace(runner));
+    let result = f();
+    RUNNER.with(|current| current.replace(previous));
+    result
+}
+
+/// Runs `f` with the commands printed instead of run when `dry_run` is set.
+pub fn dry_run<T>(dry_run: bool, f: impl FnOnce() -> T) -> T {
+    if dry_run {
+        with_runner(Arc::new(DryRunner), f)
+    } else {
+        f()
+    }
+}
+
+/// Like [`thread::spawn`], with the runner of the current thread.
+pub fn spawn<T, F>(f: F) -> JoinHandle<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let runner = current();
+    thread::spawn(move || with_runner(runner, f))
+}
+
+/// Like [`Scope::spawn`], with the runner of the current thread.
+pub fn spawn_scoped<'scope, T, F>(
+    scope: &'scope Scope<'scope, '_>,
+    f: F,
+) -> ScopedJoinHandle<'scope, T>
+where
+    T: Send + 'scope,
+    F: FnOnce() -> T + Send + 'scope,
+{
+    let runner = current();
+    scope.spawn(move || with_runner(runner, f))
+}
+
+/// Maps `items` with `f` in at most `limit` threads, which use the runner of the current
+/// thread. The results are in the order of `items`.
+pub fn parallel_map<T, R, F>(items: &[T], limit: usize, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(items.len()));
+    thread::scope(|scope| {
+        for _ in 0..limit.clamp(1, items.len().max(1)) {
+            spawn_scoped(scope, || {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(i) else { break };
+                    let result = f(item);
+                    results.lock().unwrap().push((i, result));
+                }
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// Records the command lines instead of running them. The commands exit with the codes and
+/// print the output of `replies` in turn, then exit with `code` without output.
+#[cfg(test)]
+pub struct RecordingRunner {
+    pub code: i32,
+    pub commands: Mutex<Vec<String>>,
+    pub replies: Mutex<VecDeque<(i32, String)>>,
+}
+
+#[cfg(test)]
+impl RecordingRunner {
+    pub fn new(code: i32) -> Arc<Self> {
+        Self::with_replies(code, &[])
+    }
+
+    pub fn with_replies(code: i32, replies: &[(i32, &str)]) -> Arc<Self> {
+        Arc::new(RecordingRunner {
+            code,
+            commands: Mutex::new(Vec::new()),
+            replies: Mutex::new(
+                replies
+                    .iter()
+                    .map(|(code, stdout)| (*code, stdout.to_string()))
+                    .collect(),
+            ),
+        })
+    }
+
+    /// The command lines recorded so far.
+    pub fn commands(&self) -> Vec<String> {
+        self.commands.lock().unwrap().clone()
+    }
+
+    fn exit_status(code: i32) -> ExitStatus {
+        use std::os::unix::process::ExitStatusExt;
+        ExitStatus::from_raw(code << 8)
+    }
+}
+
+#[cfg(test)]
+impl Runner for RecordingRunner {
+    fn status(&self, cmd: &mut Command) -> io::Result<ExitStatus> {
+        Ok(self.output(cmd)?.status)
+    }
+
+    fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        let line = crate::utils::ssh::display_command(cmd);
+        self.commands.lock().unwrap().push(line);
+        let (code, stdout) = self
+            .replies
+            .lock()
+            .unwrap()
+            .pop_front()
+            .unwrap_or((self.code, String::new()));
+        Ok(Output {
+            status: Self::exit_status(code),
+            stdout: stdout.into_bytes(),
+            stderr: Vec::new(),
+        })
+    }
+
+    fn pipe(&self, cmd: &mut Command, _input: &[u8]) -> io::Result<Output> {
+        self.output(cmd)
+    }
+
+    fn pty(&self, mut cmd: Command, _observer: &mut dyn PtyObserver) -> anyhow::Result<ExitStatus> {
+        Ok(self.status(&mut cmd)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parallel_map() {
+        let items: Vec<usize> = (0..20).collect();
+        let running = AtomicUsize::new(0);
+        let most = AtomicUsize::new(0);
+        let recorder = RecordingRunner::new(0);
+        let results = with_runner(recorder.clone(), || {
+            parallel_map(&items, 4, |i| {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(5));
+                let _ = status(&mut Command::new("true"));
+                running.fetch_sub(1, Ordering::SeqCst);
+                i * 2
+            })
+        });
+        assert_eq!(results, (0..20).map(|i| i * 2).collect::<Vec<_>>());
+        assert!(most.load(Ordering::SeqCst) <= 4);
+        assert_eq!(recorder.commands().len(), 20);
+        assert!(parallel_map(&[] as &[usize], 4, |i| *i).is_empty());
+    }
+}
@@ -1,16 +1,23 @@
 use crate::{
     models::session::Session,
-    utils::ssh::{master_ssh_close, master_ssh_create},
+    utils::runner,
+    utils::ssh::{
+        ExitError, display_command, master_close_command, master_ssh_close, master_ssh_command,
+        master_ssh_create, option_args,
+    },
 };
 use anyhow::{Context, Result};
 use std::{path::Path, process::Command};
 
+/// Copies files with scp over a master connection to the session. With `dry_run`, only
+/// prints the ssh and scp command lines.
 pub fn copy_file(
     src_session: Option<&Session>,
     dst_session: Option<&Session>,
     src_path: Vec<&Path>,
     dst_path: &Path,
     recursive: bool,
+    dry_run: bool,
 ) -> Result<()> {
     let mut s_bits = 0;
     let src_uri: Vec<String> = if let Some(session) = src_session {
@@ -47,25 +54,26 @@ pub fn copy_file(
         dst_session.unwrap()
     };
 
-    // first create a master ssh connection
-    let control_path = master_ssh_create(sess).context("Failed to create master SSH connection")?;
-
-    let mut cmd = Command::new("scp");
-    cmd.arg("-o")
-        .arg(format!("ControlPath={}", control_path.display()));
-
-    if recursive {
-        cmd.arg("-r");
+    if dry_run {
+        let (master, control_path) = master_ssh_command(sess)?;
+        let scp = scp_command(sess, &control_path, recursive, &src_uri, &dst_uri);
+        println!("{}", display_command(&master));
+        println!("{}", display_command(&scp));
+        println!("{}", display_command(&master_close_command(sess)?));
+        return Ok(());
     }
 
-    for src in src_uri.clone() {
-        cmd.arg(src);
-    }
-    cmd.arg(dst_uri.clone());
+    // first create a master ssh connection
+    let control_path = master_ssh_create(sess).context("Failed to create master SSH connection")?;
 
-    let status = cmd.status().context("Failed to execute SCP command")?;
+    let mut cmd = scp_command(sess, &control_path, recursive, &src_uri, &dst_uri);
+    let status = runner::status(&mut cmd).context("Failed to execute SCP command")?;
     if !status.success() {
-        anyhow::bail!("SCP command failed with exit code: {}", status);
+        return Err(ExitError {
+            context: "SCP command failed",
+            status,
+        }
+        .into());
     }
 
     master_ssh_close(sess).context("Failed to close master SSH connection")?;
@@ -79,6 +87,28 @@ pub fn copy_file(
     Ok(())
 }
 
+fn scp_command(
+    session: &Session,
+    control_path: &Path,
+    recursive: bool,
+    src_uri: &[String],
+    dst_uri: &str,
+) -> Command {
+    let mut cmd = Command::new("scp");
+    cmd.arg("-o")
+        .arg(format!("ControlPath={}", control_path.display()));
+    // the extra arguments went to the master connection, scp does not take most of them
+    cmd.args(option_args(session));
+
+    if recursive {
+        cmd.arg("-r");
+    }
+
+    cmd.args(src_uri);
+    cmd.arg(dst_uri);
+    cmd
+}
+
 fn generate_scp_uri(session: &Session, path: &Path) -> String {
     let mut uri = String::from("scp://");
     uri.push_str(&session.user);
@@ -0,0 +1,171 @@
+//! Encryption of the TOTP secrets at rest. They are sealed with ChaCha20-Poly1305 under a
+//! key of this machine, kept apart from the config in the data directory and only readable
+//! by its owner. This keeps the secrets out of the config, its copies and backups, but
+//! anyone who can read both the config and the key, e.g. the same user, can still open them.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::cell::OnceCell;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+
+/// Prefix of the sealed secrets, followed by the base64 of the nonce and the ciphertext.
+const PREFIX: &str = "enc:v1:";
+
+const NONCE_LEN: usize = 12;
+
+/// The key sealing the secrets, read or generated on first use.
+pub struct SecretKey {
+    path: PathBuf,
+    key: OnceCell<Key>,
+}
+
+impl SecretKey {
+    /// The key at `path`, or the one of this machine in the data directory.
+    pub fn new(path: Option<PathBuf>) -> Self {
+        SecretKey {
+            path: path.unwrap_or_else(|| Self::get_default_path().unwrap()),
+            key: OnceCell::new(),
+        }
+    }
+
+    /// Encrypts `secret`, leaving it as it is if it is sealed already.
+    pub fn seal(&self, secret: &str) -> Result<String> {
+        if secret.starts_with(PREFIX) {
+            return Ok(secret.to_string());
+        }
+        let cipher = ChaCha20Poly1305::new(self.key()?);
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, secret.as_bytes())
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt a secret"))?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        Ok(format!("{}{}", PREFIX, STANDARD.encode(sealed)))
+    }
+
+    /// Decrypts `value`, which older configs hold in plain text.
+    pub fn open(&self, value: &str) -> Result<String> {
+        let Some(encoded) = value.strip_prefix(PREFIX) else {
+            return Ok(value.to_string());
+        };
+        let sealed = STANDARD
+            .decode(encoded)
+            .ok()
+            .filter(|sealed| sealed.len() > NONCE_LEN)
+            .context("Invalid encrypted secret")?;
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let secret = ChaCha20Poly1305::new(self.key()?)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Failed to decrypt a secret, it was encrypted with another key than {:?}",
+                    self.path
+                )
+            })?;
+        String::from_utf8(secret).context("Invalid encrypted secret")
+    }
+
+    fn key(&self) -> Result<&Key> {
+        if let Some(key) = self.key.get() {
+            return Ok(key);
+        }
+        let key = match self.read()? {
+            Some(key) => key,
+            None => self.generate()?,
+        };
+        Ok(self.key.get_or_init(|| key))
+    }
+
+    fn read(&self) -> Result<Option<Key>> {
+        let content = match fs::read_to_string(&self.path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(e)
+                    .with_context(|| format!("Failed to read secret key at {:?}", self.path));
+            }
+        };
+        STANDARD
+            .decode(content.trim())
+            .ok()
+            .filter(|key| key.len() == 32)
+            .map(|key| Some(*Key::from_slice(&key)))
+            .with_context(|| format!("Invalid secret key at {:?}", self.path))
+    }
+
+    /// Generates the key. It is written aside and linked in place, so that a concurrent run
+    /// either sees the whole key or generates its own, and then the one that won is used.
+    fn generate(&self) -> Result<Key> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create directory at {:?}", parent))?;
+        }
+        let key = ChaCha20Poly1305::generate_key(&mut OsRng);
+        let tmp_path = self
+            .path
+            .with_extension(format!("key.{}.tmp", std::process::id()));
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&tmp_path)
+            .with_context(|| format!("Failed to write secret key at {:?}", tmp_path))?;
+        let result = writeln!(file, "{}", STANDARD.encode(key))
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::hard_link(&tmp_path, &self.path));
+        let _ = fs::remove_file(&tmp_path);
+        match result {
+            Ok(()) => Ok(key),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => self
+                .read()?
+                .with_context(|| format!("Failed to read secret key at {:?}", self.path)),
+            Err(e) => {
+                Err(e).with_context(|| format!("Failed to write secret key at {:?}", self.path))
+            }
+        }
+    }
+
+    fn get_default_path() -> Result<PathBuf> {
+        let data_dir = dirs::data_dir().context("Failed to get data directory")?;
+        Ok(data_dir.join("devlg").join("secret.key"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("keys").join("secret.key");
+        let key = SecretKey::new(Some(path.clone()));
+
+        let sealed = key.seal("JBSWY3DPEHPK3PXP")?;
+        assert!(sealed.starts_with(PREFIX));
+        assert!(!sealed.contains("JBSWY3DPEHPK3PXP"));
+        assert_eq!(key.seal(&sealed)?, sealed);
+        assert_eq!(key.open(&sealed)?, "JBSWY3DPEHPK3PXP");
+        assert_eq!(key.open("JBSWY3DPEHPK3PXP")?, "JBSWY3DPEHPK3PXP");
+
+        use std::os::unix::fs::PermissionsExt;
+        assert_eq!(fs::metadata(&path)?.permissions().mode() & 0o777, 0o600);
+        // the key is read back by the next run
+        assert_eq!(
+            SecretKey::new(Some(path)).open(&sealed)?,
+            "JBSWY3DPEHPK3PXP"
+        );
+
+        let other = SecretKey::new(Some(dir.path().join("other.key")));
+        assert!(other.open(&sealed).is_err());
+        assert!(other.open("enc:v1:AAAA").is_err());
+        Ok(())
+    }
+}
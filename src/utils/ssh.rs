@@ -1,68 +1,492 @@
-use crate::models::session::Session;
+use crate::models::session::{Multiplexer, ReconnectPolicy, Session, Transport};
+use crate::utils::asciicast::{Header, Recorder};
+use crate::utils::pty::PtyObserver;
+use crate::utils::totp::Prompter;
+use crate::utils::{hostkey, keys, pty, runner, time};
 use anyhow::{Context, Ok, Result};
-use std::{path::PathBuf, process::Command};
+use std::{
+    collections::HashMap,
+    os::unix::fs::DirBuilderExt,
+    path::PathBuf,
+    process::{Command, ExitStatus, Output, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Error returned when an ssh or scp process exits unsuccessfully.
+#[derive(Debug, Error)]
+#[error("{context} with exit code: {status}")]
+pub struct ExitError {
+    pub context: &'static str,
+    pub status: ExitStatus,
+}
+
+/// Per-connection options of [`connect_ssh`].
+#[derive(Default)]
+pub struct ConnectOptions {
+    /// Record the terminal into this asciicast file
+    pub record_to: Option<PathBuf>,
+    /// Reconnect policy overriding the one of the session
+    pub reconnect: Option<ReconnectPolicy>,
+    /// One-off remote command run instead of the session's, never reconnected
+    pub command: Option<Vec<String>>,
+    /// Only print the ssh command line instead of running it
+    pub dry_run: bool,
+}
+
+/// Exit code of ssh when the connection itself failed, as opposed to the remote command.
+pub const CONNECTION_ERROR_CODE: i32 = 255;
+/// A connection that lasted this long is considered established, so that dropping it
+/// starts the reconnect attempts over.
+const STABLE_CONNECTION_SECS: u64 = 60;
 
 /// Establishes an SSH connection to the remote server using the system's SSH client.
 ///
 /// This function uses the system's SSH client to establish a connection to the remote server.
-/// It supports both password and key-based authentication.
+/// It supports both password and key-based authentication. If the session has a reconnect
+/// policy, a dropped connection (ssh exiting with 255) is retried with exponential backoff,
+/// while a normal exit of the remote shell ends the session.
 ///
 /// # Arguments
 ///
 /// * `session` - The SSH session configuration
+/// * `options` - Options of this connection only
 ///
 /// # Returns
 ///
 /// * `Ok(())` - If the connection was successful
 /// * `Err(_)` - If the connection failed
-pub fn connect_ssh(session: &Session) -> Result<()> {
-    println!(
-        "Connecting to {}@{}:{}...",
-        session.user, session.host, session.port
-    );
+pub fn connect_ssh(session: &Session, options: &ConnectOptions) -> Result<()> {
+    if options.dry_run {
+        println!("{}", display_command(&login_command(session, options)?));
+        return Ok(());
+    }
+
+    let mut recorder = match &options.record_to {
+        Some(path) => {
+            let (width, height) = pty::terminal_size().unwrap_or((80, 24));
+            let header = Header {
+                version: 2,
+                width,
+                height,
+                timestamp: Some(time::now()),
+                title: Some(session.name.clone()),
+                env: ["TERM", "SHELL"]
+                    .into_iter()
+                    .filter_map(|k| std::env::var(k).ok().map(|v| (k.to_string(), v)))
+                    .collect::<HashMap<_, _>>(),
+            };
+            println!("Recording to {}", path.display());
+            Some(Recorder::create(path, &header)?)
+        }
+        None => None,
+    };
+    if !matches!(session.transport, Transport::Command(_)) {
+        hostkey::ensure_known(session)?;
+    }
+    // rerunning a one-off command after a drop could run it twice, and only ssh exits with
+    // CONNECTION_ERROR_CODE when the connection drops
+    let reconnect = match (&options.command, &session.transport) {
+        (None, Transport::Ssh) => options.reconnect.as_ref().or(session.reconnect.as_ref()),
+        _ => None,
+    };
+
+    let mut attempt = 0;
+    let mut delay = reconnect.map(|r| r.initial_delay_secs).unwrap_or_default();
+    loop {
+        match &session.transport {
+            Transport::Command(template) => eprintln!(
+                "Connecting to {} with {}...",
+                session.host,
+                template.first().map(String::as_str).unwrap_or_default()
+            ),
+            _ => eprintln!(
+                "Connecting to {}@{}:{}...",
+                session.user, session.host, session.port
+            ),
+        }
+
+        let mut cmd = login_command(session, options)?;
+
+        // Execute the SSH command
+        let started = Instant::now();
+        let status = if prompts_totp(session) {
+            let recorder = recorder.as_mut().map(|r| r as &mut dyn PtyObserver);
+            runner::pty(cmd, &mut Prompter::new(session, recorder)?)?
+        } else {
+            match recorder.as_mut() {
+                Some(recorder) => runner::pty(cmd, recorder)?,
+                None => runner::status(&mut cmd).context("Failed to execute SSH command")?,
+            }
+        };
+
+        if status.success() {
+            return Ok(());
+        }
+        check_host_key(session, status)?;
+
+        // only a network drop is worth reconnecting, not a failing remote shell
+        let Some(policy) = reconnect.filter(|_| status.code() == Some(CONNECTION_ERROR_CODE))
+        else {
+            return Err(ExitError {
+                context: "SSH connection failed",
+                status,
+            }
+            .into());
+        };
+
+        if started.elapsed().as_secs() >= STABLE_CONNECTION_SECS {
+            attempt = 0;
+            delay = policy.initial_delay_secs;
+        }
+        if attempt >= policy.max_attempts {
+            return Err(ExitError {
+                context: "SSH connection failed after reconnect attempts",
+                status,
+            }
+            .into());
+        }
+
+        attempt += 1;
+        eprintln!(
+            "Connection lost, reconnecting in {}s (attempt {}/{})...",
+            delay, attempt, policy.max_attempts
+        );
+        thread::sleep(Duration::from_secs(delay));
+        delay = (delay * 2).clamp(1, policy.max_delay_secs.max(1));
+    }
+}
+
+/// Whether the login of the session types the password and one-time code itself.
+fn prompts_totp(session: &Session) -> bool {
+    session.auth_type == crate::models::session::AuthType::Totp
+        && !matches!(session.transport, Transport::Command(_))
+}
+
+/// The ssh command of an interactive login, or of the one-off command of `options`.
+fn login_command(session: &Session, options: &ConnectOptions) -> Result<Command> {
+    match &session.transport {
+        Transport::Ssh => {}
+        Transport::Mosh => return mosh_command(session, options),
+        Transport::Command(template) => return transport_command(template, session, options),
+    }
+    let mut cmd = ssh_command(session, &[])?;
+
+    if let Some(command) = &options.command {
+        // only ask for a tty when there is one, so the output can be piped
+        if unsafe { libc::isatty(0) } == 1 {
+            cmd.arg("-t");
+        }
+        cmd.arg(&session.host).args(command);
+    } else if let Some(remote_command) = remote_command(session) {
+        cmd.arg("-t").arg(&session.host).arg(remote_command);
+    } else {
+        cmd.arg(&session.host);
+    }
+    Ok(cmd)
+}
+
+/// The mosh command of a login, with the ssh command of the session for its bootstrap.
+fn mosh_command(session: &Session, options: &ConnectOptions) -> Result<Command> {
+    let mut cmd = Command::new("mosh");
+    // sshpass takes the password from the environment, to keep it out of the arguments
+    let mut ssh = match session.auth_type {
+        crate::models::session::AuthType::Password => {
+            cmd.env(
+                "SSHPASS",
+                session.password.as_ref().context("Password not found")?,
+            );
+            let mut ssh = Command::new("sshpass");
+            ssh.arg("-e").arg("ssh");
+            ssh
+        }
+        // the prompts of TOTP authentication are answered in the pty of the login
+        crate::models::session::AuthType::Key | crate::models::session::AuthType::Totp => {
+            Command::new("ssh")
+        }
+    };
+    add_ssh_args(&mut ssh, session)?;
+    cmd.arg(format!("--ssh={}", command_line(&ssh)))
+        .arg(&session.host);
+
+    // mosh-server runs the command itself, without a shell
+    if let Some(command) = &options.command {
+        cmd.arg("--").args(command);
+    } else if let Some(remote_command) = remote_command(session) {
+        cmd.args(["--", "sh", "-c"]).arg(remote_command);
+    }
+    Ok(cmd)
+}
+
+/// The command of a login through the command `template` of the session, with the one-off
+/// command of `options` appended.
+fn transport_command(
+    template: &[String],
+    session: &Session,
+    options: &ConnectOptions,
+) -> Result<Command> {
+    let args: Vec<String> = template
+        .iter()
+        .map(|arg| {
+            arg.replace("{host}", &session.host)
+                .replace("{user}", &session.user)
+                .replace("{port}", &session.port.to_string())
+        })
+        .collect();
+    let (program, args) = args
+        .split_first()
+        .context("Transport command cannot be empty")?;
+
+    let mut cmd = Command::new(program);
+    cmd.args(args);
+    if let Some(command) = &options.command {
+        cmd.args(command);
+    }
+    Ok(cmd)
+}
+
+/// Runs `command` on the host of the session without a terminal, with the extra ssh
+/// `options`. These come before the options of the session, as ssh keeps the first value.
+pub fn exec_ssh(session: &Session, options: &[&str], command: &str) -> Result<()> {
+    let mut cmd = ssh_command(session, options)?;
+    cmd.arg(&session.host).arg(command);
+    hostkey::ensure_known(session)?;
+
+    let status = runner::status(&mut cmd).context("Failed to execute SSH command")?;
+    if !status.success() {
+        check_host_key(session, status)?;
+        return Err(ExitError {
+            context: "Remote command failed",
+            status,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Runs `command` on the host of the session like [`exec_ssh`], capturing its output
+/// instead of failing on errors.
+pub fn exec_ssh_output(session: &Session, options: &[&str], command: &str) -> Result<Output> {
+    let mut cmd = ssh_command(session, options)?;
+    cmd.arg(&session.host).arg(command).stdin(Stdio::null());
+    hostkey::ensure_known(session)?;
+    runner::output(&mut cmd).context("Failed to execute SSH command")
+}
+
+/// ssh options for running commands unattended: never prompt, except for the single
+/// password prompt sshpass answers.
+pub fn batch_options(session: &Session) -> [&'static str; 2] {
+    match session.auth_type {
+        // one-time codes can't be answered unattended
+        crate::models::session::AuthType::Key | crate::models::session::AuthType::Totp => {
+            ["-o", "BatchMode=yes"]
+        }
+        crate::models::session::AuthType::Password => ["-o", "NumberOfPasswordPrompts=1"],
+    }
+}
+
+/// Tells a changed host key apart from other connection failures of a session with
+/// pinned host keys, failing with both fingerprints.
+fn check_host_key(session: &Session, status: ExitStatus) -> Result<()> {
+    if session.host_key_fingerprints.is_empty()
+        || status.code() != Some(CONNECTION_ERROR_CODE)
+        || matches!(session.transport, Transport::Command(_))
+    {
+        return Ok(());
+    }
+    // the host may also just be unreachable, in which case the scan fails as well
+    match hostkey::scan(session) {
+        std::result::Result::Ok(_) => hostkey::verify(session),
+        Err(_) => Ok(()),
+    }
+}
+
+/// Builds the ssh (or sshpass) command with the extra `options`, then the authentication,
+/// keepalive and connection options of the session, up to but not including the host.
+fn ssh_command(session: &Session, options: &[&str]) -> Result<Command> {
+    if let Transport::Command(_) = session.transport {
+        anyhow::bail!(
+            "Session '{}' connects with a command of its own, not with ssh",
+            session.name
+        );
+    }
 
     let mut cmd = match session.auth_type {
         crate::models::session::AuthType::Password => {
-            // Use sshpass for password authentication
+            // Use sshpass for password authentication, with the password in the environment
+            // rather than in the arguments, which other users can see in `ps`
             let mut cmd = Command::new("sshpass");
-            cmd.arg("-p")
-                .arg(session.password.as_ref().context("Password not found")?);
-            cmd.arg("ssh");
+            cmd.env(
+                "SSHPASS",
+                session.password.as_ref().context("Password not found")?,
+            );
+            cmd.arg("-e").arg("ssh");
             cmd
         }
         crate::models::session::AuthType::Key => {
             // Use regular ssh for key authentication
             Command::new("ssh")
         }
+        crate::models::session::AuthType::Totp => {
+            // The password and code are typed by a Prompter, see connect_ssh
+            let mut cmd = Command::new("ssh");
+            cmd.arg("-o")
+                .arg("PreferredAuthentications=keyboard-interactive,password");
+            cmd
+        }
     };
+    cmd.args(options);
+    add_ssh_args(&mut cmd, session)?;
+    Ok(cmd)
+}
 
+/// Adds the authentication, keepalive and connection options of the session to the ssh
+/// command `cmd`.
+fn add_ssh_args(cmd: &mut Command, session: &Session) -> Result<()> {
     // Add port
     cmd.arg("-p").arg(session.port.to_string());
 
     // Add user
     cmd.arg("-l").arg(&session.user);
 
-    // Add option StrictHostKeyChecking=accept-new
-    cmd.arg("-o").arg("StrictHostKeyChecking=accept-new");
+    if !session.host_key_fingerprints.is_empty() {
+        // Only trust the pinned host keys, these come first so the session cannot override them
+        cmd.arg("-o").arg(format!(
+            "UserKnownHostsFile={}",
+            hostkey::known_hosts_path()?.display()
+        ));
+        cmd.arg("-o").arg("StrictHostKeyChecking=yes");
+    } else if !session
+        .ssh_options
+        .keys()
+        .any(|k| k.eq_ignore_ascii_case("StrictHostKeyChecking"))
+    {
+        // Add option StrictHostKeyChecking=accept-new, unless the session sets its own
+        cmd.arg("-o").arg("StrictHostKeyChecking=accept-new");
+    }
+
+    // Add keepalive options
+    if let Some(interval) = session.keepalive_interval {
+        cmd.arg("-o")
+            .arg(format!("ServerAliveInterval={}", interval));
+    }
+    if let Some(count_max) = session.keepalive_count_max {
+        cmd.arg("-o")
+            .arg(format!("ServerAliveCountMax={}", count_max));
+    }
+
+    // Add the environment of the session
+    if !session.env.is_empty() {
+        let vars: Vec<String> = session
+            .env
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, ssh_config_quote(value)))
+            .collect();
+        cmd.arg("-o").arg(format!("SetEnv={}", vars.join(" ")));
+    }
+    for name in session.send_env.iter() {
+        cmd.arg("-o").arg(format!("SendEnv={}", name));
+    }
+
+    // Add the options and arguments of the session
+    cmd.args(option_args(session));
+    cmd.args(&session.extra_args);
 
     // Add identity file if using key authentication
-    if let crate::models::session::AuthType::Key = session.auth_type {
-        if let Some(key_path) = &session.private_key_path {
-            cmd.arg("-i").arg(key_path);
+    if let crate::models::session::AuthType::Key = session.auth_type
+        && let Some(key_path) = &session.private_key_path
+    {
+        cmd.arg("-i").arg(keys::expand_home(key_path));
+    }
+
+    Ok(())
+}
+
+/// The `ssh_options` of the session as `-o Key=Value` arguments.
+pub fn option_args(session: &Session) -> Vec<String> {
+    session
+        .ssh_options
+        .iter()
+        .flat_map(|(key, value)| ["-o".to_string(), format!("{}={}", key, value)])
+        .collect()
+}
+
+/// Remote command of the session: its `remote_command`, run in its multiplexer
+/// session if it has one, creating or reattaching to it.
+fn remote_command(session: &Session) -> Option<String> {
+    let Some(multiplexer) = session.multiplexer else {
+        return session.remote_command.clone();
+    };
+
+    let name = shell_quote(
+        session
+            .multiplexer_session
+            .as_deref()
+            .unwrap_or(&session.name),
+    );
+    let mut command = match multiplexer {
+        Multiplexer::Tmux => format!("tmux new-session -A -s {}", name),
+        Multiplexer::Screen => format!("screen -D -RR {}", name),
+    };
+    if let Some(remote_command) = &session.remote_command {
+        // only used when the multiplexer session is created
+        match multiplexer {
+            Multiplexer::Tmux => command.push(' '),
+            Multiplexer::Screen => command.push_str(" sh -c "),
         }
+        command.push_str(&shell_quote(remote_command));
     }
+    Some(command)
+}
 
-    // Add host
-    cmd.arg(&session.host);
+/// Quotes `s` for an ssh option value if it contains whitespace or quotes.
+fn ssh_config_quote(s: &str) -> String {
+    if s.is_empty() || s.contains(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '\\')) {
+        format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        s.to_string()
+    }
+}
 
-    // Execute the SSH command
-    let status = cmd.status().context("Failed to execute SSH command")?;
+/// The command line of `cmd` as it could be typed in a shell, with the password given to
+/// sshpass masked.
+pub fn display_command(cmd: &Command) -> String {
+    let password = cmd
+        .get_envs()
+        .any(|(key, value)| key == "SSHPASS" && value.is_some());
+    let line = command_line(cmd);
+    if password {
+        format!("SSHPASS=*** {}", line)
+    } else {
+        line
+    }
+}
 
-    if !status.success() {
-        anyhow::bail!("SSH connection failed with exit code: {}", status);
+/// The command line of `cmd` as it could be typed in a shell.
+fn command_line(cmd: &Command) -> String {
+    std::iter::once(cmd.get_program())
+        .chain(cmd.get_args())
+        .map(|word| shell_word(&word.to_string_lossy()))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// `s` as a word of a shell command line, quoted only when needed.
+fn shell_word(s: &str) -> String {
+    if !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_=.,:/@%+".contains(c))
+    {
+        s.to_string()
+    } else {
+        shell_quote(s)
     }
+}
 
-    Ok(())
+/// Quotes `s` as a single word for a POSIX shell.
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
 }
 
 /// Create a master SSH connection to the remote server.
@@ -72,56 +496,52 @@ pub fn connect_ssh(session: &Session) -> Result<()> {
 /// -N: do not execute a remote command
 /// -o StrictHostKeyChecking=accept-new: accept new host keys
 /// -o ExitOnForwardFailure=yes: exit if forwarding fails
-/// -o ControlPath=~/.ssh/<session_name>
+/// -o ControlPath=<data_dir>/devlg/control/<session_name>
 pub fn master_ssh_create(session: &Session) -> Result<PathBuf> {
-    let mut cmd = match session.auth_type {
-        crate::models::session::AuthType::Password => {
-            // Use sshpass for password authentication
-            let mut cmd = Command::new("sshpass");
-            cmd.arg("-p")
-                .arg(session.password.as_ref().context("Password not found")?);
-            cmd.arg("ssh");
-            cmd
-        }
-        crate::models::session::AuthType::Key => {
-            // Use regular ssh for key authentication
-            Command::new("ssh")
+    let (mut cmd, control_path) = master_ssh_command(session)?;
+    hostkey::ensure_known(session)?;
+    // the socket gives access to the connection, only its owner may reach it
+    if let Some(parent) = control_path.parent()
+        && !parent.exists()
+    {
+        std::fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(parent)
+            .with_context(|| format!("Failed to create control directory at {:?}", parent))?;
+    }
+
+    // Execute the SSH command
+    let status = runner::status(&mut cmd).context("Failed to execute SSH command")?;
+
+    if !status.success() {
+        check_host_key(session, status)?;
+        return Err(ExitError {
+            context: "SSH connection failed",
+            status,
         }
-    };
+        .into());
+    }
+
+    Ok(control_path)
+}
+
+/// The ssh command of [`master_ssh_create`], with the control path of the connection.
+pub fn master_ssh_command(session: &Session) -> Result<(Command, PathBuf)> {
+    let control_path = control_path(session)?;
+    let mut cmd = ssh_command(session, &[])?;
 
     cmd.arg("-M")
         .arg("-fN")
         .arg("-o")
-        .arg("StrictHostKeyChecking=accept-new")
-        .arg("-o")
         .arg("ExitOnForwardFailure=yes")
         .arg("-o")
-        .arg(format!("ControlPath=~/.ssh/{}", session.name));
-
-    // Add port
-    cmd.arg("-p").arg(session.port.to_string());
-
-    // Add user
-    cmd.arg("-l").arg(&session.user);
-
-    // Add identity file if using key authentication
-    if let crate::models::session::AuthType::Key = session.auth_type {
-        if let Some(key_path) = &session.private_key_path {
-            cmd.arg("-i").arg(key_path);
-        }
-    }
+        .arg(format!("ControlPath={}", control_path.display()));
 
     // Add host
     cmd.arg(&session.host);
 
-    // Execute the SSH command
-    let status = cmd.status().context("Failed to execute SSH command")?;
-
-    if !status.success() {
-        anyhow::bail!("SSH connection failed with exit code: {}", status);
-    }
-
-    Ok(PathBuf::from(format!("~/.ssh/{}", session.name)))
+    Ok((cmd, control_path))
 }
 
 /// Close the master SSH connection to the remote server.
@@ -129,33 +549,44 @@ pub fn master_ssh_create(session: &Session) -> Result<PathBuf> {
 /// -O: exit
 /// -S: control path
 pub fn master_ssh_close(session: &Session) -> Result<()> {
-    let control_path = PathBuf::from(format!("~/.ssh/{}", session.name));
-    // FIXME: PathBuf.exists() is not working for the socket file, why?
-    // if !control_path.exists() {
-    //     println!("Control path does not exist: {}", control_path.display());
-    //     return Ok(());
-    // }
-
-    let mut cmd = Command::new("ssh");
-    cmd.arg("-S")
-        .arg(control_path.to_string_lossy().to_string())
-        .arg("-O")
-        .arg("exit")
-        .arg(format!(
-            "{}@{}:{}",
-            session.user, session.host, session.port
-        ));
+    let mut cmd = master_close_command(session)?;
 
     // TODO: redirect stderr to /dev/null
-    let status = cmd.status().context("Failed to execute SSH command")?;
+    let status = runner::status(&mut cmd).context("Failed to execute SSH command")?;
 
     if !status.success() {
-        anyhow::bail!("SSH connection failed with exit code: {}", status);
+        return Err(ExitError {
+            context: "SSH connection failed",
+            status,
+        }
+        .into());
     }
 
     Ok(())
 }
 
+/// The ssh command of [`master_ssh_close`].
+pub fn master_close_command(session: &Session) -> Result<Command> {
+    let mut cmd = Command::new("ssh");
+    cmd.arg("-S")
+        .arg(control_path(session)?)
+        .arg("-O")
+        .arg("exit")
+        .arg("-p")
+        .arg(session.port.to_string())
+        .arg("-l")
+        .arg(&session.user)
+        .arg(&session.host);
+    Ok(cmd)
+}
+
+/// The control socket of the master connection of the session. It is an absolute path: ssh
+/// never sees a shell, so a `~` would be taken literally by some of the commands.
+fn control_path(session: &Session) -> Result<PathBuf> {
+    let data_dir = dirs::data_dir().context("Failed to get data directory")?;
+    Ok(data_dir.join("devlg").join("control").join(&session.name))
+}
+
 /// Establishes an SSH connection to the remote server using the ssh2 crate.
 ///
 /// This function uses the ssh2 crate to establish a connection to the remote server.
@@ -177,5 +608,236 @@ pub fn master_ssh_close(session: &Session) -> Result<()> {
 pub fn connect_ssh2(session: &Session) -> Result<()> {
     // TODO: Implement SSH connection using ssh2 crate
     println!("SSH2 connection not yet implemented. Using system SSH client instead.");
-    connect_ssh(session)
+    connect_ssh(session, &ConnectOptions::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::AuthType;
+    use crate::utils::runner::RecordingRunner;
+    use crate::utils::scp;
+    use std::path::Path;
+
+    #[test]
+    fn test_display_command() -> Result<()> {
+        let mut session = Session::new(
+            "web".to_string(),
+            "example.com".to_string(),
+            "deploy".to_string(),
+            2222,
+            AuthType::Password,
+            None,
+            Some("-p s3cret".to_string()),
+            None,
+        );
+        session.remote_command = Some("cd /srv && bash".to_string());
+
+        let line = display_command(&login_command(&session, &ConnectOptions::default())?);
+        assert!(!line.contains("s3cret"));
+        assert_eq!(
+            line,
+            "SSHPASS=*** sshpass -e ssh -p 2222 -l deploy -o StrictHostKeyChecking=accept-new \
+             -t example.com 'cd /srv && bash'"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_transports() -> Result<()> {
+        let mut session: Session = toml::from_str(
+            r#"
+            name = "pod"
+            host = "web-0"
+            user = "app"
+            port = 22
+            auth_type = "password"
+            transport = { command = ["kubectl", "exec", "-it", "{host}", "--"] }
+            "#,
+        )?;
+        session.validate()?;
+        let options = ConnectOptions {
+            command: Some(vec!["su".to_string(), "{user}".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            display_command(&login_command(&session, &options)?),
+            "kubectl exec -it web-0 -- su '{user}'"
+        );
+        assert!(exec_ssh(&session, &[], "true").is_err());
+
+        session.transport = Transport::Mosh;
+        session.password = Some("s3cret".to_string());
+        session.port = 2222;
+        session
+            .ssh_options
+            .insert("ProxyJump".to_string(), "bastion".to_string());
+        let cmd = login_command(&session, &ConnectOptions::default())?;
+        assert_eq!(
+            display_command(&cmd),
+            "SSHPASS=*** mosh '--ssh=sshpass -e ssh -p 2222 -l app -o StrictHostKeyChecking=accept-new \
+             -o ProxyJump=bastion' web-0"
+        );
+        let env: Vec<_> = cmd.get_envs().collect();
+        assert_eq!(env, vec![("SSHPASS".as_ref(), Some("s3cret".as_ref()))]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_commands_run() -> Result<()> {
+        let session = Session::new(
+            "web".to_string(),
+            "example.com".to_string(),
+            "deploy".to_string(),
+            2222,
+            AuthType::Key,
+            Some(PathBuf::from("/keys/id_ed25519")),
+            None,
+            None,
+        );
+        let control_path = control_path(&session)?;
+        assert!(control_path.is_absolute());
+
+        let recorder = RecordingRunner::new(0);
+        runner::with_runner(recorder.clone(), || -> Result<()> {
+            let options = ConnectOptions {
+                command: Some(vec!["uptime".to_string()]),
+                ..Default::default()
+            };
+            connect_ssh(&session, &options)?;
+            scp::copy_file(
+                None,
+                Some(&session),
+                vec![Path::new("a.txt")],
+                Path::new("/tmp"),
+                false,
+                false,
+            )
+        })?;
+        let commands = recorder.commands();
+        assert_eq!(commands.len(), 4);
+        assert!(commands[0].ends_with(" example.com uptime"));
+        // the control path may need quoting, e.g. on macOS
+        let option = shell_word(&format!("ControlPath={}", control_path.display()));
+        assert!(commands[1].ends_with(&format!(
+            "-M -fN -o ExitOnForwardFailure=yes -o {} example.com",
+            option
+        )));
+        assert_eq!(
+            commands[2],
+            format!("scp -o {} a.txt scp://deploy@example.com:2222//tmp", option)
+        );
+        assert_eq!(
+            commands[3],
+            format!(
+                "ssh -S {} -O exit -p 2222 -l deploy example.com",
+                shell_word(&control_path.to_string_lossy())
+            )
+        );
+
+        // the options of the caller win over the ones of the session
+        let mut batch = session.clone();
+        batch
+            .ssh_options
+            .insert("BatchMode".to_string(), "no".to_string());
+        let recorder = RecordingRunner::new(0);
+        runner::with_runner(recorder.clone(), || {
+            exec_ssh_output(&batch, &batch_options(&batch), "true")
+        })?;
+        let line = &recorder.commands()[0];
+        assert!(line.starts_with("ssh -o BatchMode=yes -p 2222 -l deploy"));
+        assert!(line.contains(" -o BatchMode=no "));
+
+        // the remote exit code is passed on, without reconnecting
+        let recorder = RecordingRunner::new(3);
+        let result = runner::with_runner(recorder.clone(), || {
+            exec_ssh(&session, &batch_options(&session), "false")
+        });
+        let exit = result.unwrap_err().downcast::<ExitError>().unwrap();
+        assert_eq!(exit.status.code(), Some(3));
+        assert_eq!(recorder.commands().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_keepalive_options() -> Result<()> {
+        let session: Session = toml::from_str(
+            r#"
+            name = "web"
+            host = "example.com"
+            user = "deploy"
+            port = 22
+            auth_type = "key"
+            private_key_path = "/keys/id_ed25519"
+            keepalive_interval = 15
+            keepalive_count_max = 3
+            reconnect = { max_attempts = 3 }
+            "#,
+        )?;
+        assert_eq!(
+            session.reconnect,
+            Some(ReconnectPolicy {
+                max_attempts: 3,
+                ..Default::default()
+            })
+        );
+
+        let cmd = ssh_command(&session, &[])?;
+        let args: Vec<_> = cmd.get_args().map(|a| a.to_string_lossy()).collect();
+        assert!(
+            args.windows(2)
+                .any(|w| w == ["-o", "ServerAliveInterval=15"])
+        );
+        assert!(
+            args.windows(2)
+                .any(|w| w == ["-o", "ServerAliveCountMax=3"])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconnect() -> Result<()> {
+        let mut session = Session::new(
+            "web".to_string(),
+            "example.com".to_string(),
+            "deploy".to_string(),
+            22,
+            AuthType::Key,
+            Some(PathBuf::from("/keys/id_ed25519")),
+            None,
+            None,
+        );
+        // one attempt, without waiting for it
+        session.reconnect = Some(ReconnectPolicy {
+            max_attempts: 1,
+            initial_delay_secs: 0,
+            max_delay_secs: 1,
+        });
+        let attempts = |code: i32, options: &ConnectOptions| {
+            let recorder = RecordingRunner::new(code);
+            let result = runner::with_runner(recorder.clone(), || connect_ssh(&session, options));
+            let exit = result.unwrap_err().downcast::<ExitError>().unwrap();
+            assert_eq!(exit.status.code(), Some(code));
+            recorder.commands().len()
+        };
+
+        // a dropped connection is reconnected up to max_attempts times
+        assert_eq!(attempts(255, &ConnectOptions::default()), 2);
+        // a failing remote shell is not a drop
+        assert_eq!(attempts(1, &ConnectOptions::default()), 1);
+        // one-off commands are never rerun
+        let options = ConnectOptions {
+            command: Some(vec!["uptime".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(attempts(255, &options), 1);
+
+        // the login is over once the reconnect succeeds
+        let recorder = RecordingRunner::with_replies(1, &[(255, ""), (0, "")]);
+        runner::with_runner(recorder.clone(), || {
+            connect_ssh(&session, &ConnectOptions::default())
+        })?;
+        assert_eq!(recorder.commands().len(), 2);
+        Ok(())
+    }
 }
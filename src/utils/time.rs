@@ -0,0 +1,29 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Formats a duration in seconds as e.g. `45s`, `12m03s` or `3h05m`.
+pub fn format_duration(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{}s", secs),
+        60..3600 => format!("{}m{:02}s", secs / 60, secs % 60),
+        _ => format!("{}h{:02}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Formats how long ago `timestamp` was, e.g. `5m ago` or `3d ago`.
+pub fn format_age(timestamp: u64) -> String {
+    let age = now().saturating_sub(timestamp);
+    match age {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", age / 60),
+        3600..86400 => format!("{}h ago", age / 3600),
+        _ => format!("{}d ago", age / 86400),
+    }
+}
@@ -0,0 +1,172 @@
+use anyhow::{Context, Result};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::models::session::Session;
+use crate::utils::pty::PtyObserver;
+use crate::utils::time;
+
+/// Seconds each code is valid for, the RFC 6238 default used by authenticator apps.
+pub const STEP_SECS: u64 = 30;
+
+/// The RFC 6238 code of the base32 `secret` at `timestamp`: 6 digits, SHA-1, 30 second
+/// steps, like the authenticator apps.
+pub fn code(secret: &str, timestamp: u64) -> Result<String> {
+    Ok(totp(secret)?.generate(timestamp))
+}
+
+/// Fails if `secret` is not valid base32.
+pub fn validate_secret(secret: &str) -> Result<()> {
+    totp(secret).map(|_| ())
+}
+
+fn totp(secret: &str) -> Result<TOTP> {
+    // secrets are often shown in groups of four letters, in lowercase
+    let secret: String = secret
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>()
+        .to_uppercase();
+    let bytes = Secret::Encoded(secret)
+        .to_bytes()
+        .ok()
+        .filter(|bytes| !bytes.is_empty())
+        .context("Invalid TOTP secret, expected base32")?;
+    // many services still use 80 bit secrets, shorter than RFC 4226 recommends
+    Ok(TOTP::new_unchecked(Algorithm::SHA1, 6, 1, STEP_SECS, bytes))
+}
+
+/// Words of the prompts asking for the one-time code, in lowercase.
+const CODE_PROMPTS: [&str; 6] = [
+    "verification code",
+    "one-time",
+    "otp",
+    "token",
+    "passcode",
+    "authenticator",
+];
+/// Password prompts answered before giving up, like ssh's NumberOfPasswordPrompts.
+const MAX_PASSWORD_ANSWERS: u32 = 3;
+/// Output kept to find the prompts, in bytes.
+const MAX_RECENT_LEN: usize = 512;
+
+/// Answers the keyboard-interactive prompts of a login with the password and the current
+/// one-time code of the session, passing everything on to `inner`. It stops once the code
+/// was sent, so the prompts of the remote shell (e.g. sudo) are left to the user.
+pub struct Prompter<'a> {
+    password: String,
+    secret: String,
+    inner: Option<&'a mut dyn PtyObserver>,
+    recent: String,
+    password_answers: u32,
+    answer: Option<Vec<u8>>,
+    done: bool,
+}
+
+impl<'a> Prompter<'a> {
+    pub fn new(session: &Session, inner: Option<&'a mut dyn PtyObserver>) -> Result<Self> {
+        Ok(Prompter {
+            password: session.password.clone().context("Password not found")?,
+            secret: session
+                .totp_secret
+                .clone()
+                .context("TOTP secret not found")?,
+            inner,
+            recent: String::new(),
+            password_answers: 0,
+            answer: None,
+            done: false,
+        })
+    }
+}
+
+impl PtyObserver for Prompter<'_> {
+    fn output(&mut self, data: &[u8]) -> Result<()> {
+        if let Some(inner) = self.inner.as_mut() {
+            inner.output(data)?;
+        }
+        if self.done {
+            return Ok(());
+        }
+
+        self.recent
+            .push_str(&String::from_utf8_lossy(data).to_lowercase());
+        if self.recent.len() > MAX_RECENT_LEN {
+            let mut start = self.recent.len() - MAX_RECENT_LEN;
+            while !self.recent.is_char_boundary(start) {
+                start += 1;
+            }
+            self.recent.drain(..start);
+        }
+
+        let prompt = self.recent.trim_end();
+        if !prompt.ends_with(':') {
+            return Ok(());
+        }
+        let line = prompt.rsplit(['\n', '\r']).next().unwrap_or_default();
+        if CODE_PROMPTS.iter().any(|word| line.contains(word)) {
+            let code = code(&self.secret, time::now())?;
+            self.answer = Some(format!("{}\n", code).into_bytes());
+            self.done = true;
+        } else if line.contains("password") {
+            self.password_answers += 1;
+            if self.password_answers > MAX_PASSWORD_ANSWERS {
+                // the password is wrong, let the user see it fail
+                self.done = true;
+                return Ok(());
+            }
+            self.answer = Some(format!("{}\n", self.password).into_bytes());
+        } else {
+            return Ok(());
+        }
+        self.recent.clear();
+        Ok(())
+    }
+
+    fn resize(&mut self, cols: u16, rows: u16) -> Result<()> {
+        match self.inner.as_mut() {
+            Some(inner) => inner.resize(cols, rows),
+            None => Ok(()),
+        }
+    }
+
+    fn input(&mut self) -> Option<Vec<u8>> {
+        self.answer.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::session::{AuthType, SessionBuilder};
+
+    #[test]
+    fn test_totp_prompts() -> Result<()> {
+        // the SHA-1 test vector of RFC 6238, "12345678901234567890" in base32
+        let secret = "gezd gnbv gy3t qojq gezd gnbv gy3t qojq";
+        assert_eq!(code(secret, 59)?, "287082");
+        assert_eq!(code(secret, 1111111109)?, "081804");
+        assert!(validate_secret("not base32!").is_err());
+
+        let session = SessionBuilder::new()
+            .name("bastion".to_string())
+            .host("bastion.example.com".to_string())
+            .user("user".to_string())
+            .auth_type(AuthType::Totp)
+            .password(Some("s3cret".to_string()))
+            .totp_secret(Some(secret.to_string()))
+            .build()?;
+        let mut prompter = Prompter::new(&session, None)?;
+        let mut answer = |output: &str| -> Result<Option<String>> {
+            prompter.output(output.as_bytes())?;
+            Ok(prompter.input().map(|a| String::from_utf8(a).unwrap()))
+        };
+
+        assert_eq!(answer("Welcome\r\n(user@bastion) Pass")?, None);
+        assert_eq!(answer("word: ")?, Some("s3cret\n".to_string()));
+        let code = answer("\r\nVerification code: ")?.unwrap();
+        assert_eq!(code.trim().len(), 6);
+        // the prompts of the remote shell are the user's
+        assert_eq!(answer("\r\n[sudo] password for user: ")?, None);
+        Ok(())
+    }
+}
@@ -0,0 +1,585 @@
+//! End-to-end tests running the devlg binary with a config of its own. The tests that connect
+//! start a throwaway sshd on localhost, and are skipped when sshd is not installed. The same
+//! commands also run against fake ssh and scp scripts, which run the remote commands locally.
+//! The config and data directories are the ones of Linux.
+#![cfg(target_os = "linux")]
+
+use std::fs;
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+/// A home of its own for devlg, with its config and data directories.
+struct Env {
+    home: TempDir,
+}
+
+impl Env {
+    fn new() -> Self {
+        Env {
+            home: tempfile::tempdir().unwrap(),
+        }
+    }
+
+    fn path(&self) -> &Path {
+        self.home.path()
+    }
+
+    fn config_path(&self) -> PathBuf {
+        self.path().join(".config").join("devlg.toml")
+    }
+
+    fn write_config(&self, content: &str) {
+        fs::create_dir_all(self.config_path().parent().unwrap()).unwrap();
+        fs::write(self.config_path(), content).unwrap();
+    }
+
+    fn run(&self, args: &[&str]) -> Output {
+        self.command(args).output().unwrap()
+    }
+
+    /// The command running devlg with `args` in this home.
+    fn command(&self, args: &[&str]) -> Command {
+        let mut cmd = Command::new(env!("CARGO_BIN_EXE_devlg"));
+        cmd.args(args)
+            .env("HOME", self.path())
+            .env("PATH", self.search_path())
+            .env("XDG_CONFIG_HOME", self.path().join(".config"))
+            .env("XDG_DATA_HOME", self.path().join(".local/share"))
+            .env("RUST_BACKTRACE", "0")
+            .stdin(Stdio::null());
+        cmd
+    }
+
+    /// The PATH of devlg, looking in the `bin` directory of the home first.
+    fn search_path(&self) -> String {
+        format!(
+            "{}:{}",
+            self.path().join("bin").display(),
+            std::env::var("PATH").unwrap_or_default()
+        )
+    }
+
+    /// Writes an executable script to the `bin` directory of the home.
+    fn write_script(&self, name: &str, content: &str) {
+        let bin = self.path().join("bin");
+        fs::create_dir_all(&bin).unwrap();
+        fs::write(bin.join(name), content).unwrap();
+        fs::set_permissions(bin.join(name), fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// Runs devlg, failing the test if it fails, and returns its stdout.
+    fn devlg(&self, args: &[&str]) -> String {
+        let output = self.run(args);
+        assert!(
+            output.status.success(),
+            "devlg {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+}
+
+#[test]
+fn test_config_changes() {
+    let env = Env::new();
+    let key = env.path().join("id_ed25519");
+    fs::write(&key, "").unwrap();
+    let key = key.to_str().unwrap();
+
+    env.devlg(&[
+        "add",
+        "-n",
+        "web",
+        "-H",
+        "web.example.com",
+        "-k",
+        key,
+        "-t",
+        "prod",
+    ]);
+    env.devlg(&[
+        "add",
+        "-n",
+        "db",
+        "-H",
+        "db.example.com",
+        "-a",
+        "password",
+        "-P",
+        "s3cret",
+    ]);
+    env.devlg(&["modify", "db", "-t", "prod"]);
+    let list = env.devlg(&["list"]);
+    assert!(list.contains("web (root@web.example.com:22)"));
+
+    // a dry run changes nothing
+    let before = fs::read_to_string(env.config_path()).unwrap();
+    let diff = env.devlg(&["--dry-run", "modify", "web", "-p", "2222"]);
+    assert!(diff.contains("~ session web\n    port: 22 -> 2222"));
+    assert_eq!(fs::read_to_string(env.config_path()).unwrap(), before);
+
+    // bulk deletes need --yes without a terminal
+    assert!(!env.run(&["delete", "-t", "prod"]).status.success());
+    env.devlg(&["delete", "-t", "prod", "--yes"]);
+    assert!(!env.devlg(&["list"]).contains("web"));
+    env.devlg(&["undo"]);
+    assert_eq!(fs::read_to_string(env.config_path()).unwrap(), before);
+    let log = env.devlg(&["log"]);
+    assert!(log.contains("-P ***"));
+    assert!(!log.contains("s3cret"));
+}
+
+#[test]
+fn test_dry_run_commands() {
+    let env = Env::new();
+    env.write_config(
+        r#"templates = []
+
+[[sessions]]
+name = "web"
+host = "web.example.com"
+user = "deploy"
+port = 2222
+auth_type = "password"
+password = "s3cret"
+
+[[sessions]]
+name = "pinned"
+host = "pinned.example.com"
+user = "deploy"
+port = 22
+auth_type = "key"
+private_key_path = "/keys/id_ed25519"
+host_key_fingerprints = ["SHA256:ed"]
+"#,
+    );
+
+    let login = env.devlg(&["login", "web", "--dry-run"]);
+    assert_eq!(
+        login,
+        "SSHPASS=*** sshpass -e ssh -p 2222 -l deploy -o StrictHostKeyChecking=accept-new \
+         web.example.com\n"
+    );
+
+    // pinned host keys are neither scanned nor written
+    let known_hosts = env.path().join(".local/share/devlg/known_hosts");
+    let login = env.devlg(&["login", "pinned", "--dry-run"]);
+    assert_eq!(
+        login,
+        format!(
+            "ssh -p 22 -l deploy -o UserKnownHostsFile={} -o StrictHostKeyChecking=yes \
+             -i /keys/id_ed25519 pinned.example.com\n",
+            known_hosts.display()
+        )
+    );
+    assert!(!known_hosts.exists());
+
+    let cp = env.devlg(&["cp", "a.txt", "/tmp", "--dst", "web", "--dry-run"]);
+    let control_path = env.path().join(".local/share/devlg/control/web");
+    let lines: Vec<&str> = cp.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].ends_with(&format!(
+        "-M -fN -o ExitOnForwardFailure=yes -o ControlPath={} web.example.com",
+        control_path.display()
+    )));
+    assert_eq!(
+        lines[1],
+        format!(
+            "scp -o ControlPath={} a.txt scp://deploy@web.example.com:2222//tmp",
+            control_path.display()
+        )
+    );
+    assert_eq!(
+        lines[2],
+        format!(
+            "ssh -S {} -O exit -p 2222 -l deploy web.example.com",
+            control_path.display()
+        )
+    );
+    assert!(!cp.contains("s3cret"));
+}
+
+#[test]
+fn test_dry_run_changes() {
+    let env = Env::new();
+    let key = env.path().join("id_ed25519");
+    let status = Command::new("ssh-keygen")
+        .args(["-q", "-t", "ed25519", "-N", "", "-f"])
+        .arg(&key)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    env.write_config(&format!(
+        r#"templates = []
+
+[[sessions]]
+name = "web"
+host = "web.example.com"
+user = "deploy"
+port = 2222
+auth_type = "password"
+password = "s3cret"
+
+[[sessions]]
+name = "own"
+host = "own.example.com"
+user = "deploy"
+port = 22
+auth_type = "key"
+private_key_path = "{}"
+host_key_fingerprints = ["SHA256:ed"]
+"#,
+        key.display()
+    ));
+    env.devlg(&["tag", "web", "--action", "add", "--tags", "prod"]);
+    let config = fs::read_to_string(env.config_path()).unwrap();
+    let data = env.path().join(".local/share/devlg");
+    let journal = fs::read_to_string(data.join("journal.json")).unwrap();
+
+    for args in [
+        &["rename", "web", "www"][..],
+        &["clone", "web", "web2", "--host", "web2.example.com"],
+        &["undo"],
+        &["hostkey", "forget", "own"],
+    ] {
+        let output = env.devlg(&[args, &["--dry-run"]].concat());
+        assert!(
+            output.ends_with("Dry run, the config was not saved.\n"),
+            "devlg {}: {}",
+            args.join(" "),
+            output
+        );
+    }
+
+    // the editor runs for real, the edit is only shown
+    let output = env
+        .command(&["edit", "web", "--dry-run"])
+        .env("EDITOR", "sed -i s/web.example.com/www.example.com/")
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("www.example.com"), "{}", stdout);
+    assert!(stdout.ends_with("Dry run, the config was not saved.\n"));
+
+    let generate = env.devlg(&["key", "gen", "web", "--dry-run"]);
+    assert_eq!(
+        generate,
+        format!(
+            "ssh-keygen -q -t ed25519 -N '' -C devlg-web -f {}\n",
+            data.join("keys/web").display()
+        )
+    );
+
+    let deploy = env.devlg(&["key", "deploy", "web", "--dry-run"]);
+    let lines: Vec<&str> = deploy.lines().collect();
+    assert!(lines[0].starts_with("ssh-keygen "));
+    assert!(lines[1].starts_with("SSHPASS=*** sshpass -e ssh -p 2222 -l deploy "));
+    assert!(lines[1].contains("<new key> devlg-web"));
+    assert!(lines[2].contains(" -o IdentitiesOnly=yes "));
+    assert!(!deploy.contains("s3cret"));
+
+    let rotate = env.devlg(&["key", "rotate", "own", "--dry-run"]);
+    assert!(rotate.starts_with("ssh-keygen "));
+    assert!(rotate.contains(&format!(
+        "Would move {}.new to ",
+        data.join("keys/own").display()
+    )));
+
+    let sync = env.path().join("sync");
+    env.devlg(&["sync", "init", sync.to_str().unwrap(), "--dry-run"]);
+    assert!(!sync.exists());
+
+    assert_eq!(fs::read_to_string(env.config_path()).unwrap(), config);
+    assert_eq!(
+        fs::read_to_string(data.join("journal.json")).unwrap(),
+        journal
+    );
+    assert!(!data.join("keys").exists());
+    assert!(!data.join("known_hosts").exists());
+}
+
+#[test]
+fn test_protected_sessions() {
+    let env = Env::new();
+    env.write_config(
+        r#"templates = []
+
+[[sessions]]
+name = "web"
+host = "web.example.com"
+user = "deploy"
+port = 22
+auth_type = "password"
+password = "s3cret"
+tags = ["old"]
+
+[[sessions]]
+name = "db"
+host = "db.example.com"
+user = "deploy"
+port = 22
+auth_type = "password"
+password = "s3cret"
+tags = ["old", "prod"]
+
+[settings]
+protected_tags = ["prod"]
+"#,
+    );
+    let list = env.devlg(&["list"]);
+    let protected: Vec<&str> = list
+        .lines()
+        .filter(|l| l.ends_with(" (protected)"))
+        .collect();
+    assert_eq!(protected.len(), 1);
+    assert!(protected[0].starts_with("2. db "));
+
+    // the name must be typed in a terminal, a dry run connects to nothing
+    let output = env.run(&["login", "db"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Session 'db' is protected"));
+    env.devlg(&["login", "db", "--dry-run"]);
+
+    let output = env.run(&["delete", "-t", "old", "--yes"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--allow-protected"));
+    env.devlg(&["delete", "web"]);
+    env.devlg(&["delete", "-t", "old", "--yes", "--allow-protected"]);
+    assert!(env.devlg(&["list"]).contains("No SSH sessions found."));
+}
+
+/// An sshd on a free port of localhost, accepting a key of its own for the current user.
+struct Sshd {
+    dir: TempDir,
+    port: u16,
+    child: Child,
+}
+
+impl Sshd {
+    fn start() -> Option<Self> {
+        let sshd = ["/usr/sbin/sshd", "/usr/local/sbin/sshd", "/usr/bin/sshd"]
+            .into_iter()
+            .map(PathBuf::from)
+            .find(|path| path.exists())?;
+
+        let dir = tempfile::tempdir().unwrap();
+        for (name, kind) in [("host_key", "ed25519"), ("id_ed25519", "ed25519")] {
+            let status = Command::new("ssh-keygen")
+                .args(["-q", "-t", kind, "-N", "", "-f"])
+                .arg(dir.path().join(name))
+                .status()
+                .unwrap();
+            assert!(status.success());
+        }
+        fs::copy(
+            dir.path().join("id_ed25519.pub"),
+            dir.path().join("authorized_keys"),
+        )
+        .unwrap();
+        fs::set_permissions(dir.path(), fs::Permissions::from_mode(0o700)).unwrap();
+
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = dir.path().join("sshd_config");
+        fs::write(
+            &config,
+            format!(
+                "Port {port}\n\
+                 ListenAddress 127.0.0.1\n\
+                 HostKey {dir}/host_key\n\
+                 AuthorizedKeysFile {dir}/authorized_keys\n\
+                 PidFile {dir}/sshd.pid\n\
+                 PasswordAuthentication no\n\
+                 KbdInteractiveAuthentication no\n\
+                 PermitRootLogin yes\n\
+                 StrictModes no\n\
+                 UsePAM no\n\
+                 LogLevel ERROR\n",
+                dir = dir.path().display()
+            ),
+        )
+        .unwrap();
+
+        let child = Command::new(sshd)
+            .args(["-D", "-e", "-f"])
+            .arg(&config)
+            .stdin(Stdio::null())
+            .spawn()
+            .unwrap();
+        let sshd = Sshd { dir, port, child };
+
+        let started = Instant::now();
+        while TcpStream::connect(("127.0.0.1", port)).is_err() {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "sshd did not start"
+            );
+            thread::sleep(Duration::from_millis(50));
+        }
+        Some(sshd)
+    }
+
+    /// The config of devlg with a session `e2e` for this sshd.
+    fn config(&self) -> String {
+        let user = String::from_utf8(Command::new("id").arg("-un").output().unwrap().stdout)
+            .unwrap()
+            .trim()
+            .to_string();
+        format!(
+            r#"templates = []
+
+[[sessions]]
+name = "e2e"
+host = "127.0.0.1"
+user = "{user}"
+port = {port}
+auth_type = "key"
+private_key_path = "{dir}/id_ed25519"
+
+[settings.ssh_options]
+BatchMode = "yes"
+UserKnownHostsFile = "{dir}/known_hosts"
+"#,
+            port = self.port,
+            dir = self.dir.path().display()
+        )
+    }
+}
+
+impl Drop for Sshd {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn test_login_and_copy() {
+    let Some(sshd) = Sshd::start() else {
+        eprintln!("sshd not found, skipping");
+        return;
+    };
+    let env = Env::new();
+    env.write_config(&sshd.config());
+
+    let output = env.devlg(&["login", "e2e", "--", "echo", "hello"]);
+    assert_eq!(output, "hello\n");
+    let output = env.run(&["login", "e2e", "--", "exit", "3"]);
+    assert_eq!(output.status.code(), Some(3));
+
+    let local = env.path().join("local.txt");
+    let remote = env.path().join("remote.txt");
+    let copied = env.path().join("copied.txt");
+    fs::write(&local, "content").unwrap();
+    env.devlg(&[
+        "cp",
+        local.to_str().unwrap(),
+        remote.to_str().unwrap(),
+        "--dst",
+        "e2e",
+    ]);
+    env.devlg(&[
+        "cp",
+        remote.to_str().unwrap(),
+        copied.to_str().unwrap(),
+        "--src",
+        "e2e",
+    ]);
+    assert_eq!(fs::read_to_string(&copied).unwrap(), "content");
+
+    let history = env.devlg(&["history", "e2e"]);
+    assert!(history.contains("exec"));
+    assert!(history.contains("cp"));
+}
+
+/// Logs its command line to `~/ssh.log`, then runs the command following the host locally.
+const FAKE_SSH: &str = r#"#!/bin/sh
+echo "ssh $*" >> "$HOME/ssh.log"
+while [ $# -gt 0 ]; do
+    case "$1" in
+        -[BbcDEeFIiJLlmOopQRSWw]) shift 2 ;;
+        -*) shift ;;
+        *) break ;;
+    esac
+done
+shift
+[ $# -eq 0 ] && exit 0
+exec sh -c "$*"
+"#;
+
+/// Logs its command line to `~/ssh.log`, then copies locally, dropping the `scp://` prefixes.
+const FAKE_SCP: &str = r#"#!/bin/sh
+echo "scp $*" >> "$HOME/ssh.log"
+while [ $# -gt 2 ]; do shift; done
+exec cp "$(echo "$1" | sed 's|^scp://[^/]*/||')" "$(echo "$2" | sed 's|^scp://[^/]*/||')"
+"#;
+
+#[test]
+fn test_login_and_copy_with_fake_ssh() {
+    let env = Env::new();
+    env.write_script("ssh", FAKE_SSH);
+    env.write_script("scp", FAKE_SCP);
+    let key = env.path().join("id_ed25519");
+    fs::write(&key, "").unwrap();
+    env.write_config(&format!(
+        r#"templates = []
+
+[[sessions]]
+name = "fake"
+host = "fake.example.com"
+user = "deploy"
+port = 2222
+auth_type = "key"
+private_key_path = "{}"
+"#,
+        key.display()
+    ));
+
+    let output = env.devlg(&["login", "fake", "--", "echo", "hello"]);
+    assert_eq!(output, "hello\n");
+    let output = env.run(&["login", "fake", "--", "exit", "3"]);
+    assert_eq!(output.status.code(), Some(3));
+
+    let local = env.path().join("local.txt");
+    let remote = env.path().join("remote.txt");
+    let copied = env.path().join("copied.txt");
+    fs::write(&local, "content").unwrap();
+    env.devlg(&[
+        "cp",
+        local.to_str().unwrap(),
+        remote.to_str().unwrap(),
+        "--dst",
+        "fake",
+    ]);
+    env.devlg(&[
+        "cp",
+        remote.to_str().unwrap(),
+        copied.to_str().unwrap(),
+        "--src",
+        "fake",
+    ]);
+    assert_eq!(fs::read_to_string(&copied).unwrap(), "content");
+
+    let log = fs::read_to_string(env.path().join("ssh.log")).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert!(lines[0].starts_with("ssh -p 2222 -l deploy "));
+    assert!(lines[0].ends_with(&format!("-i {} fake.example.com echo hello", key.display())));
+    assert!(log.contains(&format!(
+        " {} scp://deploy@fake.example.com:2222/{}\n",
+        local.display(),
+        remote.display()
+    )));
+
+    let history = env.devlg(&["history", "fake"]);
+    assert!(history.contains("exec"));
+    assert!(history.contains("cp"));
+}